@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CrashReportSummary } from "./CrashReportSummary";
+
+export interface CrashReport { summary: CrashReportSummary, content: string, }
\ No newline at end of file
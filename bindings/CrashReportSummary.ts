@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CrashReportKind } from "./CrashReportKind";
+
+export interface CrashReportSummary { file_name: string, kind: CrashReportKind, time: bigint, description: string | null, exception: string | null, suspected_mods: Array<string>, java_version: string | null, jvm: string | null, }
\ No newline at end of file
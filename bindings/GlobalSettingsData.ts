@@ -1,3 +1,3 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export interface GlobalSettingsData { core_name: string, safe_mode: boolean, domain: string | null, }
\ No newline at end of file
+export interface GlobalSettingsData { core_name: string, safe_mode: boolean, domain: string | null, timezone: string | null, auto_start_delay: number, }
\ No newline at end of file
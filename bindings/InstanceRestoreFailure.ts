@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameType } from "./GameType";
+import type { InstanceUuid } from "./InstanceUuid";
+
+export interface InstanceRestoreFailure { path: string, uuid: InstanceUuid | null, game_type: GameType | null, error: string, }
\ No newline at end of file
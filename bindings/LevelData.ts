@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SpawnPoint } from "./SpawnPoint";
+
+export interface LevelData { name: string | null, seed: bigint | null, game_mode: string | null, difficulty: string | null, spawn: SpawnPoint | null, data_version: number | null, version_name: string | null, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LineRuleKind } from "./LineRuleKind";
+
+export interface LineRule { kind: LineRuleKind, pattern: string, }
\ No newline at end of file
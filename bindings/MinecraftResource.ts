@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MinecraftResourceKind } from "./MinecraftResourceKind";
+import type { ModMetadata } from "./ModMetadata";
+
+export interface MinecraftResource { file_name: string, kind: MinecraftResourceKind, enabled: boolean, size: bigint, metadata: Array<ModMetadata>, error: string | null, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DependencyKind } from "./DependencyKind";
+
+export interface ModDependency { id: string, version_range: string | null, kind: DependencyKind, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ModLoader } from "./ModLoader";
+
+export type ModIssue = { type: "DuplicateMod", id: string, file_names: Array<string>, } | { type: "MissingDependency", file_name: string, mod_id: string, dependency_id: string, version_range: string | null, } | { type: "DependencyVersionMismatch", file_name: string, mod_id: string, dependency_id: string, version_range: string, found_version: string, } | { type: "Incompatible", file_name: string, mod_id: string, incompatible_id: string, incompatible_file_name: string, } | { type: "WrongLoader", file_name: string, mod_id: string, loader: ModLoader, };
\ No newline at end of file
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ModLoader = "Fabric" | "Forge" | "Bukkit";
\ No newline at end of file
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ModDependency } from "./ModDependency";
+import type { ModLoader } from "./ModLoader";
+
+export interface ModMetadata { loader: ModLoader, id: string, name: string, version: string | null, authors: Array<string>, dependencies: Array<ModDependency>, provides: Array<string>, }
\ No newline at end of file
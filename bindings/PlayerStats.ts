@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface PlayerStats { player_id: string, player_name: string, total_playtime: bigint, last_seen: bigint, session_count: number, online: boolean, }
\ No newline at end of file
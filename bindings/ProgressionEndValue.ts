@@ -2,4 +2,4 @@
 import type { InstanceInfo } from "./InstanceInfo";
 import type { InstanceUuid } from "./InstanceUuid";
 
-export type ProgressionEndValue = { type: "InstanceCreation" } & InstanceInfo | { type: "InstanceDelete", instance_uuid: InstanceUuid, } | { type: "FSOperationCompleted", instance_uuid: InstanceUuid, success: boolean, message: string, };
\ No newline at end of file
+export type ProgressionEndValue = { type: "InstanceCreation" } & InstanceInfo | { type: "InstanceDelete", instance_uuid: InstanceUuid, } | { type: "FSOperationCompleted", instance_uuid: InstanceUuid, success: boolean, message: string, } | { type: "InstanceRestore", instance_uuid: InstanceUuid, backup_name: string, };
\ No newline at end of file
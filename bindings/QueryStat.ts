@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface QueryStat { motd: string, game_type: string, version: string, server_mod: string | null, plugins: Array<string>, map: string, online: number, max: number, players: Array<string>, }
\ No newline at end of file
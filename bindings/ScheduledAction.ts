@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ScheduledAction = { type: "Start" } | { type: "Stop", countdown: boolean, } | { type: "Restart", countdown: boolean, } | { type: "Command", command: string, } | { type: "Macro", name: string, args: Array<string>, };
\ No newline at end of file
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InstanceUuid } from "./InstanceUuid";
+import type { ScheduledAction } from "./ScheduledAction";
+
+export interface ScheduledJob { id: string, instance_uuid: InstanceUuid, cron: string, action: ScheduledAction, enabled: boolean, last_run: bigint | null, }
\ No newline at end of file
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MinecraftPlayer } from "./MinecraftPlayer";
+
+export interface ServerStatus { version: string, protocol: number, online: number, max: number, motd: string, sample: Array<MinecraftPlayer>, latency: number, }
\ No newline at end of file
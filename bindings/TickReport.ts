@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export interface TickReport { tps: number | null, mspt: number | null, lag_warnings: number, ticks_behind: bigint, }
\ No newline at end of file
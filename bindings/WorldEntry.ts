@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LevelData } from "./LevelData";
+
+export interface WorldEntry { level_name: string, name: string, size: bigint, last_played: bigint | null, active: boolean, level: LevelData | null, }
\ No newline at end of file
@@ -8,3 +8,14 @@ CREATE TABLE IF NOT EXISTS ClientEvents (
     caused_by_user_id   TEXT,
     instance_id         TEXT
 );
+
+-- One row per stay of a player on an instance, times are unix timestamps in seconds
+CREATE TABLE IF NOT EXISTS PlayerSessions (
+    id                  INTEGER     PRIMARY KEY     AUTOINCREMENT,
+    instance_id         TEXT        NOT NULL,
+    player_id           TEXT        NOT NULL,
+    player_name         TEXT        NOT NULL,
+    joined_at           BIGINT      NOT NULL,
+    left_at             BIGINT
+);
+CREATE INDEX IF NOT EXISTS PlayerSessionsInstance ON PlayerSessions (instance_id, joined_at);
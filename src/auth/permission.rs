@@ -20,6 +20,12 @@ pub struct UserPermission {
     pub can_read_instance_file: HashSet<InstanceUuid>,
     // unsafe permission, owner exclusive unless explicitly granted
     pub can_write_instance_file: HashSet<InstanceUuid>,
+    #[serde(default)]
+    pub can_manage_instance_backup: HashSet<InstanceUuid>,
+    #[serde(default)]
+    pub can_manage_instance_schedule: HashSet<InstanceUuid>,
+    #[serde(default)]
+    pub can_moderate_instance_players: HashSet<InstanceUuid>,
 
     pub can_create_instance: bool,
     pub can_delete_instance: bool,
@@ -43,6 +49,9 @@ impl UserPermission {
             can_access_instance_macro: HashSet::new(),
             can_read_instance_file: HashSet::new(),
             can_write_instance_file: HashSet::new(),
+            can_manage_instance_backup: HashSet::new(),
+            can_manage_instance_schedule: HashSet::new(),
+            can_moderate_instance_players: HashSet::new(),
             can_create_instance: false,
             can_delete_instance: false,
             can_read_global_file: false,
@@ -160,6 +160,27 @@ impl User {
                         .can_write_instance_file
                         .contains(instance_id)
             }
+            UserAction::ManageBackup(instance_id) => {
+                self.is_admin
+                    || self
+                        .permissions
+                        .can_manage_instance_backup
+                        .contains(instance_id)
+            }
+            UserAction::ManageSchedule(instance_id) => {
+                self.is_admin
+                    || self
+                        .permissions
+                        .can_manage_instance_schedule
+                        .contains(instance_id)
+            }
+            UserAction::ModeratePlayers(instance_id) => {
+                self.is_admin
+                    || self
+                        .permissions
+                        .can_moderate_instance_players
+                        .contains(instance_id)
+            }
             UserAction::AccessMacro(Some(instance_id)) => self
                 .permissions
                 .can_access_instance_macro
@@ -212,6 +233,15 @@ impl User {
                     UserAction::WriteInstanceFile(_) => {
                         eyre!("You don't have permission to write this instance's file")
                     }
+                    UserAction::ManageBackup(_) => {
+                        eyre!("You don't have permission to manage this instance's backups")
+                    }
+                    UserAction::ManageSchedule(_) => {
+                        eyre!("You don't have permission to manage this instance's schedule")
+                    }
+                    UserAction::ModeratePlayers(_) => {
+                        eyre!("You don't have permission to moderate this instance's players")
+                    }
                     UserAction::CreateInstance => {
                         eyre!("You don't have permission to create instance")
                     }
@@ -274,6 +304,9 @@ pub enum UserAction {
     AccessMacro(Option<InstanceUuid>),
     ReadInstanceFile(InstanceUuid),
     WriteInstanceFile(InstanceUuid),
+    ManageBackup(InstanceUuid),
+    ManageSchedule(InstanceUuid),
+    ModeratePlayers(InstanceUuid),
 
     // global actions:
     CreateInstance,
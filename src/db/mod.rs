@@ -1,3 +1,4 @@
+pub mod player_sessions;
 pub mod read;
 pub mod types;
 pub mod write;
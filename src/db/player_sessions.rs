@@ -0,0 +1,506 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    error::Error,
+    events::{Event, EventInner, InstanceEvent, InstanceEventInner},
+    prelude::LODESTONE_EPOCH_MIL,
+    traits::{
+        t_player::{Player, TPlayer},
+        t_server::State,
+    },
+    types::InstanceUuid,
+};
+
+use color_eyre::eyre::Context;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqlitePool;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tracing::{error, info, warn};
+use ts_rs::TS;
+
+use super::write::init_client_events_table;
+
+/// Upper bound on the number of points returned by `player_count_series`
+pub const MAX_SERIES_POINTS: i64 = 1000;
+
+/// A single stay of a player on an instance, times are unix timestamps in seconds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerSession {
+    pub player_id: String,
+    pub player_name: String,
+    pub joined_at: i64,
+    /// `None` while the player is still online
+    pub left_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct PlayerStats {
+    pub player_id: String,
+    /// The name the player used in their most recent session
+    pub player_name: String,
+    /// in seconds
+    pub total_playtime: i64,
+    pub last_seen: i64,
+    pub session_count: u32,
+    pub online: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct PeakPlayerCount {
+    pub count: u32,
+    /// When the peak was first reached
+    pub time: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct PlayerCountPoint {
+    pub time: i64,
+    pub count: u32,
+}
+
+pub async fn write_player_sessions_task(
+    mut event_receiver: Receiver<Event>,
+    sqlite_pool: SqlitePool,
+) {
+    if let Err(error) = init_player_sessions_table(&sqlite_pool).await {
+        warn!("Failed to initialize player sessions table: {}", error);
+        return;
+    }
+    // the core may have been killed while players were online
+    match close_stale_sessions(&sqlite_pool).await {
+        Ok(0) => {}
+        Ok(closed) => info!("Closed {} stale player sessions", closed),
+        Err(error) => warn!("Failed to close stale player sessions: {}", error),
+    }
+
+    loop {
+        let event = match event_receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => {
+                warn!("Event buffer lagged");
+                continue;
+            }
+            Err(RecvError::Closed) => {
+                warn!("Event buffer closed");
+                break;
+            }
+        };
+        let (instance_uuid, instance_event_inner) = match event.event_inner {
+            EventInner::InstanceEvent(InstanceEvent {
+                instance_uuid,
+                instance_event_inner,
+                ..
+            }) => (instance_uuid, instance_event_inner),
+            _ => continue,
+        };
+        let now = chrono::Utc::now().timestamp();
+        let result = match instance_event_inner {
+            InstanceEventInner::PlayerChange {
+                players_joined,
+                players_left,
+                ..
+            } => {
+                record_player_change(
+                    &sqlite_pool,
+                    &instance_uuid,
+                    &players_joined,
+                    &players_left,
+                    now,
+                )
+                .await
+            }
+            InstanceEventInner::StateTransition {
+                to: State::Stopped | State::Error,
+            } => close_instance_sessions(&sqlite_pool, &instance_uuid, now)
+                .await
+                .map(|_| ()),
+            _ => continue,
+        };
+        if let Err(e) = result {
+            error!("Error writing player session to database: {}", e);
+        }
+    }
+}
+
+async fn record_player_change(
+    pool: &SqlitePool,
+    instance_uuid: &InstanceUuid,
+    players_joined: &HashSet<Player>,
+    players_left: &HashSet<Player>,
+    now: i64,
+) -> Result<(), Error> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .context("Failed to aquire db connection")?;
+    for player in players_left {
+        let player_id = player.get_id();
+        sqlx::query!(
+            r#"
+UPDATE PlayerSessions
+SET left_at = ?1
+WHERE instance_id = ?2 AND player_id = ?3 AND left_at IS NULL
+            "#,
+            now,
+            instance_uuid,
+            player_id,
+        )
+        .execute(&mut connection)
+        .await
+        .context("Failed to write to DB")?;
+    }
+    for player in players_joined {
+        let player_id = player.get_id();
+        let player_name = player.get_name();
+        sqlx::query!(
+            r#"
+INSERT INTO PlayerSessions
+(instance_id, player_id, player_name, joined_at)
+VALUES
+(?1, ?2, ?3, ?4)
+            "#,
+            instance_uuid,
+            player_id,
+            player_name,
+            now,
+        )
+        .execute(&mut connection)
+        .await
+        .context("Failed to write to DB")?;
+    }
+    Ok(())
+}
+
+/// Close every open session of an instance, returns the number of sessions closed
+async fn close_instance_sessions(
+    pool: &SqlitePool,
+    instance_uuid: &InstanceUuid,
+    left_at: i64,
+) -> Result<u64, Error> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .context("Failed to aquire db connection")?;
+    let closed = sqlx::query!(
+        r#"
+UPDATE PlayerSessions
+SET left_at = MAX(joined_at, ?1)
+WHERE instance_id = ?2 AND left_at IS NULL
+        "#,
+        left_at,
+        instance_uuid,
+    )
+    .execute(&mut connection)
+    .await
+    .context("Failed to write to DB")?
+    .rows_affected();
+    Ok(closed)
+}
+
+/// Sessions left open by a previous run are closed at the last recorded event of their instance,
+/// which is the best guess we have for when the server went down
+async fn close_stale_sessions(pool: &SqlitePool) -> Result<u64, Error> {
+    init_client_events_table(pool).await?;
+    let mut connection = pool
+        .acquire()
+        .await
+        .context("Failed to aquire db connection")?;
+    let instance_ids = sqlx::query!(
+        r#"
+SELECT DISTINCT instance_id as "instance_id!: InstanceUuid"
+FROM PlayerSessions
+WHERE left_at IS NULL
+        "#
+    )
+    .fetch_all(&mut connection)
+    .await
+    .context("Failed to fetch player sessions")?;
+    let mut last_activities = Vec::new();
+    for row in instance_ids {
+        let last_snowflake = sqlx::query!(
+            r#"
+SELECT MAX(snowflake) as "snowflake?: i64"
+FROM ClientEvents
+WHERE instance_id = ?1
+            "#,
+            &row.instance_id,
+        )
+        .fetch_one(&mut connection)
+        .await
+        .context("Failed to fetch events")?
+        .snowflake;
+        // sessions fall back to zero length when the instance has no recorded events
+        let last_activity = last_snowflake
+            .map(|snowflake| ((snowflake >> 22) + LODESTONE_EPOCH_MIL.with(|p| *p)) / 1000)
+            .unwrap_or(0);
+        last_activities.push((row.instance_id, last_activity));
+    }
+    drop(connection);
+    let mut closed = 0;
+    for (instance_uuid, last_activity) in last_activities {
+        closed += close_instance_sessions(pool, &instance_uuid, last_activity).await?;
+    }
+    Ok(closed)
+}
+
+pub async fn init_player_sessions_table(pool: &SqlitePool) -> Result<(), Error> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .context("Failed to aquire db connection")?;
+
+    sqlx::query!(
+        r#"
+        CREATE TABLE IF NOT EXISTS PlayerSessions (
+            id                  INTEGER     PRIMARY KEY     AUTOINCREMENT,
+            instance_id         TEXT        NOT NULL,
+            player_id           TEXT        NOT NULL,
+            player_name         TEXT        NOT NULL,
+            joined_at           BIGINT      NOT NULL,
+            left_at             BIGINT
+        );
+        "#
+    )
+    .execute(&mut connection)
+    .await
+    .context("Failed to create table")?;
+
+    sqlx::query!(
+        r#"
+        CREATE INDEX IF NOT EXISTS PlayerSessionsInstance ON PlayerSessions (instance_id, joined_at);
+        "#
+    )
+    .execute(&mut connection)
+    .await
+    .context("Failed to create index")?;
+
+    Ok(())
+}
+
+/// Fetch the sessions of an instance that overlap with `[start, end]`
+pub async fn get_player_sessions(
+    pool: &SqlitePool,
+    instance_uuid: &InstanceUuid,
+    start: i64,
+    end: i64,
+) -> Result<Vec<PlayerSession>, Error> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .context("Failed to aquire connection to db")?;
+    let rows = sqlx::query!(
+        r#"
+SELECT
+player_id, player_name, joined_at as "joined_at: i64", left_at as "left_at: i64"
+FROM PlayerSessions
+WHERE instance_id = ?1 AND joined_at <= ?2 AND (left_at IS NULL OR left_at >= ?3)
+ORDER BY joined_at"#,
+        instance_uuid,
+        end,
+        start,
+    )
+    .fetch_all(&mut connection)
+    .await
+    .context("Failed to fetch player sessions")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| PlayerSession {
+            player_id: row.player_id,
+            player_name: row.player_name,
+            joined_at: row.joined_at,
+            left_at: row.left_at,
+        })
+        .collect())
+}
+
+/// Aggregate sessions per player, open sessions count up to `now`.
+/// Sorted by total playtime, longest first
+pub fn player_stats(sessions: &[PlayerSession], now: i64) -> Vec<PlayerStats> {
+    let mut stats: HashMap<&str, (PlayerStats, i64)> = HashMap::new();
+    for session in sessions {
+        let left_at = session.left_at.unwrap_or(now).max(session.joined_at);
+        let (entry, latest_join) = stats.entry(session.player_id.as_str()).or_insert_with(|| {
+            (
+                PlayerStats {
+                    player_id: session.player_id.clone(),
+                    player_name: session.player_name.clone(),
+                    total_playtime: 0,
+                    last_seen: left_at,
+                    session_count: 0,
+                    online: false,
+                },
+                session.joined_at,
+            )
+        });
+        entry.total_playtime = entry
+            .total_playtime
+            .saturating_add(left_at.saturating_sub(session.joined_at));
+        entry.last_seen = entry.last_seen.max(left_at);
+        entry.session_count += 1;
+        entry.online |= session.left_at.is_none();
+        if session.joined_at >= *latest_join {
+            *latest_join = session.joined_at;
+            entry.player_name = session.player_name.clone();
+        }
+    }
+    let mut ret: Vec<PlayerStats> = stats.into_values().map(|(stats, _)| stats).collect();
+    ret.sort_by(|a, b| {
+        b.total_playtime
+            .cmp(&a.total_playtime)
+            .then_with(|| a.player_id.cmp(&b.player_id))
+    });
+    ret
+}
+
+/// The highest number of players online at once within `[start, end]`
+pub fn peak_concurrent_players(
+    sessions: &[PlayerSession],
+    start: i64,
+    end: i64,
+) -> Option<PeakPlayerCount> {
+    // (time, delta), a leave sorts before a join at the same instant so reconnects are not counted twice
+    let mut changes: Vec<(i64, i32)> = Vec::new();
+    for session in sessions {
+        let joined_at = session.joined_at.max(start);
+        let left_at = session.left_at.unwrap_or(i64::MAX).min(end);
+        if joined_at > left_at || joined_at > end {
+            continue;
+        }
+        changes.push((joined_at, 1));
+        if left_at < end {
+            changes.push((left_at, -1));
+        }
+    }
+    changes.sort();
+    let mut current: i32 = 0;
+    let mut peak: Option<PeakPlayerCount> = None;
+    for (time, delta) in changes {
+        current += delta;
+        if peak
+            .as_ref()
+            .map_or(true, |peak| current > peak.count as i32)
+        {
+            peak = Some(PeakPlayerCount {
+                count: current as u32,
+                time,
+            });
+        }
+    }
+    peak
+}
+
+/// Number of players online sampled every `interval` seconds from `start` to `end`.
+/// The interval is widened if it would produce more than `MAX_SERIES_POINTS` points
+pub fn player_count_series(
+    sessions: &[PlayerSession],
+    start: i64,
+    end: i64,
+    interval: i64,
+) -> Vec<PlayerCountPoint> {
+    if end < start {
+        return Vec::new();
+    }
+    let min_interval = end.saturating_sub(start) / MAX_SERIES_POINTS + 1;
+    let interval = interval.max(min_interval).max(1);
+    let mut ret = Vec::new();
+    let mut time = start;
+    while time <= end {
+        let count = sessions
+            .iter()
+            .filter(|session| {
+                session.joined_at <= time && session.left_at.map_or(true, |left| left > time)
+            })
+            .count() as u32;
+        ret.push(PlayerCountPoint { time, count });
+        time = match time.checked_add(interval) {
+            Some(time) => time,
+            None => break,
+        };
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(player_id: &str, joined_at: i64, left_at: Option<i64>) -> PlayerSession {
+        PlayerSession {
+            player_id: player_id.to_string(),
+            player_name: player_id.to_uppercase(),
+            joined_at,
+            left_at,
+        }
+    }
+
+    #[test]
+    fn test_player_stats() {
+        let mut sessions = vec![
+            session("a", 0, Some(100)),
+            session("b", 50, Some(60)),
+            session("a", 200, None),
+        ];
+        sessions[2].player_name = "renamed".to_string();
+        let stats = player_stats(&sessions, 250);
+        assert_eq!(
+            stats,
+            vec![
+                PlayerStats {
+                    player_id: "a".to_string(),
+                    player_name: "renamed".to_string(),
+                    total_playtime: 150,
+                    last_seen: 250,
+                    session_count: 2,
+                    online: true,
+                },
+                PlayerStats {
+                    player_id: "b".to_string(),
+                    player_name: "B".to_string(),
+                    total_playtime: 10,
+                    last_seen: 60,
+                    session_count: 1,
+                    online: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_peak_and_series() {
+        let sessions = vec![
+            session("a", 0, Some(100)),
+            session("b", 50, Some(100)),
+            session("c", 100, Some(150)),
+            session("a", 100, None),
+        ];
+        assert_eq!(
+            peak_concurrent_players(&sessions, 0, 1000),
+            Some(PeakPlayerCount { count: 2, time: 50 })
+        );
+        assert_eq!(
+            peak_concurrent_players(&sessions, 120, 1000),
+            Some(PeakPlayerCount {
+                count: 2,
+                time: 120
+            })
+        );
+        assert_eq!(peak_concurrent_players(&[], 0, 1000), None);
+
+        let series = player_count_series(&sessions, 0, 200, 50);
+        assert_eq!(
+            series.iter().map(|p| p.count).collect::<Vec<_>>(),
+            vec![1, 2, 2, 1, 1]
+        );
+        assert_eq!(series.last().unwrap().time, 200);
+        assert!(
+            player_count_series(&sessions, 0, 1_000_000, 1).len() <= MAX_SERIES_POINTS as usize + 1
+        );
+        assert!(player_count_series(&sessions, 10, 0, 1).is_empty());
+        // the widest range must neither overflow nor produce too many points
+        let series = player_count_series(&sessions, i64::MIN, i64::MAX, i64::MAX);
+        assert!(!series.is_empty() && series.len() <= MAX_SERIES_POINTS as usize + 1);
+    }
+}
@@ -9,6 +9,7 @@ use crate::{
     auth::{permission::UserPermission, user_id::UserId},
     macro_executor::MacroPID,
     output_types::ClientEvent,
+    scheduler::ScheduledAction,
     traits::{t_macro::ExitStatus, t_player::Player, t_server::State, InstanceInfo},
     types::{InstanceUuid, Snowflake, TimeRange},
 };
@@ -120,6 +121,19 @@ pub enum InstanceEventInner {
         player: String,
         player_message: String,
     },
+    PlayerDeath {
+        player: String,
+        message: String,
+    },
+    PlayerAdvancement {
+        player: String,
+        advancement: String,
+    },
+    ScheduledJobRun {
+        job_id: String,
+        action: ScheduledAction,
+        error: Option<String>,
+    },
 }
 
 impl AsRef<InstanceEventInner> for InstanceEventInner {
@@ -210,6 +224,10 @@ pub enum ProgressionEndValue {
         success: bool,
         message: String,
     },
+    InstanceRestore {
+        instance_uuid: InstanceUuid,
+        backup_name: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, TS, PartialEq)]
@@ -226,6 +244,10 @@ pub enum ProgressionStartValue {
     InstanceDelete {
         instance_uuid: InstanceUuid,
     },
+    InstanceRestore {
+        instance_uuid: InstanceUuid,
+        backup_name: String,
+    },
 }
 
 // the backend will keep exactly 1 copy of ProgressionStart, and 1 copy of ProgressionUpdate OR ProgressionEnd
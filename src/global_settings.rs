@@ -13,6 +13,12 @@ pub struct GlobalSettingsData {
     pub core_name: String,
     pub safe_mode: bool,
     pub domain: Option<String>,
+    /// IANA time zone the scheduler runs in, e.g. `Europe/Berlin`, `None` uses the system's time zone
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// seconds to wait between auto starting instances
+    #[serde(default)]
+    pub auto_start_delay: u32,
 }
 
 impl Default for GlobalSettingsData {
@@ -21,6 +27,8 @@ impl Default for GlobalSettingsData {
             core_name: format!("{}'s Lodestone Core", whoami::realname()),
             safe_mode: true,
             domain: None,
+            timezone: None,
+            auto_start_delay: 0,
         }
     }
 }
@@ -146,6 +154,41 @@ impl GlobalSettings {
     pub fn domain(&self) -> Option<String> {
         self.global_settings_data.domain.clone()
     }
+
+    pub async fn set_timezone(&mut self, timezone: Option<chrono_tz::Tz>) -> Result<(), Error> {
+        let old_timezone = self.global_settings_data.timezone.clone();
+        self.global_settings_data.timezone = timezone.map(|timezone| timezone.name().to_string());
+        match self.write_to_file().await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                self.global_settings_data.timezone = old_timezone;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn timezone(&self) -> Option<chrono_tz::Tz> {
+        self.global_settings_data
+            .timezone
+            .as_ref()
+            .and_then(|timezone| timezone.parse().ok())
+    }
+
+    pub async fn set_auto_start_delay(&mut self, auto_start_delay: u32) -> Result<(), Error> {
+        let old_auto_start_delay = self.global_settings_data.auto_start_delay;
+        self.global_settings_data.auto_start_delay = auto_start_delay;
+        match self.write_to_file().await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                self.global_settings_data.auto_start_delay = old_auto_start_delay;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn auto_start_delay(&self) -> u32 {
+        self.global_settings_data.auto_start_delay
+    }
 }
 
 impl AsRef<GlobalSettingsData> for GlobalSettings {
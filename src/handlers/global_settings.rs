@@ -111,11 +111,65 @@ pub async fn change_domain(
     Ok(())
 }
 
+pub async fn change_timezone(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Json(timezone): Json<Option<String>>,
+) -> Result<(), Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    if !requester.is_owner {
+        return Err(Error {
+            kind: ErrorKind::PermissionDenied,
+            source: eyre!("Not authorized to change core timezone"),
+        });
+    }
+    let timezone = match timezone {
+        Some(timezone) => Some(timezone.parse::<chrono_tz::Tz>().map_err(|_| Error {
+            kind: ErrorKind::BadRequest,
+            source: eyre!("Unknown time zone {}", timezone),
+        })?),
+        None => None,
+    };
+    state
+        .global_settings
+        .lock()
+        .await
+        .set_timezone(timezone)
+        .await?;
+    Ok(())
+}
+
+pub async fn change_auto_start_delay(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthBearer(token): AuthBearer,
+    Json(auto_start_delay): Json<u32>,
+) -> Result<(), Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    if !requester.is_owner {
+        return Err(Error {
+            kind: ErrorKind::PermissionDenied,
+            source: eyre!("Not authorized to change core auto start delay"),
+        });
+    }
+    state
+        .global_settings
+        .lock()
+        .await
+        .set_auto_start_delay(auto_start_delay)
+        .await?;
+    Ok(())
+}
+
 pub fn get_global_settings_routes(state: AppState) -> Router {
     Router::new()
         .route("/global_settings", get(get_core_settings))
         .route("/global_settings/name", put(change_core_name))
         .route("/global_settings/safe_mode", put(change_core_safe_mode))
         .route("/global_settings/domain", put(change_domain))
+        .route("/global_settings/timezone", put(change_timezone))
+        .route(
+            "/global_settings/auto_start_delay",
+            put(change_auto_start_delay),
+        )
         .with_state(state)
 }
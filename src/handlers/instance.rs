@@ -1,4 +1,4 @@
-use axum::routing::{delete, get, post};
+use axum::routing::{delete, get, post, put};
 use axum::Router;
 use axum::{extract::Path, Json};
 use axum_auth::AuthBearer;
@@ -6,6 +6,7 @@ use axum_auth::AuthBearer;
 use color_eyre::eyre::{eyre, Context};
 use serde::Deserialize;
 use tracing::error;
+use ts_rs::TS;
 
 use crate::auth::user::UserAction;
 use crate::error::{Error, ErrorKind};
@@ -15,12 +16,13 @@ use crate::implementations::generic;
 use crate::traits::t_configurable::GameType;
 
 
+use crate::implementations::minecraft::backup::BackupEntry;
 use crate::implementations::minecraft::MinecraftInstance;
 use crate::prelude::{path_to_instances, GameInstance};
 use crate::traits::t_configurable::manifest::SetupValue;
 use crate::traits::{t_configurable::TConfigurable, t_server::TServer, InstanceInfo, TInstance};
 
-use crate::types::{DotLodestoneConfig, InstanceUuid};
+use crate::types::{DotLodestoneConfig, InstanceRestoreFailure, InstanceUuid};
 use crate::{implementations::minecraft, traits::t_server::State, AppState};
 
 use super::instance_setup_configs::HandlerGameType;
@@ -44,6 +46,24 @@ pub async fn get_instance_list(
     Ok(Json(list_of_configs))
 }
 
+pub async fn get_instance_restore_failures(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<InstanceRestoreFailure>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    // failures without a known uuid can't be tied to an instance permission
+    let failures = state
+        .restore_failures
+        .iter()
+        .filter(|failure| match &failure.uuid {
+            Some(uuid) => requester.can_perform_action(&UserAction::ViewInstance(uuid.clone())),
+            None => requester.is_owner,
+        })
+        .cloned()
+        .collect();
+    Ok(Json(failures))
+}
+
 pub async fn get_instance_info(
     Path(uuid): Path<InstanceUuid>,
     axum::extract::State(state): axum::extract::State<AppState>,
@@ -175,6 +195,9 @@ pub async fn create_minecraft_instance(
             perm.can_view_instance.insert(uuid.clone());
             perm.can_read_instance_file.insert(uuid.clone());
             perm.can_write_instance_file.insert(uuid.clone());
+            perm.can_manage_instance_backup.insert(uuid.clone());
+            perm.can_manage_instance_schedule.insert(uuid.clone());
+            perm.can_moderate_instance_players.insert(uuid.clone());
             // ignore errors since we don't care if the permissions update fails
             let _ = state
                 .users_manager
@@ -302,17 +325,34 @@ pub async fn delete_instance(
                     .map_err(Into::into);
             }
 
-            state
-                .port_manager
-                .lock()
-                .await
-                .deallocate(instance.port().await);
+            let mut port_manager = state.port_manager.lock().await;
+            port_manager.deallocate(instance.port().await);
+            port_manager.set_query_port(&uuid, None);
+            drop(port_manager);
             let instance_path = instance.path().await;
             // if instance is generic
             if let GameInstance::GenericInstance(i) = instance {
                 i.destruct().await;
             };
             drop(instances);
+            if let Err(e) = state
+                .scheduler
+                .lock()
+                .await
+                .delete_instance_jobs(&uuid)
+                .await
+            {
+                error!("Failed to delete scheduled jobs of deleted instance: {}", e);
+            }
+            if let Err(e) = state
+                .startup_manager
+                .lock()
+                .await
+                .remove_config(&uuid)
+                .await
+            {
+                error!("Failed to delete startup config of deleted instance: {}", e);
+            }
             let res = crate::util::fs::remove_dir_all(instance_path).await;
             match &res {
                 Ok(_) => event_broadcaster.send(Event::new_progression_event_end(
@@ -344,9 +384,85 @@ pub async fn delete_instance(
     }
 }
 
+pub async fn list_instance_backups(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<BackupEntry>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ManageBackup(uuid.clone()))?;
+    let instance = state
+        .instances
+        .lock()
+        .await
+        .get(&uuid)
+        .cloned()
+        .ok_or_else(|| Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Instance not found"),
+        })?;
+    match instance {
+        GameInstance::MinecraftInstance(instance) => Ok(Json(instance.list_backups().await?)),
+        GameInstance::GenericInstance(_) => Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("This instance does not support backups"),
+        }),
+    }
+}
+
+#[derive(Deserialize, TS)]
+#[ts(export)]
+pub struct RestoreBackupRequest {
+    /// back up the current state of the instance before restoring
+    pub safety_backup: bool,
+}
+
+pub async fn restore_instance_backup(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, backup_name)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+    Json(request): Json<RestoreBackupRequest>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ManageBackup(uuid.clone()))?;
+    let caused_by = CausedBy::User {
+        user_id: requester.uid.clone(),
+        user_name: requester.username.clone(),
+    };
+    // don't hold the instances lock while stopping the instance and extracting the backup
+    let instance = state
+        .instances
+        .lock()
+        .await
+        .get(&uuid)
+        .cloned()
+        .ok_or_else(|| Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Instance not found"),
+        })?;
+    match instance {
+        GameInstance::MinecraftInstance(mut instance) => {
+            instance
+                .restore_backup(&backup_name, request.safety_backup, caused_by)
+                .await?
+        }
+        GameInstance::GenericInstance(_) => {
+            return Err(Error {
+                kind: ErrorKind::UnsupportedOperation,
+                source: eyre!("This instance does not support backups"),
+            })
+        }
+    }
+    Ok(Json(()))
+}
+
 pub fn get_instance_routes(state: AppState) -> Router {
     Router::new()
         .route("/instance/list", get(get_instance_list))
+        .route(
+            "/instance/restore_failures",
+            get(get_instance_restore_failures),
+        )
         .route(
             "/instance/create/:game_type",
             post(create_minecraft_instance),
@@ -354,5 +470,10 @@ pub fn get_instance_routes(state: AppState) -> Router {
         .route("/instance/create_generic", post(create_generic_instance))
         .route("/instance/:uuid", delete(delete_instance))
         .route("/instance/:uuid/info", get(get_instance_info))
+        .route("/instance/:uuid/backups", get(list_instance_backups))
+        .route(
+            "/instance/:uuid/backups/:backup_name/restore",
+            put(restore_instance_backup),
+        )
         .with_state(state)
 }
@@ -0,0 +1,275 @@
+use axum::{
+    extract::Path,
+    routing::{get, put},
+    Json, Router,
+};
+
+use axum_auth::AuthBearer;
+use serde::Deserialize;
+
+use crate::{
+    auth::user::UserAction,
+    error::Error,
+    implementations::minecraft::access_list::{
+        BannedIpEntry, BannedPlayerEntry, OpEntry, WhitelistEntry, WhitelistSettings,
+    },
+    types::InstanceUuid,
+    AppState,
+};
+
+use super::util::authorize_minecraft_instance;
+
+#[derive(Deserialize)]
+pub struct BanRequest {
+    pub reason: Option<String>,
+}
+
+pub async fn get_whitelist(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<WhitelistEntry>>, Error> {
+    let (instance, _) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::AccessSetting(uuid.clone()),
+    )
+    .await?;
+    Ok(Json(instance.get_whitelist().await?))
+}
+
+pub async fn add_to_whitelist(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::AccessSetting(uuid.clone()),
+    )
+    .await?;
+    instance.add_to_whitelist(&player, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub async fn remove_from_whitelist(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::AccessSetting(uuid.clone()),
+    )
+    .await?;
+    instance.remove_from_whitelist(&player, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub async fn get_whitelist_settings(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<WhitelistSettings>, Error> {
+    let (instance, _) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::AccessSetting(uuid.clone()),
+    )
+    .await?;
+    Ok(Json(instance.get_whitelist_settings().await?))
+}
+
+pub async fn set_whitelist_settings(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(settings): Json<WhitelistSettings>,
+) -> Result<Json<()>, Error> {
+    let (mut instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::AccessSetting(uuid.clone()),
+    )
+    .await?;
+    instance.set_whitelist_settings(settings, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub async fn get_ops(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<OpEntry>>, Error> {
+    let (instance, _) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    Ok(Json(instance.get_ops().await?))
+}
+
+pub async fn add_op(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance.add_op(&player, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub async fn remove_op(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance.remove_op(&player, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub async fn get_banned_players(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<BannedPlayerEntry>>, Error> {
+    let (instance, _) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    Ok(Json(instance.get_banned_players().await?))
+}
+
+pub async fn ban_player(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+    Json(request): Json<BanRequest>,
+) -> Result<Json<()>, Error> {
+    let (instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance
+        .add_ban(&player, request.reason, None, caused_by)
+        .await?;
+    Ok(Json(()))
+}
+
+pub async fn pardon_player(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance.remove_ban(&player, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub async fn get_banned_ips(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<BannedIpEntry>>, Error> {
+    let (instance, _) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    Ok(Json(instance.get_banned_ips().await?))
+}
+
+pub async fn ban_ip(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, ip)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+    Json(request): Json<BanRequest>,
+) -> Result<Json<()>, Error> {
+    let (instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance.ban_ip(&ip, request.reason, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub async fn pardon_ip(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, ip)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (instance, caused_by) = authorize_minecraft_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance.pardon_ip(&ip, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub fn get_instance_access_list_routes(state: AppState) -> Router {
+    Router::new()
+        .route("/instance/:uuid/whitelist", get(get_whitelist))
+        .route(
+            "/instance/:uuid/whitelist/:player",
+            put(add_to_whitelist).delete(remove_from_whitelist),
+        )
+        .route(
+            "/instance/:uuid/whitelist_settings",
+            get(get_whitelist_settings).put(set_whitelist_settings),
+        )
+        .route("/instance/:uuid/ops", get(get_ops))
+        .route("/instance/:uuid/ops/:player", put(add_op).delete(remove_op))
+        .route("/instance/:uuid/bans/players", get(get_banned_players))
+        .route(
+            "/instance/:uuid/bans/players/:player",
+            put(ban_player).delete(pardon_player),
+        )
+        .route("/instance/:uuid/bans/ips", get(get_banned_ips))
+        .route(
+            "/instance/:uuid/bans/ips/:ip",
+            put(ban_ip).delete(pardon_ip),
+        )
+        .with_state(state)
+}
@@ -9,6 +9,9 @@ use color_eyre::eyre::eyre;
 use crate::{
     auth::user::UserAction,
     error::{Error, ErrorKind},
+    implementations::minecraft::{countdown::CountdownConfig, line_parser::LineRule},
+    prelude::GameInstance,
+    startup::StartupConfig,
     traits::t_configurable::{
         manifest::{ConfigurableManifest, ConfigurableValue},
         TConfigurable,
@@ -112,6 +115,225 @@ pub async fn set_instance_description(
     Ok(Json(()))
 }
 
+pub async fn set_instance_backup_period(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(backup_period): Json<Option<u32>>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    state
+        .instances
+        .lock()
+        .await
+        .get_mut(&uuid)
+        .ok_or_else(|| Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Instance not found"),
+        })?
+        .set_backup_period(backup_period)
+        .await?;
+    Ok(Json(()))
+}
+
+pub async fn set_instance_max_backups(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(max_backups): Json<u32>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    match state.instances.lock().await.get(&uuid) {
+        Some(GameInstance::MinecraftInstance(instance)) => {
+            instance.set_max_backups(max_backups).await?
+        }
+        Some(_) => {
+            return Err(Error {
+                kind: ErrorKind::UnsupportedOperation,
+                source: eyre!("This instance does not support backups"),
+            })
+        }
+        None => {
+            return Err(Error {
+                kind: ErrorKind::NotFound,
+                source: eyre!("Instance not found"),
+            })
+        }
+    }
+    Ok(Json(()))
+}
+
+pub async fn set_instance_stop_timeout(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(stop_timeout): Json<u32>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    match state.instances.lock().await.get(&uuid) {
+        Some(GameInstance::MinecraftInstance(instance)) => {
+            instance.set_stop_timeout(stop_timeout).await?
+        }
+        Some(_) => {
+            return Err(Error {
+                kind: ErrorKind::UnsupportedOperation,
+                source: eyre!("This instance does not support setting a stop timeout"),
+            })
+        }
+        None => {
+            return Err(Error {
+                kind: ErrorKind::NotFound,
+                source: eyre!("Instance not found"),
+            })
+        }
+    }
+    Ok(Json(()))
+}
+
+pub async fn set_instance_countdown_config(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(countdown): Json<CountdownConfig>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    match state.instances.lock().await.get(&uuid) {
+        Some(GameInstance::MinecraftInstance(instance)) => {
+            instance.set_countdown_config(countdown).await?
+        }
+        Some(_) => {
+            return Err(Error {
+                kind: ErrorKind::UnsupportedOperation,
+                source: eyre!("This instance does not support countdowns"),
+            })
+        }
+        None => {
+            return Err(Error {
+                kind: ErrorKind::NotFound,
+                source: eyre!("Instance not found"),
+            })
+        }
+    }
+    Ok(Json(()))
+}
+
+pub async fn set_instance_check_mods_on_start(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(check_mods_on_start): Json<bool>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    match state.instances.lock().await.get(&uuid) {
+        Some(GameInstance::MinecraftInstance(instance)) => {
+            instance
+                .set_check_mods_on_start(check_mods_on_start)
+                .await?
+        }
+        Some(_) => {
+            return Err(Error {
+                kind: ErrorKind::UnsupportedOperation,
+                source: eyre!("This instance does not support mod analysis"),
+            })
+        }
+        None => {
+            return Err(Error {
+                kind: ErrorKind::NotFound,
+                source: eyre!("Instance not found"),
+            })
+        }
+    }
+    Ok(Json(()))
+}
+
+pub async fn get_instance_line_rules(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<LineRule>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    match state.instances.lock().await.get(&uuid) {
+        Some(GameInstance::MinecraftInstance(instance)) => {
+            Ok(Json(instance.get_line_rules().await))
+        }
+        Some(_) => Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("This instance does not support console line rules"),
+        }),
+        None => Err(Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Instance not found"),
+        }),
+    }
+}
+
+pub async fn set_instance_line_rules(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(line_rules): Json<Vec<LineRule>>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    match state.instances.lock().await.get(&uuid) {
+        Some(GameInstance::MinecraftInstance(instance)) => {
+            instance.set_line_rules(line_rules).await?
+        }
+        Some(_) => {
+            return Err(Error {
+                kind: ErrorKind::UnsupportedOperation,
+                source: eyre!("This instance does not support console line rules"),
+            })
+        }
+        None => {
+            return Err(Error {
+                kind: ErrorKind::NotFound,
+                source: eyre!("Instance not found"),
+            })
+        }
+    }
+    Ok(Json(()))
+}
+
+pub async fn get_instance_startup_config(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<StartupConfig>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    Ok(Json(state.startup_manager.lock().await.get_config(&uuid)))
+}
+
+pub async fn set_instance_startup_config(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(startup_config): Json<StartupConfig>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessSetting(uuid.clone()))?;
+    if !state.instances.lock().await.contains_key(&uuid) {
+        return Err(Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Instance not found"),
+        });
+    }
+    state
+        .startup_manager
+        .lock()
+        .await
+        .set_config(uuid, startup_config)
+        .await?;
+    Ok(Json(()))
+}
+
 pub async fn change_version(
     axum::extract::State(state): axum::extract::State<AppState>,
     Path((uuid, new_version)): Path<(InstanceUuid, String)>,
@@ -147,5 +369,28 @@ pub fn get_instance_config_routes(state: AppState) -> Router {
         )
         .route("/instance/:uuid/name", put(set_instance_name))
         .route("/instance/:uuid/description", put(set_instance_description))
+        .route(
+            "/instance/:uuid/backup_period",
+            put(set_instance_backup_period),
+        )
+        .route("/instance/:uuid/max_backups", put(set_instance_max_backups))
+        .route(
+            "/instance/:uuid/stop_timeout",
+            put(set_instance_stop_timeout),
+        )
+        .route(
+            "/instance/:uuid/countdown_config",
+            put(set_instance_countdown_config),
+        )
+        .route(
+            "/instance/:uuid/check_mods_on_start",
+            put(set_instance_check_mods_on_start),
+        )
+        .route(
+            "/instance/:uuid/line_rules",
+            get(get_instance_line_rules).put(set_instance_line_rules),
+        )
+        .route("/instance/:uuid/startup", get(get_instance_startup_config))
+        .route("/instance/:uuid/startup", put(set_instance_startup_config))
         .with_state(state)
 }
@@ -1,15 +1,28 @@
 use std::collections::HashSet;
 
-use axum::{extract::Path, routing::get, Json, Router};
+use axum::{
+    extract::{Path, Query},
+    routing::{get, put},
+    Json, Router,
+};
+use axum_auth::AuthBearer;
 use color_eyre::eyre::eyre;
+use serde::Deserialize;
 
 use crate::{
+    auth::user::UserAction,
+    db::player_sessions::{
+        get_player_sessions, peak_concurrent_players, player_count_series, player_stats,
+        PeakPlayerCount, PlayerCountPoint, PlayerStats,
+    },
     error::{Error, ErrorKind},
     traits::t_player::{Player, TPlayerManagement},
     types::InstanceUuid,
     AppState,
 };
 
+use super::util::authorize_instance;
+
 pub async fn get_player_count(
     axum::extract::State(state): axum::extract::State<AppState>,
     Path(uuid): Path<InstanceUuid>,
@@ -83,6 +96,217 @@ pub async fn get_player_list(
         .map(Json)
 }
 
+#[derive(Deserialize)]
+pub struct KickPlayerRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BanPlayerRequest {
+    pub reason: Option<String>,
+    /// in seconds, `None` bans permanently
+    pub duration: Option<u32>,
+}
+
+pub async fn kick_player(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player_name)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+    Json(request): Json<KickPlayerRequest>,
+) -> Result<Json<()>, Error> {
+    let (mut instance, caused_by) = authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance
+        .kick_player(&player_name, request.reason, caused_by)
+        .await
+        .map(Json)
+}
+
+pub async fn ban_player(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player_name)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+    Json(request): Json<BanPlayerRequest>,
+) -> Result<Json<()>, Error> {
+    let (mut instance, caused_by) = authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance
+        .ban_player(&player_name, request.reason, request.duration, caused_by)
+        .await
+        .map(Json)
+}
+
+pub async fn pardon_player(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player_name)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (mut instance, caused_by) = authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance
+        .pardon_player(&player_name, caused_by)
+        .await
+        .map(Json)
+}
+
+pub async fn op_player(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player_name)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (mut instance, caused_by) = authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance.op_player(&player_name, caused_by).await.map(Json)
+}
+
+pub async fn deop_player(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player_name)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let (mut instance, caused_by) = authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance
+        .deop_player(&player_name, caused_by)
+        .await
+        .map(Json)
+}
+
+pub async fn message_player(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, player_name)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+    Json(message): Json<String>,
+) -> Result<Json<()>, Error> {
+    let (mut instance, caused_by) = authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ModeratePlayers(uuid.clone()),
+    )
+    .await?;
+    instance
+        .message_player(&player_name, &message, caused_by)
+        .await
+        .map(Json)
+}
+
+/// Times are unix timestamps in seconds
+#[derive(Deserialize)]
+pub struct PlayerHistoryQuery {
+    pub start: Option<i64>,
+    /// defaults to now
+    pub end: Option<i64>,
+    /// in seconds, only used by the count history
+    pub interval: Option<i64>,
+}
+
+/// Default window of the peak and count history queries
+const DEFAULT_HISTORY_WINDOW: i64 = 24 * 60 * 60;
+const DEFAULT_HISTORY_INTERVAL: i64 = 5 * 60;
+
+/// The `(start, end)` of a history query, by default the day up to now
+fn history_range(query: &PlayerHistoryQuery) -> Result<(i64, i64), Error> {
+    let end = query.end.unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let start = query
+        .start
+        .unwrap_or_else(|| end.saturating_sub(DEFAULT_HISTORY_WINDOW));
+    if start > end {
+        return Err(Error {
+            kind: ErrorKind::BadRequest,
+            source: eyre!("Start of the time range is after its end"),
+        });
+    }
+    Ok((start, end))
+}
+
+pub async fn get_player_stats(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<PlayerStats>>, Error> {
+    authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ViewInstance(uuid.clone()),
+    )
+    .await?;
+    let sessions = get_player_sessions(&state.sqlite_pool, &uuid, i64::MIN, i64::MAX).await?;
+    Ok(Json(player_stats(
+        &sessions,
+        chrono::Utc::now().timestamp(),
+    )))
+}
+
+pub async fn get_peak_player_count(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Query(query): Query<PlayerHistoryQuery>,
+) -> Result<Json<Option<PeakPlayerCount>>, Error> {
+    authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ViewInstance(uuid.clone()),
+    )
+    .await?;
+    let (start, end) = history_range(&query)?;
+    let sessions = get_player_sessions(&state.sqlite_pool, &uuid, start, end).await?;
+    Ok(Json(peak_concurrent_players(&sessions, start, end)))
+}
+
+pub async fn get_player_count_history(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Query(query): Query<PlayerHistoryQuery>,
+) -> Result<Json<Vec<PlayerCountPoint>>, Error> {
+    authorize_instance(
+        &state,
+        &uuid,
+        &token,
+        UserAction::ViewInstance(uuid.clone()),
+    )
+    .await?;
+    let (start, end) = history_range(&query)?;
+    let interval = query.interval.unwrap_or(DEFAULT_HISTORY_INTERVAL);
+    if interval <= 0 {
+        return Err(Error {
+            kind: ErrorKind::BadRequest,
+            source: eyre!("Interval must be positive"),
+        });
+    }
+    let sessions = get_player_sessions(&state.sqlite_pool, &uuid, start, end).await?;
+    Ok(Json(player_count_series(&sessions, start, end, interval)))
+}
+
 pub fn get_instance_players_routes(state: AppState) -> Router {
     Router::new()
         .route("/instance/:uuid/players/count", get(get_player_count))
@@ -91,5 +315,20 @@ pub fn get_instance_players_routes(state: AppState) -> Router {
             get(get_max_player_count).put(set_max_player_count),
         )
         .route("/instance/:uuid/players", get(get_player_list))
+        .route("/instance/:uuid/players/stats", get(get_player_stats))
+        .route("/instance/:uuid/players/peak", get(get_peak_player_count))
+        .route(
+            "/instance/:uuid/players/count_history",
+            get(get_player_count_history),
+        )
+        .route("/instance/:uuid/players/:player/kick", put(kick_player))
+        .route("/instance/:uuid/players/:player/ban", put(ban_player))
+        .route("/instance/:uuid/players/:player/pardon", put(pardon_player))
+        .route("/instance/:uuid/players/:player/op", put(op_player))
+        .route("/instance/:uuid/players/:player/deop", put(deop_player))
+        .route(
+            "/instance/:uuid/players/:player/message",
+            put(message_player),
+        )
         .with_state(state)
 }
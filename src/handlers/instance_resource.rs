@@ -0,0 +1,203 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path},
+    routing::{delete, get, put},
+    Json, Router,
+};
+
+use axum_auth::AuthBearer;
+use color_eyre::eyre::{eyre, Context};
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    auth::user::UserAction,
+    error::{Error, ErrorKind},
+    events::CausedBy,
+    implementations::minecraft::{mod_analysis::ModIssue, world::WorldEntry},
+    prelude::{path_to_tmp, GameInstance},
+    traits::t_resource::TResourceManagement,
+    types::InstanceUuid,
+    AppState,
+};
+
+use super::util::{get_instance, get_minecraft_instance};
+
+pub async fn list_instance_resources(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<serde_json::Value>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ReadResource(uuid.clone()))?;
+    let instance = get_instance(&state, &uuid).await?;
+    Ok(Json(instance.list().await))
+}
+
+pub async fn load_instance_resource(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, resource)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::WriteResource(uuid.clone()))?;
+    let mut instance = get_instance(&state, &uuid).await?;
+    instance.load(&resource).await?;
+    Ok(Json(()))
+}
+
+pub async fn unload_instance_resource(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, resource)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::WriteResource(uuid.clone()))?;
+    let mut instance = get_instance(&state, &uuid).await?;
+    instance.unload(&resource).await?;
+    Ok(Json(()))
+}
+
+pub async fn delete_instance_resource(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, resource)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::WriteResource(uuid.clone()))?;
+    let mut instance = get_instance(&state, &uuid).await?;
+    instance.delete(&resource).await?;
+    Ok(Json(()))
+}
+
+pub async fn analyze_instance_mods(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<ModIssue>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ReadResource(uuid.clone()))?;
+    match get_instance(&state, &uuid).await? {
+        GameInstance::MinecraftInstance(instance) => Ok(Json(instance.analyze_mods().await?)),
+        _ => Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("This instance does not support mod analysis"),
+        }),
+    }
+}
+
+pub async fn list_instance_worlds(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<WorldEntry>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ReadResource(uuid.clone()))?;
+    let instance = get_minecraft_instance(&state, &uuid).await?;
+    Ok(Json(instance.list_worlds().await?))
+}
+
+pub async fn set_instance_active_world(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(level_name): Json<String>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::WriteResource(uuid.clone()))?;
+    let mut instance = get_minecraft_instance(&state, &uuid).await?;
+    instance.set_active_world(level_name).await?;
+    Ok(Json(()))
+}
+
+pub async fn import_instance_world(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    mut multipart: Multipart,
+) -> Result<Json<WorldEntry>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::WriteResource(uuid.clone()))?;
+    let instance = get_minecraft_instance(&state, &uuid).await?;
+
+    let mut field = multipart
+        .next_field()
+        .await
+        .context("Failed to read multipart field")?
+        .ok_or_else(|| Error {
+            kind: ErrorKind::BadRequest,
+            source: eyre!("Missing world archive"),
+        })?;
+    let name = field.file_name().ok_or_else(|| Error {
+        kind: ErrorKind::BadRequest,
+        source: eyre!("Missing file name"),
+    })?;
+    crate::util::fs::create_dir_all(path_to_tmp()).await?;
+    let temp_dir = tempfile::tempdir_in(path_to_tmp())
+        .context("Failed to create temporary directory for the upload")?;
+    // the extension is kept so that the archive format can be detected
+    let path_to_archive = temp_dir.path().join(sanitize_filename::sanitize(name));
+    let mut file = crate::util::fs::create(&path_to_archive).await?;
+    while let Some(chunk) = field.chunk().await.context("Failed to read chunk")? {
+        file.write_all(&chunk)
+            .await
+            .context("Failed to write chunk")?;
+    }
+    drop(file);
+
+    Ok(Json(instance.import_world(&path_to_archive).await?))
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateWorldRequest {
+    pub seed: Option<String>,
+}
+
+pub async fn regenerate_instance_world(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(request): Json<RegenerateWorldRequest>,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::WriteResource(uuid.clone()))?;
+    let caused_by = CausedBy::User {
+        user_id: requester.uid.clone(),
+        user_name: requester.username.clone(),
+    };
+    let mut instance = get_minecraft_instance(&state, &uuid).await?;
+    instance.regenerate_world(request.seed, caused_by).await?;
+    Ok(Json(()))
+}
+
+pub fn get_instance_resource_routes(state: AppState) -> Router {
+    Router::new()
+        .route("/instance/:uuid/resources", get(list_instance_resources))
+        .route("/instance/:uuid/mod_analysis", get(analyze_instance_mods))
+        .route(
+            "/instance/:uuid/resources/:resource/load",
+            put(load_instance_resource),
+        )
+        .route(
+            "/instance/:uuid/resources/:resource/unload",
+            put(unload_instance_resource),
+        )
+        .route(
+            "/instance/:uuid/resources/:resource",
+            delete(delete_instance_resource),
+        )
+        .route("/instance/:uuid/worlds", get(list_instance_worlds))
+        .route(
+            "/instance/:uuid/worlds/active",
+            put(set_instance_active_world),
+        )
+        .route(
+            "/instance/:uuid/worlds/regenerate",
+            put(regenerate_instance_world),
+        )
+        // world archives are routinely larger than the default body limit
+        .route(
+            "/instance/:uuid/worlds/import",
+            put(import_instance_world).layer(DefaultBodyLimit::disable()),
+        )
+        .with_state(state)
+}
@@ -0,0 +1,124 @@
+use axum::{
+    extract::Path,
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+
+use axum_auth::AuthBearer;
+use color_eyre::eyre::eyre;
+
+use crate::{
+    auth::user::{User, UserAction},
+    error::{Error, ErrorKind},
+    scheduler::{ScheduledAction, ScheduledJob, ScheduledJobConfig},
+    types::InstanceUuid,
+    AppState,
+};
+
+/// A job runs with the system's authority, so whoever schedules it must be able to run the action
+fn try_schedule_action(
+    requester: &User,
+    uuid: &InstanceUuid,
+    action: &ScheduledAction,
+) -> Result<(), Error> {
+    requester.try_action(&UserAction::ManageSchedule(uuid.clone()))?;
+    match action {
+        ScheduledAction::Start => requester.try_action(&UserAction::StartInstance(uuid.clone())),
+        ScheduledAction::Stop { .. } => {
+            requester.try_action(&UserAction::StopInstance(uuid.clone()))
+        }
+        ScheduledAction::Restart { .. } => {
+            requester.try_action(&UserAction::StartInstance(uuid.clone()))?;
+            requester.try_action(&UserAction::StopInstance(uuid.clone()))
+        }
+        ScheduledAction::Command { .. } => {
+            requester.try_action(&UserAction::AccessConsole(uuid.clone()))
+        }
+        ScheduledAction::Macro { .. } => {
+            requester.try_action(&UserAction::AccessMacro(Some(uuid.clone())))
+        }
+    }
+}
+
+fn job_not_found() -> Error {
+    Error {
+        kind: ErrorKind::NotFound,
+        source: eyre!("Scheduled job not found"),
+    }
+}
+
+pub async fn get_instance_schedule(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<ScheduledJob>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ManageSchedule(uuid.clone()))?;
+    Ok(Json(state.scheduler.lock().await.jobs_for_instance(&uuid)))
+}
+
+pub async fn create_scheduled_job(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(config): Json<ScheduledJobConfig>,
+) -> Result<Json<ScheduledJob>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    try_schedule_action(&requester, &uuid, &config.action)?;
+    if !state.instances.lock().await.contains_key(&uuid) {
+        return Err(Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Instance not found"),
+        });
+    }
+    let job = state.scheduler.lock().await.add_job(uuid, config).await?;
+    Ok(Json(job))
+}
+
+pub async fn update_scheduled_job(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, job_id)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+    Json(config): Json<ScheduledJobConfig>,
+) -> Result<Json<ScheduledJob>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    try_schedule_action(&requester, &uuid, &config.action)?;
+    let mut scheduler = state.scheduler.lock().await;
+    match scheduler.get_job(&job_id) {
+        Some(job) if job.instance_uuid == uuid => {}
+        _ => return Err(job_not_found()),
+    }
+    let job = scheduler.update_job(&job_id, config).await?;
+    Ok(Json(job))
+}
+
+pub async fn delete_scheduled_job(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, job_id)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ManageSchedule(uuid.clone()))?;
+    let mut scheduler = state.scheduler.lock().await;
+    match scheduler.get_job(&job_id) {
+        Some(job) if job.instance_uuid == uuid => {}
+        _ => return Err(job_not_found()),
+    }
+    scheduler.delete_job(&job_id).await?;
+    Ok(Json(()))
+}
+
+pub fn get_instance_schedule_routes(state: AppState) -> Router {
+    Router::new()
+        .route("/instance/:uuid/schedule", get(get_instance_schedule))
+        .route("/instance/:uuid/schedule", post(create_scheduled_job))
+        .route(
+            "/instance/:uuid/schedule/:job_id",
+            put(update_scheduled_job),
+        )
+        .route(
+            "/instance/:uuid/schedule/:job_id",
+            delete(delete_scheduled_job),
+        )
+        .with_state(state)
+}
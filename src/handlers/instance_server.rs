@@ -14,6 +14,15 @@ use crate::{
     auth::user::UserAction,
     error::{Error, ErrorKind},
     events::CausedBy,
+    implementations::minecraft::{
+        command::CommandOutput,
+        countdown::CountdownAction,
+        crash_report::{CrashReport, CrashReportSummary},
+        query::QueryStat,
+        server_list_ping::ServerStatus,
+    },
+    port_manager::PortManager,
+    prelude::GameInstance,
     types::InstanceUuid,
 };
 
@@ -22,6 +31,8 @@ use crate::{
     AppState,
 };
 
+use super::util::get_minecraft_instance;
+
 pub async fn start_instance(
     axum::extract::State(state): axum::extract::State<AppState>,
     Path(uuid): Path<InstanceUuid>,
@@ -40,12 +51,28 @@ pub async fn start_instance(
     })?;
     let port = instance.port().await;
 
-    if state.port_manager.lock().await.port_status(port).is_in_use {
+    let mut port_manager = state.port_manager.lock().await;
+    if port_manager.port_status(port).is_in_use {
         return Err(Error {
             kind: ErrorKind::Internal,
             source: eyre!("Port {} is in use", port),
         });
     }
+    // query.port may have been edited since the last start
+    let query_port = match &*instance {
+        GameInstance::MinecraftInstance(instance) => instance.query_port().await,
+        _ => None,
+    };
+    port_manager.set_query_port(&uuid, query_port);
+    if let Some(query_port) = query_port {
+        if !PortManager::udp_port_available(query_port) {
+            return Err(Error {
+                kind: ErrorKind::Internal,
+                source: eyre!("Query port {} is in use", query_port),
+            });
+        }
+    }
+    drop(port_manager);
 
     instance.start(caused_by, false).await?;
     Ok(Json(()))
@@ -150,6 +177,43 @@ pub async fn send_command(
         .map(|_| Json(()))
 }
 
+/// Unlike `send_command`, this returns the server's response when the command could go through RCON
+pub async fn run_command(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+    Json(command): Json<String>,
+) -> Result<Json<CommandOutput>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::AccessConsole(uuid.clone()))?;
+    let caused_by = CausedBy::User {
+        user_id: requester.uid.clone(),
+        user_name: requester.username.clone(),
+    };
+    let instance = state
+        .instances
+        .lock()
+        .await
+        .get(&uuid)
+        .cloned()
+        .ok_or_else(|| Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Instance not found"),
+        })?;
+    match instance {
+        GameInstance::MinecraftInstance(instance) => {
+            Ok(Json(instance.run_command(&command, caused_by).await?))
+        }
+        instance => {
+            instance.send_command(&command, caused_by).await?;
+            Ok(Json(CommandOutput {
+                output: None,
+                stdin_fallback: true,
+            }))
+        }
+    }
+}
+
 pub async fn get_instance_state(
     axum::extract::State(state): axum::extract::State<AppState>,
     Path(uuid): Path<InstanceUuid>,
@@ -177,13 +241,149 @@ pub async fn get_instance_state(
     )))
 }
 
+pub async fn stop_instance_countdown(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::StopInstance(uuid.clone()))?;
+    let caused_by = CausedBy::User {
+        user_id: requester.uid.clone(),
+        user_name: requester.username.clone(),
+    };
+    get_minecraft_instance(&state, &uuid)
+        .await?
+        .start_countdown(CountdownAction::Stop, caused_by)
+        .await?;
+    Ok(Json(()))
+}
+
+pub async fn restart_instance_countdown(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester
+        .try_action(&UserAction::StopInstance(uuid.clone()))
+        .and_then(|_x| requester.try_action(&UserAction::StartInstance(uuid.clone())))?;
+    let caused_by = CausedBy::User {
+        user_id: requester.uid.clone(),
+        user_name: requester.username.clone(),
+    };
+    get_minecraft_instance(&state, &uuid)
+        .await?
+        .start_countdown(CountdownAction::Restart, caused_by)
+        .await?;
+    Ok(Json(()))
+}
+
+pub async fn cancel_instance_countdown(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<()>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::StopInstance(uuid.clone()))?;
+    let caused_by = CausedBy::User {
+        user_id: requester.uid.clone(),
+        user_name: requester.username.clone(),
+    };
+    get_minecraft_instance(&state, &uuid)
+        .await?
+        .cancel_countdown(caused_by)
+        .await?;
+    Ok(Json(()))
+}
+
+pub async fn get_crash_reports(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Vec<CrashReportSummary>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ReadInstanceFile(uuid.clone()))?;
+    Ok(Json(
+        get_minecraft_instance(&state, &uuid)
+            .await?
+            .list_crash_reports()
+            .await?,
+    ))
+}
+
+pub async fn get_crash_report(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path((uuid, file_name)): Path<(InstanceUuid, String)>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<CrashReport>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ReadInstanceFile(uuid.clone()))?;
+    Ok(Json(
+        get_minecraft_instance(&state, &uuid)
+            .await?
+            .get_crash_report(&file_name)
+            .await?,
+    ))
+}
+
+pub async fn get_server_status(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Option<ServerStatus>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ViewInstance(uuid.clone()))?;
+    Ok(Json(
+        get_minecraft_instance(&state, &uuid)
+            .await?
+            .get_server_status()
+            .await,
+    ))
+}
+
+pub async fn get_query_stat(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    Path(uuid): Path<InstanceUuid>,
+    AuthBearer(token): AuthBearer,
+) -> Result<Json<Option<QueryStat>>, Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(&token)?;
+    requester.try_action(&UserAction::ViewInstance(uuid.clone()))?;
+    Ok(Json(
+        get_minecraft_instance(&state, &uuid)
+            .await?
+            .get_query_stat()
+            .await,
+    ))
+}
+
 pub fn get_instance_server_routes(state: AppState) -> Router {
     Router::new()
         .route("/instance/:uuid/start", put(start_instance))
         .route("/instance/:uuid/stop", put(stop_instance))
         .route("/instance/:uuid/restart", put(restart_instance))
         .route("/instance/:uuid/kill", put(kill_instance))
+        .route(
+            "/instance/:uuid/countdown/stop",
+            put(stop_instance_countdown),
+        )
+        .route(
+            "/instance/:uuid/countdown/restart",
+            put(restart_instance_countdown),
+        )
+        .route(
+            "/instance/:uuid/countdown/cancel",
+            put(cancel_instance_countdown),
+        )
         .route("/instance/:uuid/console", post(send_command))
+        .route("/instance/:uuid/command", post(run_command))
         .route("/instance/:uuid/state", get(get_instance_state))
+        .route("/instance/:uuid/server_status", get(get_server_status))
+        .route("/instance/:uuid/query", get(get_query_stat))
+        .route("/instance/:uuid/crash_reports", get(get_crash_reports))
+        .route(
+            "/instance/:uuid/crash_reports/:file_name",
+            get(get_crash_report),
+        )
         .with_state(state)
 }
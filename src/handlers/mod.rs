@@ -8,10 +8,13 @@ pub mod gateway;
 pub mod global_fs;
 pub mod global_settings;
 pub mod instance;
+pub mod instance_access_list;
 pub mod instance_config;
 pub mod instance_fs;
 pub mod instance_macro;
 pub mod instance_players;
+pub mod instance_resource;
+pub mod instance_schedule;
 pub mod instance_server;
 pub mod instance_setup_configs;
 pub mod monitor;
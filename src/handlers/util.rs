@@ -1,6 +1,14 @@
-use color_eyre::eyre::Context;
+use color_eyre::eyre::{eyre, Context};
 
-use crate::error::Error;
+use crate::{
+    auth::user::UserAction,
+    error::{Error, ErrorKind},
+    events::CausedBy,
+    implementations::minecraft::MinecraftInstance,
+    prelude::GameInstance,
+    types::InstanceUuid,
+    AppState,
+};
 
 pub fn parse_bearer_token(token: &str) -> Option<String> {
     let mut split = token.split_ascii_whitespace();
@@ -23,3 +31,63 @@ pub fn decode_base64(input: &str) -> Result<String, Error> {
     )
     .context("Invalid UTF-8")?)
 }
+
+pub async fn get_instance(state: &AppState, uuid: &InstanceUuid) -> Result<GameInstance, Error> {
+    state
+        .instances
+        .lock()
+        .await
+        .get(uuid)
+        .cloned()
+        .ok_or_else(|| Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Instance not found"),
+        })
+}
+
+fn into_minecraft_instance(instance: GameInstance) -> Result<MinecraftInstance, Error> {
+    match instance {
+        GameInstance::MinecraftInstance(instance) => Ok(instance),
+        _ => Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("This operation is only supported by Minecraft instances"),
+        }),
+    }
+}
+
+pub async fn get_minecraft_instance(
+    state: &AppState,
+    uuid: &InstanceUuid,
+) -> Result<MinecraftInstance, Error> {
+    into_minecraft_instance(get_instance(state, uuid).await?)
+}
+
+/// Checks that the owner of `token` may perform `action`, returning the instance
+/// and who to attribute the action to
+pub async fn authorize_instance(
+    state: &AppState,
+    uuid: &InstanceUuid,
+    token: &str,
+    action: UserAction,
+) -> Result<(GameInstance, CausedBy), Error> {
+    let requester = state.users_manager.read().await.try_auth_or_err(token)?;
+    requester.try_action(&action)?;
+    let instance = get_instance(state, uuid).await?;
+    Ok((
+        instance,
+        CausedBy::User {
+            user_id: requester.uid.clone(),
+            user_name: requester.username.clone(),
+        },
+    ))
+}
+
+pub async fn authorize_minecraft_instance(
+    state: &AppState,
+    uuid: &InstanceUuid,
+    token: &str,
+    action: UserAction,
+) -> Result<(MinecraftInstance, CausedBy), Error> {
+    let (instance, caused_by) = authorize_instance(state, uuid, token, action).await?;
+    Ok((into_minecraft_instance(instance)?, caused_by))
+}
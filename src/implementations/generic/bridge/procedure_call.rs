@@ -101,6 +101,34 @@ pub enum ProcedureCallInner {
     GetPlayerCount,
     GetMaxPlayerCount,
     GetPlayerList,
+    KickPlayer {
+        player_name: String,
+        reason: Option<String>,
+        caused_by: CausedBy,
+    },
+    BanPlayer {
+        player_name: String,
+        reason: Option<String>,
+        duration: Option<u32>,
+        caused_by: CausedBy,
+    },
+    PardonPlayer {
+        player_name: String,
+        caused_by: CausedBy,
+    },
+    OpPlayer {
+        player_name: String,
+        caused_by: CausedBy,
+    },
+    DeopPlayer {
+        player_name: String,
+        caused_by: CausedBy,
+    },
+    MessagePlayer {
+        player_name: String,
+        message: String,
+        caused_by: CausedBy,
+    },
     // end of TPlayerManagement
     // start of TMacro
     GetMacroList,
@@ -1,3 +1,4 @@
+import { CausedBy } from "../libs/bindings/CausedBy.ts";
 import { GenericPlayer } from "../libs/bindings/GenericPlayer.ts";
 
 export async function getPlayerCount(): Promise<number> {
@@ -11,3 +12,28 @@ export async function getPlayerList(): Promise<GenericPlayer[]> {
 export async function getMaxPlayerCount(): Promise<number> {
     throw new Error("Not implemented");
 }
+
+export async function kickPlayer(player_name: string, reason: string | null, caused_by: CausedBy): Promise<void> {
+    throw new Error("Not implemented");
+}
+
+// duration is in seconds, null bans permanently
+export async function banPlayer(player_name: string, reason: string | null, duration: number | null, caused_by: CausedBy): Promise<void> {
+    throw new Error("Not implemented");
+}
+
+export async function pardonPlayer(player_name: string, caused_by: CausedBy): Promise<void> {
+    throw new Error("Not implemented");
+}
+
+export async function opPlayer(player_name: string, caused_by: CausedBy): Promise<void> {
+    throw new Error("Not implemented");
+}
+
+export async function deopPlayer(player_name: string, caused_by: CausedBy): Promise<void> {
+    throw new Error("Not implemented");
+}
+
+export async function messagePlayer(player_name: string, message: string, caused_by: CausedBy): Promise<void> {
+    throw new Error("Not implemented");
+}
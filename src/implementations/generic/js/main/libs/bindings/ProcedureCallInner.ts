@@ -47,6 +47,28 @@ export type ProcedureCallInner =
   | { type: "GetPlayerCount" }
   | { type: "GetMaxPlayerCount" }
   | { type: "GetPlayerList" }
+  | {
+    type: "KickPlayer";
+    player_name: string;
+    reason: string | null;
+    caused_by: CausedBy;
+  }
+  | {
+    type: "BanPlayer";
+    player_name: string;
+    reason: string | null;
+    duration: number | null;
+    caused_by: CausedBy;
+  }
+  | { type: "PardonPlayer"; player_name: string; caused_by: CausedBy }
+  | { type: "OpPlayer"; player_name: string; caused_by: CausedBy }
+  | { type: "DeopPlayer"; player_name: string; caused_by: CausedBy }
+  | {
+    type: "MessagePlayer";
+    player_name: string;
+    message: string;
+    caused_by: CausedBy;
+  }
   | { type: "GetMacroList" }
   | { type: "GetTaskList" }
   | { type: "GetHistoryList" }
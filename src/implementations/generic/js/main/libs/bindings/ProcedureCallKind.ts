@@ -29,6 +29,12 @@ export type ProcedureCallKind =
   | "GetPlayerCount"
   | "GetMaxPlayerCount"
   | "GetPlayerList"
+  | "KickPlayer"
+  | "BanPlayer"
+  | "PardonPlayer"
+  | "OpPlayer"
+  | "DeopPlayer"
+  | "MessagePlayer"
   | "GetMacroList"
   | "GetTaskList"
   | "GetHistoryList"
@@ -22,7 +22,7 @@ import {
 import { isErrorIR } from "./typeguards/ErrorIRTypeGuard.ts";
 import { ProcedureCallResultInner } from "./bindings/ProcedureCallResultInner.ts";
 import { getAutoStart, getConfigurableManifest, getDescription, getGame, getName, getPort, getRestartOnCrash, getVersion, setAutoStart, setDescription, setName, setPort, setRestartOnCrash, updateConfigurable } from "../implementation/configurable.ts";
-import { getMaxPlayerCount, getPlayerCount, getPlayerList } from "../implementation/players.ts";
+import { banPlayer, deopPlayer, getMaxPlayerCount, getPlayerCount, getPlayerList, kickPlayer, messagePlayer, opPlayer, pardonPlayer } from "../implementation/players.ts";
 import { isTConfig, isTMacro, isTPlayer, isTServer } from "./utils.ts";
 import { restoreInstance, setupInstance, setupManifest } from "../implementation/setup.ts";
 
@@ -45,6 +45,18 @@ async function tPlayerHandle(procedure: ProcedureCall) {
             ret = {
                 Num: await getMaxPlayerCount(),
             };
+        } else if (inner.type === "KickPlayer") {
+            await kickPlayer(inner.player_name, inner.reason, inner.caused_by);
+        } else if (inner.type === "BanPlayer") {
+            await banPlayer(inner.player_name, inner.reason, inner.duration, inner.caused_by);
+        } else if (inner.type === "PardonPlayer") {
+            await pardonPlayer(inner.player_name, inner.caused_by);
+        } else if (inner.type === "OpPlayer") {
+            await opPlayer(inner.player_name, inner.caused_by);
+        } else if (inner.type === "DeopPlayer") {
+            await deopPlayer(inner.player_name, inner.caused_by);
+        } else if (inner.type === "MessagePlayer") {
+            await messagePlayer(inner.player_name, inner.message, inner.caused_by);
         }
     } catch (e) {
         if (isErrorIR(e)) {
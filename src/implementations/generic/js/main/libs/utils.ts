@@ -19,6 +19,12 @@ export function isTServer(inner: ProcedureCallInner): boolean {
       case "GetPlayerCount":
       case "GetPlayerList":
       case "GetMaxPlayerCount":
+      case "KickPlayer":
+      case "BanPlayer":
+      case "PardonPlayer":
+      case "OpPlayer":
+      case "DeopPlayer":
+      case "MessagePlayer":
         return true;
     }
     return false;
@@ -1,7 +1,7 @@
-use std::{path::PathBuf, rc::Rc};
+use std::{path::PathBuf, rc::Rc, time::Duration};
 
 use async_trait::async_trait;
-use color_eyre::eyre::Context;
+use color_eyre::eyre::{eyre, Context};
 use tracing::error;
 use url::Url;
 
@@ -10,7 +10,7 @@ use self::{
     r#macro::GenericMainWorkerGenerator,
 };
 use crate::{
-    error::Error,
+    error::{Error, ErrorKind},
     event_broadcaster::EventBroadcaster,
     events::CausedBy,
     macro_executor::{self, MacroExecutor, MacroPID, SpawnResult, WorkerOptionGenerator},
@@ -34,6 +34,9 @@ pub mod player;
 pub mod resource;
 pub mod server;
 
+/// upper bound on how long the instance's macro may take to restore it
+const RESTORE_TIMEOUT: Duration = Duration::from_secs(120);
+
 #[derive(Clone)]
 pub struct GenericInstance {
     dot_lodestone_config: DotLodestoneConfig,
@@ -173,14 +176,33 @@ impl GenericInstance {
             )
             .await?;
 
-        main_module_future.await;
-
-        procedure_bridge
-            .call(ProcedureCallInner::RestoreInstance {
-                dot_lodestone_config: dot_lodestone_config.clone(),
-                path: path_to_instance.clone(),
+        // the restore procedure runs in the instance's own macro, which may never answer
+        let restored = tokio::time::timeout(RESTORE_TIMEOUT, async {
+            main_module_future.await;
+            procedure_bridge
+                .call(ProcedureCallInner::RestoreInstance {
+                    dot_lodestone_config: dot_lodestone_config.clone(),
+                    path: path_to_instance.clone(),
+                })
+                .await
+        })
+        .await
+        .unwrap_or_else(|_| {
+            Err(Error {
+                kind: ErrorKind::Internal,
+                source: eyre!("Timed out waiting for the instance to restore"),
             })
-            .await?;
+        });
+        if let Err(e) = restored {
+            // don't leave the macro running for an instance that failed to restore
+            if let Err(abort_error) = core_macro_executor.abort_macro(core_macro_pid) {
+                error!(
+                    "Failed to abort macro of unrestored instance: {}",
+                    abort_error
+                );
+            }
+            return Err(e);
+        }
         Ok(GenericInstance {
             dot_lodestone_config,
             procedure_bridge,
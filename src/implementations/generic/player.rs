@@ -6,6 +6,7 @@ use ts_rs::TS;
 
 use crate::{
     error::Error,
+    events::CausedBy,
     traits::t_player::{Player, TPlayer, TPlayerManagement},
 };
 
@@ -48,4 +49,84 @@ impl TPlayerManagement for GenericInstance {
             .await?
             .try_into()
     }
+
+    async fn kick_player(
+        &mut self,
+        player_name: &str,
+        reason: Option<String>,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        self.procedure_bridge
+            .call(ProcedureCallInner::KickPlayer {
+                player_name: player_name.to_string(),
+                reason,
+                caused_by,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn ban_player(
+        &mut self,
+        player_name: &str,
+        reason: Option<String>,
+        duration: Option<u32>,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        self.procedure_bridge
+            .call(ProcedureCallInner::BanPlayer {
+                player_name: player_name.to_string(),
+                reason,
+                duration,
+                caused_by,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn pardon_player(&mut self, player_name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        self.procedure_bridge
+            .call(ProcedureCallInner::PardonPlayer {
+                player_name: player_name.to_string(),
+                caused_by,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn op_player(&mut self, player_name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        self.procedure_bridge
+            .call(ProcedureCallInner::OpPlayer {
+                player_name: player_name.to_string(),
+                caused_by,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn deop_player(&mut self, player_name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        self.procedure_bridge
+            .call(ProcedureCallInner::DeopPlayer {
+                player_name: player_name.to_string(),
+                caused_by,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn message_player(
+        &mut self,
+        player_name: &str,
+        message: &str,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        self.procedure_bridge
+            .call(ProcedureCallInner::MessagePlayer {
+                player_name: player_name.to_string(),
+                message: message.to_string(),
+                caused_by,
+            })
+            .await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,539 @@
+use std::{net::IpAddr, time::Duration};
+
+use chrono::{DateTime, Local};
+use color_eyre::eyre::{eyre, Context};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{error, warn};
+use ts_rs::TS;
+
+use crate::{
+    error::{Error, ErrorKind},
+    events::CausedBy,
+    traits::t_server::{State, TServer},
+};
+
+use super::{
+    configurable::ServerPropertySetting,
+    util::{name_to_uuid, offline_player_uuid, read_properties_from_path},
+    MinecraftInstance,
+};
+
+const WHITELIST_FILE_NAME: &str = "whitelist.json";
+const OPS_FILE_NAME: &str = "ops.json";
+const BANNED_PLAYERS_FILE_NAME: &str = "banned-players.json";
+const BANNED_IPS_FILE_NAME: &str = "banned-ips.json";
+
+/// What the server itself writes for bans issued from the console
+const BAN_SOURCE: &str = "Server";
+const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+const BAN_EXPIRES_NEVER: &str = "forever";
+/// How long to wait before trying to lift an expired temporary ban again
+const BAN_LIFT_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct WhitelistEntry {
+    pub uuid: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct OpEntry {
+    pub uuid: String,
+    pub name: String,
+    pub level: u8,
+    #[serde(default)]
+    pub bypasses_player_limit: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct BannedPlayerEntry {
+    pub uuid: String,
+    pub name: String,
+    pub created: String,
+    pub source: String,
+    pub expires: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct BannedIpEntry {
+    pub ip: String,
+    pub created: String,
+    pub source: String,
+    pub expires: String,
+    pub reason: String,
+}
+
+/// The `white-list` and `enforce-whitelist` server properties
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct WhitelistSettings {
+    pub enabled: bool,
+    pub enforced: bool,
+}
+
+/// Player names are interpolated into console commands, so only accept valid Minecraft names
+pub(super) fn validate_player_name(name: &str) -> Result<(), Error> {
+    if name.is_empty()
+        || name.len() > 16
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(Error {
+            kind: ErrorKind::BadRequest,
+            source: eyre!("{} is not a valid player name", name),
+        });
+    }
+    Ok(())
+}
+
+fn validate_ip(ip: &str) -> Result<(), Error> {
+    ip.parse::<IpAddr>().map(|_| ()).map_err(|_| Error {
+        kind: ErrorKind::BadRequest,
+        source: eyre!("{} is not a valid IP address", ip),
+    })
+}
+
+/// Keep a reason or message on a single console line
+pub(super) fn single_line(reason: Option<String>) -> Option<String> {
+    reason
+        .map(|reason| reason.replace(['\r', '\n'], " ").trim().to_string())
+        .filter(|reason| !reason.is_empty())
+}
+
+/// The Mojang API returns UUIDs without hyphens, the JSON files store them with
+pub(super) fn hyphenate_uuid(uuid: &str) -> String {
+    if uuid.len() != 32 || !uuid.chars().all(|c| c.is_ascii_hexdigit()) {
+        return uuid.to_string();
+    }
+    format!(
+        "{}-{}-{}-{}-{}",
+        &uuid[0..8],
+        &uuid[8..12],
+        &uuid[12..16],
+        &uuid[16..20],
+        &uuid[20..32]
+    )
+}
+
+fn format_ban_time(time: DateTime<Local>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S %z").to_string()
+}
+
+impl MinecraftInstance {
+    /// Players who have joined before are looked up locally, only unknown players hit the Mojang API.
+    /// Offline mode servers derive UUIDs from the name instead, so the Mojang UUID would never match
+    async fn resolve_uuid(&self, name: &str) -> Result<String, Error> {
+        let online_mode = self
+            .configurable_manifest
+            .lock()
+            .await
+            .get_unique_setting_key(&ServerPropertySetting::OnlineMode(true).get_identifier())
+            .and_then(|v| v.get_value().map(|v| v.try_as_boolean().ok()))
+            .flatten()
+            .unwrap_or(true);
+        if !online_mode {
+            return Ok(offline_player_uuid(name));
+        }
+        if let Some(uuid) = self.user_cache.lock().await.uuid_of(name) {
+            return Ok(uuid);
+        }
+        let uuid = name_to_uuid(name)
+            .await
+            .map(|uuid| hyphenate_uuid(&uuid))
+            .ok_or_else(|| Error {
+                kind: ErrorKind::NotFound,
+                source: eyre!("Could not find the UUID of player {}", name),
+            })?;
+        self.user_cache.lock().await.insert(name, &uuid);
+        Ok(uuid)
+    }
+
+    /// Whether list changes should go through the console of the running server.
+    /// Editing the files is only safe while the server is stopped, since it rewrites them
+    async fn edit_access_list_live(&self) -> Result<bool, Error> {
+        match self.state().await {
+            State::Running => Ok(true),
+            State::Stopped | State::Error => Ok(false),
+            _ => Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Cannot edit the lists while server is starting or stopping"),
+            }),
+        }
+    }
+
+    async fn read_access_list<T: DeserializeOwned>(
+        &self,
+        file_name: &str,
+    ) -> Result<Vec<T>, Error> {
+        let path = self.path_to_instance.join(file_name);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .context(format!("Failed to read {}", path.display()))?;
+        if content.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(
+            serde_json::from_str(&content)
+                .context(format!("Failed to parse {}", path.display()))?,
+        )
+    }
+
+    async fn write_access_list<T: Serialize>(
+        &self,
+        file_name: &str,
+        entries: &[T],
+    ) -> Result<(), Error> {
+        let path = self.path_to_instance.join(file_name);
+        let content = serde_json::to_string_pretty(entries)
+            .context(format!("Failed to serialize {}", path.display()))?;
+        crate::util::fs::write_all(&path, content.into_bytes()).await
+    }
+
+    pub async fn get_whitelist(&self) -> Result<Vec<WhitelistEntry>, Error> {
+        self.read_access_list(WHITELIST_FILE_NAME).await
+    }
+
+    pub async fn get_ops(&self) -> Result<Vec<OpEntry>, Error> {
+        self.read_access_list(OPS_FILE_NAME).await
+    }
+
+    pub async fn get_banned_players(&self) -> Result<Vec<BannedPlayerEntry>, Error> {
+        self.read_access_list(BANNED_PLAYERS_FILE_NAME).await
+    }
+
+    pub async fn get_banned_ips(&self) -> Result<Vec<BannedIpEntry>, Error> {
+        self.read_access_list(BANNED_IPS_FILE_NAME).await
+    }
+
+    pub async fn add_to_whitelist(&self, name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        validate_player_name(name)?;
+        if self.edit_access_list_live().await? {
+            return self
+                .send_command(&format!("whitelist add {}", name), caused_by)
+                .await;
+        }
+        let mut whitelist = self.get_whitelist().await?;
+        if whitelist.iter().any(|e| e.name.eq_ignore_ascii_case(name)) {
+            return Ok(());
+        }
+        whitelist.push(WhitelistEntry {
+            uuid: self.resolve_uuid(name).await?,
+            name: name.to_string(),
+        });
+        self.write_access_list(WHITELIST_FILE_NAME, &whitelist)
+            .await
+    }
+
+    pub async fn remove_from_whitelist(
+        &self,
+        name: &str,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        validate_player_name(name)?;
+        if self.edit_access_list_live().await? {
+            return self
+                .send_command(&format!("whitelist remove {}", name), caused_by)
+                .await;
+        }
+        let mut whitelist = self.get_whitelist().await?;
+        whitelist.retain(|e| !e.name.eq_ignore_ascii_case(name));
+        self.write_access_list(WHITELIST_FILE_NAME, &whitelist)
+            .await
+    }
+
+    pub async fn add_op(&self, name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        validate_player_name(name)?;
+        if self.edit_access_list_live().await? {
+            return self.send_command(&format!("op {}", name), caused_by).await;
+        }
+        let mut ops = self.get_ops().await?;
+        if ops.iter().any(|e| e.name.eq_ignore_ascii_case(name)) {
+            return Ok(());
+        }
+        // the server gives new operators the level configured in the properties
+        let level = read_properties_from_path(&self.path_to_properties)
+            .await
+            .ok()
+            .and_then(|properties| properties.get("op-permission-level")?.parse().ok())
+            .unwrap_or(4);
+        ops.push(OpEntry {
+            uuid: self.resolve_uuid(name).await?,
+            name: name.to_string(),
+            level,
+            bypasses_player_limit: false,
+        });
+        self.write_access_list(OPS_FILE_NAME, &ops).await
+    }
+
+    pub async fn remove_op(&self, name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        validate_player_name(name)?;
+        if self.edit_access_list_live().await? {
+            return self
+                .send_command(&format!("deop {}", name), caused_by)
+                .await;
+        }
+        let mut ops = self.get_ops().await?;
+        ops.retain(|e| !e.name.eq_ignore_ascii_case(name));
+        self.write_access_list(OPS_FILE_NAME, &ops).await
+    }
+
+    /// Ban a player until `expires`, or permanently if it is `None`.
+    ///
+    /// The console `ban` command has no duration, so a temporary ban of a running server
+    /// is lifted by a timer instead. The expiry is written to `banned-players.json` once the
+    /// server stops, so the server lifts the ban itself if the timer doesn't get to
+    pub async fn add_ban(
+        &self,
+        name: &str,
+        reason: Option<String>,
+        expires: Option<DateTime<Local>>,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        validate_player_name(name)?;
+        let reason = single_line(reason);
+        if self.edit_access_list_live().await? {
+            let command = match reason {
+                Some(reason) => format!("ban {} {}", name, reason),
+                None => format!("ban {}", name),
+            };
+            self.send_command(&command, caused_by).await?;
+            let mut pending_ban_expiries = self.pending_ban_expiries.lock().await;
+            match expires {
+                Some(expires) => {
+                    pending_ban_expiries.insert(name.to_lowercase(), expires);
+                    self.spawn_ban_lift_task(name.to_string(), expires);
+                }
+                None => {
+                    pending_ban_expiries.remove(&name.to_lowercase());
+                }
+            }
+            return Ok(());
+        }
+        self.pending_ban_expiries
+            .lock()
+            .await
+            .remove(&name.to_lowercase());
+        let mut banned_players = self.get_banned_players().await?;
+        banned_players.retain(|e| !e.name.eq_ignore_ascii_case(name));
+        banned_players.push(BannedPlayerEntry {
+            uuid: self.resolve_uuid(name).await?,
+            name: name.to_string(),
+            created: format_ban_time(Local::now()),
+            source: BAN_SOURCE.to_string(),
+            expires: expires
+                .map(format_ban_time)
+                .unwrap_or_else(|| BAN_EXPIRES_NEVER.to_string()),
+            reason: reason.unwrap_or_else(|| DEFAULT_BAN_REASON.to_string()),
+        });
+        self.write_access_list(BANNED_PLAYERS_FILE_NAME, &banned_players)
+            .await
+    }
+
+    pub async fn remove_ban(&self, name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        validate_player_name(name)?;
+        self.pending_ban_expiries
+            .lock()
+            .await
+            .remove(&name.to_lowercase());
+        if self.edit_access_list_live().await? {
+            return self
+                .send_command(&format!("pardon {}", name), caused_by)
+                .await;
+        }
+        let mut banned_players = self.get_banned_players().await?;
+        banned_players.retain(|e| !e.name.eq_ignore_ascii_case(name));
+        self.write_access_list(BANNED_PLAYERS_FILE_NAME, &banned_players)
+            .await
+    }
+
+    /// Lift a temporary ban once it expires, retrying while the server is starting or stopping.
+    /// Gives up once the ban was lifted, replaced or written to `banned-players.json`
+    fn spawn_ban_lift_task(&self, name: String, expires: DateTime<Local>) {
+        let instance = self.clone();
+        tokio::spawn(async move {
+            let duration = (expires - Local::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(duration).await;
+            loop {
+                let key = name.to_lowercase();
+                if instance.pending_ban_expiries.lock().await.get(&key) != Some(&expires) {
+                    return;
+                }
+                match instance.remove_ban(&name, CausedBy::System).await {
+                    Ok(()) => return,
+                    Err(e) => {
+                        warn!(
+                            "Failed to lift the temporary ban of {}, retrying: {}",
+                            name, e
+                        );
+                        // remove_ban forgets the expiry even when it fails
+                        instance
+                            .pending_ban_expiries
+                            .lock()
+                            .await
+                            .insert(key, expires);
+                    }
+                }
+                tokio::time::sleep(BAN_LIFT_RETRY_INTERVAL).await;
+            }
+        });
+    }
+
+    /// Write the expiries of temporary bans issued through the console into
+    /// `banned-players.json`, so the server lifts them itself. Called once the process exited
+    pub(super) async fn write_pending_ban_expiries(&self) {
+        let pending_ban_expiries = std::mem::take(&mut *self.pending_ban_expiries.lock().await);
+        if pending_ban_expiries.is_empty() {
+            return;
+        }
+        let res = async {
+            let mut banned_players = self.get_banned_players().await?;
+            for entry in banned_players.iter_mut() {
+                if let Some(expires) = pending_ban_expiries.get(&entry.name.to_lowercase()) {
+                    entry.expires = format_ban_time(*expires);
+                }
+            }
+            self.write_access_list(BANNED_PLAYERS_FILE_NAME, &banned_players)
+                .await
+        }
+        .await;
+        if let Err(e) = res {
+            error!("Failed to write the expiries of temporary bans: {}", e);
+        }
+    }
+
+    pub async fn ban_ip(
+        &self,
+        ip: &str,
+        reason: Option<String>,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        validate_ip(ip)?;
+        let reason = single_line(reason);
+        if self.edit_access_list_live().await? {
+            let command = match reason {
+                Some(reason) => format!("ban-ip {} {}", ip, reason),
+                None => format!("ban-ip {}", ip),
+            };
+            return self.send_command(&command, caused_by).await;
+        }
+        let mut banned_ips = self.get_banned_ips().await?;
+        banned_ips.retain(|e| e.ip != ip);
+        banned_ips.push(BannedIpEntry {
+            ip: ip.to_string(),
+            created: format_ban_time(Local::now()),
+            source: BAN_SOURCE.to_string(),
+            expires: BAN_EXPIRES_NEVER.to_string(),
+            reason: reason.unwrap_or_else(|| DEFAULT_BAN_REASON.to_string()),
+        });
+        self.write_access_list(BANNED_IPS_FILE_NAME, &banned_ips)
+            .await
+    }
+
+    pub async fn pardon_ip(&self, ip: &str, caused_by: CausedBy) -> Result<(), Error> {
+        validate_ip(ip)?;
+        if self.edit_access_list_live().await? {
+            return self
+                .send_command(&format!("pardon-ip {}", ip), caused_by)
+                .await;
+        }
+        let mut banned_ips = self.get_banned_ips().await?;
+        banned_ips.retain(|e| e.ip != ip);
+        self.write_access_list(BANNED_IPS_FILE_NAME, &banned_ips)
+            .await
+    }
+
+    pub async fn get_whitelist_settings(&self) -> Result<WhitelistSettings, Error> {
+        let properties = read_properties_from_path(&self.path_to_properties)
+            .await
+            .unwrap_or_default();
+        let get_bool = |key: &str| {
+            properties
+                .get(key)
+                .map(|value| value.trim() == "true")
+                .unwrap_or(false)
+        };
+        Ok(WhitelistSettings {
+            enabled: get_bool("white-list"),
+            enforced: get_bool("enforce-whitelist"),
+        })
+    }
+
+    /// Update the whitelist properties, and toggle the whitelist of the running server with
+    /// `whitelist on` or `whitelist off` so that it takes effect without a restart
+    pub async fn set_whitelist_settings(
+        &mut self,
+        settings: WhitelistSettings,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        if self.edit_access_list_live().await? {
+            self.send_command(
+                if settings.enabled {
+                    "whitelist on"
+                } else {
+                    "whitelist off"
+                },
+                caused_by,
+            )
+            .await?;
+        }
+        self.set_server_property(ServerPropertySetting::WhiteList(settings.enabled))
+            .await?;
+        self.set_server_property(ServerPropertySetting::EnforceWhitelist(settings.enforced))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation() {
+        assert!(validate_player_name("Notch").is_ok());
+        assert!(validate_player_name("jeb_").is_ok());
+        assert!(validate_player_name("").is_err());
+        assert!(validate_player_name("Steve\nop Alex").is_err());
+        assert!(validate_player_name("a_name_that_is_too_long").is_err());
+
+        assert!(validate_ip("127.0.0.1").is_ok());
+        assert!(validate_ip("::1").is_ok());
+        assert!(validate_ip("127.0.0.1\nstop").is_err());
+
+        assert_eq!(
+            single_line(Some("griefing\nop me".to_string())),
+            Some("griefing op me".to_string())
+        );
+        assert_eq!(single_line(Some(" \n".to_string())), None);
+
+        assert_eq!(
+            hyphenate_uuid("069a79f444e94726a5befca90e38aaf5"),
+            "069a79f4-44e9-4726-a5be-fca90e38aaf5"
+        );
+    }
+
+    #[test]
+    fn test_access_list_format() {
+        let ops: Vec<OpEntry> = serde_json::from_str(
+            r#"[{"uuid":"069a79f4-44e9-4726-a5be-fca90e38aaf5","name":"Notch","level":4,"bypassesPlayerLimit":false}]"#,
+        )
+        .unwrap();
+        assert_eq!(ops[0].level, 4);
+        assert!(serde_json::to_string(&ops)
+            .unwrap()
+            .contains("\"bypassesPlayerLimit\":false"));
+
+        let bans: Vec<BannedPlayerEntry> = serde_json::from_str(
+            r#"[{"uuid":"069a79f4-44e9-4726-a5be-fca90e38aaf5","name":"Notch","created":"2023-04-01 12:00:00 +0000","source":"Server","expires":"forever","reason":"Banned by an operator."}]"#,
+        )
+        .unwrap();
+        assert_eq!(bans[0].expires, BAN_EXPIRES_NEVER);
+    }
+}
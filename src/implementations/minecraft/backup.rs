@@ -0,0 +1,644 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic;
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use ts_rs::TS;
+
+use crate::error::{Error, ErrorKind};
+use crate::events::{
+    CausedBy, Event, ProgressionEndValue, ProgressionEventID, ProgressionStartValue,
+};
+use crate::traits::t_server::{State, TServer};
+use crate::util::{unzip_file_async, zip_files_async, UnzipOption};
+
+use super::configurable::ServerPropertySetting;
+use super::line_parser::reports_world_saved;
+use super::{MinecraftInstance, RestoreConfig};
+
+/// Name of the directory inside the instance that holds the backup archives.
+/// It is excluded from the backups themselves.
+pub const BACKUP_DIR_NAME: &str = "backups";
+
+const WORLD_SAVE_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub fn default_max_backups() -> u32 {
+    5
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct BackupEntry {
+    pub name: String,
+    pub size: u64,
+    // unix timestamp
+    pub creation_time: Option<u64>,
+}
+
+impl From<&std::path::Path> for BackupEntry {
+    fn from(path: &std::path::Path) -> Self {
+        let metadata = path.metadata().ok();
+        Self {
+            name: path
+                .file_name()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: metadata.as_ref().map(|m| m.len()).unwrap_or_default(),
+            creation_time: metadata
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+        }
+    }
+}
+
+/// `backup-<time>.zip`, with a zero padded `_<n>` suffix when a backup was already taken within
+/// the same second. `_` sorts after `.` and the padding keeps `_010` after `_009`, so the name
+/// order stays the creation order
+fn next_backup_path(path_to_backups: &Path, timestamp: &str) -> PathBuf {
+    let mut path = path_to_backups.join(format!("backup-{timestamp}.zip"));
+    let mut n = 1;
+    while path.exists() {
+        path = path_to_backups.join(format!("backup-{timestamp}_{n:03}.zip"));
+        n += 1;
+    }
+    path
+}
+
+/// Extract a backup into the emptied instance directory, replacing the current config with the backup's.
+///
+/// Extracting never overwrites, a config left in place would push the backup's copy to
+/// `.lodestone_config_1`. The current one is written back if the backup did not bring its own
+async fn extract_backup(
+    backup_path: &Path,
+    path_to_instance: &Path,
+    path_to_config: &Path,
+) -> Result<(), Error> {
+    let current_config = crate::util::fs::read_to_string(path_to_config).await?;
+    crate::util::fs::remove_file(path_to_config).await?;
+    let res = unzip_file_async(
+        backup_path,
+        UnzipOption::ToDir(path_to_instance.to_path_buf()),
+    )
+    .await;
+    if !path_to_config.is_file() {
+        crate::util::fs::write_all(path_to_config, current_config).await?;
+    }
+    res.map(|_| ())
+}
+
+/// The backup archives in `path_to_backups`, oldest first
+async fn list_backups_in(path_to_backups: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !path_to_backups.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(path_to_backups).await.context(format!(
+        "Failed to read backup directory {}",
+        path_to_backups.display()
+    ))?;
+    while let Some(entry) = read_dir
+        .next_entry()
+        .await
+        .context("Failed to read backup directory entry")?
+    {
+        let path = entry.path();
+        if path.is_file() && path.extension().map(|ext| ext == "zip").unwrap_or(false) {
+            backups.push(path);
+        }
+    }
+    // backup names embed their creation time, so sorting by name sorts by age
+    backups.sort();
+    Ok(backups)
+}
+
+/// Remove the oldest backups until at most `max_backups` are left
+async fn prune_backups_in(path_to_backups: &Path, max_backups: usize) -> Result<(), Error> {
+    let backups = list_backups_in(path_to_backups).await?;
+    if backups.len() <= max_backups {
+        return Ok(());
+    }
+    for backup in &backups[..backups.len() - max_backups] {
+        crate::util::fs::remove_file(backup).await?;
+    }
+    Ok(())
+}
+
+impl MinecraftInstance {
+    pub fn path_to_backups(&self) -> PathBuf {
+        self.path_to_instance.join(BACKUP_DIR_NAME)
+    }
+
+    pub async fn set_max_backups(&self, max_backups: u32) -> Result<(), Error> {
+        if max_backups == 0 {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("At least one backup must be kept"),
+            });
+        }
+        self.config.lock().await.max_backups = max_backups;
+        self.write_config_to_file().await?;
+        self.prune_backups().await
+    }
+
+    /// Archive the instance directory into `backups/` and prune the oldest snapshots
+    /// so that at most `max_backups` are kept.
+    pub async fn backup(&self, caused_by: CausedBy) -> Result<PathBuf, Error> {
+        let _backup_guard = self.backup_lock.lock().await;
+        // saving can only be paused once the server accepts commands
+        let state = self.state().await;
+        if state == State::Starting || state == State::Stopping {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!(
+                    "Instance can't be backed up while it is {}",
+                    state.to_string().to_lowercase()
+                ),
+            });
+        }
+        let (progression_start_event, event_id) = Event::new_progression_event_start(
+            format!("Backing up instance {}", self.config.lock().await.name),
+            Some(3.0),
+            None,
+            caused_by,
+        );
+        self.event_broadcaster.send(progression_start_event);
+        // copying a live world produces corrupted region files, so make the server flush
+        // everything to disk and hold off writing until the archive is done
+        let pause_saving = state == State::Running;
+        let res = async {
+            if pause_saving {
+                self.event_broadcaster
+                    .send(Event::new_progression_event_update(
+                        &event_id,
+                        "Saving the world",
+                        1.0,
+                    ));
+                self.pause_world_saving().await?;
+            }
+            self.event_broadcaster
+                .send(Event::new_progression_event_update(
+                    &event_id,
+                    "Archiving instance files",
+                    1.0,
+                ));
+            self.archive_instance().await
+        }
+        .await;
+        if pause_saving {
+            self.resume_world_saving().await;
+        }
+        let backup_path = match res {
+            Ok(path) => path,
+            Err(e) => {
+                self.event_broadcaster
+                    .send(Event::new_progression_event_end(
+                        event_id,
+                        false,
+                        Some(&format!("Failed to back up instance: {e}")),
+                        None,
+                    ));
+                return Err(e);
+            }
+        };
+        self.event_broadcaster
+            .send(Event::new_progression_event_update(
+                &event_id,
+                "Removing old backups",
+                1.0,
+            ));
+        if let Err(e) = self.prune_backups().await {
+            error!("Failed to remove old backups: {e}");
+        }
+        self.event_broadcaster
+            .send(Event::new_progression_event_end(
+                event_id,
+                true,
+                Some("Backup complete"),
+                None,
+            ));
+        info!(
+            "Backed up instance {} to {}",
+            self.uuid,
+            backup_path.display()
+        );
+        Ok(backup_path)
+    }
+
+    /// Send a command through RCON if it is connected, falling back to stdin otherwise.
+    /// Returns the reply when the command went through RCON
+    pub(super) async fn send_server_command(&self, command: &str) -> Result<Option<String>, Error> {
+        let rcon_connected = self.rcon_conn.lock().await.is_some();
+        if rcon_connected {
+            // a failed RCON command may still have run, so it is not repeated over stdin
+            return self.send_rcon(command).await.map(Some);
+        }
+        self.send_command(command, CausedBy::System).await?;
+        Ok(None)
+    }
+
+    /// Issue `save-off` and `save-all flush`, then wait for the server to report that the world was saved
+    async fn pause_world_saving(&self) -> Result<(), Error> {
+        // subscribe before sending the command so the "Saved the game" line can't be missed
+        let mut world_saved_rx = self.world_saved.subscribe();
+        self.send_server_command("save-off").await?;
+        let reply = self.send_server_command("save-all flush").await?;
+        // with broadcast-rcon-to-ops off the console never shows the save, but the reply does
+        if reply.map_or(false, |reply| reports_world_saved(&reply)) {
+            return Ok(());
+        }
+        // a lagged receiver still means at least one save went through
+        let _ = tokio::time::timeout(WORLD_SAVE_TIMEOUT, world_saved_rx.recv())
+            .await
+            .map_err(|_| eyre!("Timed out waiting for the server to save the world"))?;
+        Ok(())
+    }
+
+    async fn resume_world_saving(&self) {
+        if let Err(e) = self.send_server_command("save-on").await {
+            error!(
+                "Failed to re-enable world saving for instance {}: {e}",
+                self.uuid
+            );
+        }
+    }
+
+    async fn archive_instance(&self) -> Result<PathBuf, Error> {
+        let mut files = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(&self.path_to_instance)
+            .await
+            .context(format!(
+                "Failed to read instance directory {}",
+                self.path_to_instance.display()
+            ))?;
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .context("Failed to read instance directory entry")?
+        {
+            if entry.file_name() == BACKUP_DIR_NAME {
+                continue;
+            }
+            files.push(entry.path());
+        }
+        // callers hold `backup_lock`, so nothing else can claim the name in between
+        let dest = next_backup_path(
+            &self.path_to_backups(),
+            &chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string(),
+        );
+        zip_files_async(&files, dest).await
+    }
+
+    /// List the backup archives of this instance, oldest first.
+    pub async fn list_backup_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        list_backups_in(&self.path_to_backups()).await
+    }
+
+    /// List the backups of this instance, oldest first.
+    pub async fn list_backups(&self) -> Result<Vec<BackupEntry>, Error> {
+        Ok(self
+            .list_backup_paths()
+            .await?
+            .iter()
+            .map(|p| BackupEntry::from(p.as_path()))
+            .collect())
+    }
+
+    /// Roll the instance back to the backup named `backup_name`.
+    ///
+    /// The instance is stopped first if needed. If `safety_backup` is set, the current state
+    /// of the instance is archived before any file is touched.
+    pub async fn restore_backup(
+        &mut self,
+        backup_name: &str,
+        safety_backup: bool,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        let backup_path = self
+            .list_backup_paths()
+            .await?
+            .into_iter()
+            .find(|p| p.file_name().map(|n| n == backup_name).unwrap_or(false))
+            .ok_or_else(|| Error {
+                kind: ErrorKind::NotFound,
+                source: eyre!("Backup {backup_name} not found"),
+            })?;
+        let (progression_start_event, event_id) = Event::new_progression_event_start(
+            format!(
+                "Restoring instance {} from {backup_name}",
+                self.config.lock().await.name
+            ),
+            Some(4.0),
+            Some(ProgressionStartValue::InstanceRestore {
+                instance_uuid: self.uuid.clone(),
+                backup_name: backup_name.to_string(),
+            }),
+            caused_by.clone(),
+        );
+        {
+            // taken under the state lock so a concurrent `start` either sees the flag
+            // or has already left `Stopped`, which makes us stop it below
+            let _state = self.state.lock().await;
+            if self.restoring_backup.swap(true, atomic::Ordering::SeqCst) {
+                return Err(Error {
+                    kind: ErrorKind::BadRequest,
+                    source: eyre!("A backup is already being restored"),
+                });
+            }
+        }
+        self.event_broadcaster.send(progression_start_event);
+        let res = self
+            .restore_backup_inner(&backup_path, safety_backup, caused_by, &event_id)
+            .await;
+        self.restoring_backup.store(false, atomic::Ordering::SeqCst);
+        match &res {
+            Ok(_) => self
+                .event_broadcaster
+                .send(Event::new_progression_event_end(
+                    event_id,
+                    true,
+                    Some("Instance restored successfully"),
+                    Some(ProgressionEndValue::InstanceRestore {
+                        instance_uuid: self.uuid.clone(),
+                        backup_name: backup_name.to_string(),
+                    }),
+                )),
+            Err(e) => self
+                .event_broadcaster
+                .send(Event::new_progression_event_end(
+                    event_id,
+                    false,
+                    Some(&format!("Failed to restore instance: {e}")),
+                    None,
+                )),
+        }
+        res
+    }
+
+    async fn restore_backup_inner(
+        &mut self,
+        backup_path: &std::path::Path,
+        safety_backup: bool,
+        caused_by: CausedBy,
+        event_id: &ProgressionEventID,
+    ) -> Result<(), Error> {
+        if self.state().await != State::Stopped {
+            self.event_broadcaster
+                .send(Event::new_progression_event_update(
+                    event_id,
+                    "1/4: Stopping instance",
+                    1.0,
+                ));
+            self.stop(caused_by, true).await?;
+        }
+        let _backup_guard = self.backup_lock.lock().await;
+        if safety_backup {
+            self.event_broadcaster
+                .send(Event::new_progression_event_update(
+                    event_id,
+                    "2/4: Backing up current state",
+                    1.0,
+                ));
+            // not pruned, so the snapshot being restored can't be rotated out from under us
+            self.archive_instance().await?;
+        }
+        self.event_broadcaster
+            .send(Event::new_progression_event_update(
+                event_id,
+                "3/4: Removing current instance files",
+                1.0,
+            ));
+        let mut read_dir = tokio::fs::read_dir(&self.path_to_instance)
+            .await
+            .context(format!(
+                "Failed to read instance directory {}",
+                self.path_to_instance.display()
+            ))?;
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .context("Failed to read instance directory entry")?
+        {
+            // the backup task and instance discovery rely on `.lodestone_config`,
+            // it is only swapped for the backup's copy right before extracting
+            if entry.file_name() == BACKUP_DIR_NAME || entry.file_name() == ".lodestone_config" {
+                continue;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                crate::util::fs::remove_dir_all(&path).await?;
+            } else {
+                crate::util::fs::remove_file(&path).await?;
+            }
+        }
+        self.event_broadcaster
+            .send(Event::new_progression_event_update(
+                event_id,
+                "4/4: Extracting backup",
+                1.0,
+            ));
+        extract_backup(backup_path, &self.path_to_instance, &self.path_to_config).await?;
+        self.reload_config().await
+    }
+
+    /// Re-read the config and server.properties after the files on disk were replaced
+    async fn reload_config(&mut self) -> Result<(), Error> {
+        let restore_config: RestoreConfig =
+            serde_json::from_str(&crate::util::fs::read_to_string(&self.path_to_config).await?)
+                .context(
+                "Failed to deserialize config from string. Was the config file modified manually?",
+            )?;
+        self.auto_start
+            .store(restore_config.auto_start, atomic::Ordering::Relaxed);
+        self.restart_on_crash
+            .store(restore_config.restart_on_crash, atomic::Ordering::Relaxed);
+        *self.config.lock().await = restore_config;
+        self.configurable_manifest
+            .lock()
+            .await
+            .clear_section(ServerPropertySetting::get_section_id());
+        self.read_properties().await
+    }
+
+    async fn prune_backups(&self) -> Result<(), Error> {
+        let max_backups = self.config.lock().await.max_backups as usize;
+        prune_backups_in(&self.path_to_backups(), max_backups).await
+    }
+
+    /// Spawn the task that takes a backup every `backup_period` minutes while the instance is not stopped.
+    ///
+    /// The period is re-read every tick, so changing it takes effect without a restart.
+    /// The task exits once the instance has been deleted.
+    pub(super) fn spawn_backup_task(&self) {
+        let instance = self.clone();
+        tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            let mut last_backup = tokio::time::Instant::now();
+            loop {
+                interval.tick().await;
+                // the config is briefly missing while a backup is extracted
+                if instance.restoring_backup.load(atomic::Ordering::SeqCst) {
+                    continue;
+                }
+                if !instance
+                    .path_to_instance
+                    .join(".lodestone_config")
+                    .is_file()
+                {
+                    break;
+                }
+                let backup_period = match instance.config.lock().await.backup_period {
+                    Some(period) if period > 0 => period,
+                    _ => {
+                        last_backup = tokio::time::Instant::now();
+                        continue;
+                    }
+                };
+                if last_backup.elapsed() < Duration::from_secs(backup_period as u64 * 60) {
+                    continue;
+                }
+                // nothing changes while stopped, and saving can't be paused while starting or stopping
+                if *instance.state.lock().await != State::Running {
+                    continue;
+                }
+                last_backup = tokio::time::Instant::now();
+                if let Err(e) = instance.backup(CausedBy::System).await {
+                    error!(
+                        "Scheduled backup for instance {} failed: {e}",
+                        instance.uuid
+                    );
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_list_backups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path_to_backups = temp_dir.path().join(BACKUP_DIR_NAME);
+        assert!(list_backups_in(&path_to_backups).await.unwrap().is_empty());
+
+        std::fs::create_dir(&path_to_backups).unwrap();
+        for name in [
+            "backup-2023-05-02_10-00-00.zip",
+            "backup-2023-05-01_10-00-00.zip",
+            "notes.txt",
+        ] {
+            std::fs::write(path_to_backups.join(name), "").unwrap();
+        }
+        std::fs::create_dir(path_to_backups.join("not-a-backup.zip")).unwrap();
+
+        // a second backup within the same second gets a suffix that sorts after the first one
+        let path = next_backup_path(&path_to_backups, "2023-05-02_10-00-00");
+        assert_eq!(
+            path,
+            path_to_backups.join("backup-2023-05-02_10-00-00_001.zip")
+        );
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(
+            next_backup_path(&path_to_backups, "2023-05-02_10-00-00"),
+            path_to_backups.join("backup-2023-05-02_10-00-00_002.zip")
+        );
+
+        let names: Vec<String> = list_backups_in(&path_to_backups)
+            .await
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "backup-2023-05-01_10-00-00.zip",
+                "backup-2023-05-02_10-00-00.zip",
+                "backup-2023-05-02_10-00-00_001.zip",
+            ]
+        );
+
+        // the tenth backup within a second still sorts last
+        for _ in 2..=10 {
+            std::fs::write(
+                next_backup_path(&path_to_backups, "2023-05-02_10-00-00"),
+                "",
+            )
+            .unwrap();
+        }
+        let backups = list_backups_in(&path_to_backups).await.unwrap();
+        assert_eq!(
+            backups.last().unwrap(),
+            &path_to_backups.join("backup-2023-05-02_10-00-00_010.zip")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extract_backup() {
+        let temp_lodestone_path = tempfile::tempdir().unwrap();
+        crate::prelude::init_paths(temp_lodestone_path.path().to_path_buf());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path_to_instance = temp_dir.path().join("instance");
+        let path_to_config = path_to_instance.join(".lodestone_config");
+        std::fs::create_dir(&path_to_instance).unwrap();
+        std::fs::write(&path_to_config, r#"{"name":"backed up"}"#).unwrap();
+        std::fs::write(path_to_instance.join("server.properties"), "motd=backed up").unwrap();
+        let backup_path = crate::util::zip_files(
+            &[
+                path_to_config.clone(),
+                path_to_instance.join("server.properties"),
+            ],
+            temp_dir.path().join("backup.zip"),
+        )
+        .unwrap();
+
+        // what restore_backup_inner leaves behind after emptying the instance
+        std::fs::remove_file(path_to_instance.join("server.properties")).unwrap();
+        std::fs::write(&path_to_config, r#"{"name":"current"}"#).unwrap();
+
+        extract_backup(&backup_path, &path_to_instance, &path_to_config)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path_to_config).unwrap(),
+            r#"{"name":"backed up"}"#
+        );
+        assert_eq!(
+            std::fs::read_to_string(path_to_instance.join("server.properties")).unwrap(),
+            "motd=backed up"
+        );
+        assert!(!path_to_instance.join(".lodestone_config_1").exists());
+    }
+
+    #[tokio::test]
+    async fn test_prune_backups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path_to_backups = temp_dir.path();
+        for day in 1..=4 {
+            std::fs::write(
+                path_to_backups.join(format!("backup-2023-05-0{day}_10-00-00.zip")),
+                "",
+            )
+            .unwrap();
+        }
+        std::fs::write(path_to_backups.join("notes.txt"), "").unwrap();
+
+        prune_backups_in(path_to_backups, 5).await.unwrap();
+        assert_eq!(list_backups_in(path_to_backups).await.unwrap().len(), 4);
+
+        prune_backups_in(path_to_backups, 2).await.unwrap();
+        assert_eq!(
+            list_backups_in(path_to_backups).await.unwrap(),
+            vec![
+                path_to_backups.join("backup-2023-05-03_10-00-00.zip"),
+                path_to_backups.join("backup-2023-05-04_10-00-00.zip"),
+            ]
+        );
+        // only backups are pruned
+        assert!(path_to_backups.join("notes.txt").is_file());
+    }
+}
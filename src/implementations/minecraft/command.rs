@@ -0,0 +1,127 @@
+//! Console commands that report the server's response
+use color_eyre::eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use ts_rs::TS;
+
+use crate::error::Error;
+use crate::events::CausedBy;
+use crate::traits::t_server::TServer;
+
+use super::configurable::ServerPropertySetting;
+use super::MinecraftInstance;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct CommandOutput {
+    /// What the server responded with, `None` when the command went through stdin
+    pub output: Option<String>,
+    /// Set when RCON was unavailable and the command was written to stdin instead,
+    /// its output then only shows up in the console
+    pub stdin_fallback: bool,
+}
+
+impl MinecraftInstance {
+    /// The RCON password and port, `None` unless `enable-rcon` is on and both are set
+    pub(super) async fn rcon_settings(&self) -> Option<(String, u32)> {
+        let lock = self.configurable_manifest.lock().await;
+        let enabled = lock
+            .get_unique_setting_key(&ServerPropertySetting::EnableRcon(false).get_identifier())
+            .and_then(|v| v.get_value().map(|v| v.try_as_boolean().ok()))
+            .flatten()
+            .unwrap_or(false);
+        let password = lock
+            .get_unique_setting_key(
+                &ServerPropertySetting::RconPassword(String::new()).get_identifier(),
+            )
+            .and_then(|v| v.get_value().map(|v| v.try_as_string().ok()))
+            .flatten()
+            .filter(|password| !password.is_empty())
+            .cloned();
+        let port = lock
+            .get_unique_setting_key(&ServerPropertySetting::RconPort(0).get_identifier())
+            .and_then(|v| v.get_value().map(|v| v.try_as_unsigned_integer().ok()))
+            .flatten();
+        match (enabled, password, port) {
+            (true, Some(password), Some(port)) => Some((password, port)),
+            _ => None,
+        }
+    }
+
+    /// Replaces the current RCON connection, if any
+    pub(super) async fn connect_rcon(&self) -> Result<(), Error> {
+        let (password, port) = self
+            .rcon_settings()
+            .await
+            .ok_or_else(|| eyre!("RCON is not enabled or misconfigured"))?;
+        let rcon = <rcon::Connection<tokio::net::TcpStream>>::builder()
+            .enable_minecraft_quirks(true)
+            .connect(&format!("localhost:{}", port), &password)
+            .await
+            .context("Failed to connect to RCON")?;
+        self.rcon_conn.lock().await.replace(rcon);
+        Ok(())
+    }
+
+    /// Connects to RCON unless a connection is already open
+    pub(super) async fn ensure_rcon_connected(&self) -> Result<(), Error> {
+        if self.rcon_conn.lock().await.is_some() {
+            return Ok(());
+        }
+        self.connect_rcon().await
+    }
+
+    /// Run a command through RCON to get its output, writing it to stdin when RCON is unavailable.
+    /// A command that reached RCON is never written to stdin as well, it may already have run
+    pub async fn run_command(
+        &self,
+        command: &str,
+        caused_by: CausedBy,
+    ) -> Result<CommandOutput, Error> {
+        // stopping has to go through stdin so the state transition is tracked
+        if !is_stop_command(command) {
+            match self.ensure_rcon_connected().await {
+                Ok(()) => {
+                    return Ok(CommandOutput {
+                        output: Some(self.send_rcon(command).await?),
+                        stdin_fallback: false,
+                    })
+                }
+                Err(e) => {
+                    let name = self.config.lock().await.name.clone();
+                    warn!("[{}] Falling back to stdin for command: {}", name, e);
+                }
+            }
+        }
+        self.send_command(command, caused_by).await?;
+        Ok(CommandOutput {
+            output: None,
+            stdin_fallback: true,
+        })
+    }
+}
+
+/// The console accepts a leading slash, and Bukkit based servers ignore the case of the command
+pub(super) fn is_stop_command(command: &str) -> bool {
+    let command = command.trim();
+    let command = command.strip_prefix('/').unwrap_or(command);
+    command
+        .split_whitespace()
+        .next()
+        .map_or(false, |label| label.eq_ignore_ascii_case("stop"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_stop_command() {
+        assert!(is_stop_command("stop"));
+        assert!(is_stop_command(" /stop\n"));
+        assert!(is_stop_command("STOP"));
+        assert!(!is_stop_command("stopsound @a"));
+        assert!(!is_stop_command("say stop"));
+        assert!(!is_stop_command(""));
+    }
+}
@@ -104,11 +104,16 @@ impl TConfigurable for MinecraftInstance {
 
     async fn set_restart_on_crash(&mut self, restart_on_crash: bool) -> Result<(), Error> {
         self.config.lock().await.restart_on_crash = restart_on_crash;
-        self.auto_start
+        self.restart_on_crash
             .store(restart_on_crash, atomic::Ordering::Relaxed);
         self.write_config_to_file().await
     }
 
+    async fn set_backup_period(&mut self, backup_period: Option<u32>) -> Result<(), Error> {
+        self.config.lock().await.backup_period = backup_period;
+        self.write_config_to_file().await
+    }
+
     async fn change_version(&mut self, version: String) -> Result<(), Error> {
         if *self.state.lock().await != State::Stopped {
             return Err(Error {
@@ -167,6 +172,7 @@ impl TConfigurable for MinecraftInstance {
         )
         .await?;
         let jar_path = temp_dir.path().join("server.jar");
+        self.check_world_downgrade(&version, &jar_path).await?;
         crate::util::fs::rename(jar_path, self.path().await.join("server.jar")).await?;
         self.config.lock().await.version = version;
         self.write_config_to_file().await
@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, warn};
+use ts_rs::TS;
+
+use crate::error::{Error, ErrorKind};
+use crate::events::{CausedBy, InstanceEventInner};
+use crate::traits::t_server::{State, TServer};
+
+use super::MinecraftInstance;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub enum CountdownAction {
+    Stop,
+    Restart,
+}
+
+impl CountdownAction {
+    fn verb(&self) -> &'static str {
+        match self {
+            CountdownAction::Stop => "stop",
+            CountdownAction::Restart => "restart",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export)]
+pub struct CountdownConfig {
+    /// Seconds before the action at which players are warned.
+    /// The longest one is how long the countdown lasts
+    pub warnings: Vec<u32>,
+    /// `{action}` and `{time}` are replaced with e.g. "restart" and "5 minutes"
+    pub message: String,
+    /// Broadcast with `tellraw` instead of `say`
+    pub use_tellraw: bool,
+}
+
+impl Default for CountdownConfig {
+    fn default() -> Self {
+        Self {
+            warnings: vec![300, 60, 30, 10],
+            message: "Server will {action} in {time}".to_string(),
+            use_tellraw: false,
+        }
+    }
+}
+
+fn format_countdown_time(seconds: u32) -> String {
+    fn plural(count: u32, unit: &str) -> String {
+        if count == 1 {
+            format!("{count} {unit}")
+        } else {
+            format!("{count} {unit}s")
+        }
+    }
+    match (seconds / 60, seconds % 60) {
+        (0, seconds) => plural(seconds, "second"),
+        (minutes, 0) => plural(minutes, "minute"),
+        (minutes, seconds) => format!(
+            "{} {}",
+            plural(minutes, "minute"),
+            plural(seconds, "second")
+        ),
+    }
+}
+
+impl MinecraftInstance {
+    pub async fn set_countdown_config(&self, countdown: CountdownConfig) -> Result<(), Error> {
+        if countdown.message.trim().is_empty() {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Countdown message cannot be empty"),
+            });
+        }
+        self.config.lock().await.countdown = countdown;
+        self.write_config_to_file().await
+    }
+
+    /// Warn players at the configured intervals, then stop or restart the server.
+    /// Returns once the countdown has started
+    pub async fn start_countdown(
+        &self,
+        action: CountdownAction,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        if self.state().await != State::Running {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Instance is not running"),
+            });
+        }
+        // hold the lock until the handle is stored so the task can't clear the slot first
+        let mut countdown = self.countdown.lock().await;
+        if countdown.is_some() {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("A countdown is already running"),
+            });
+        }
+        let config = self.config.lock().await.countdown.clone();
+        let mut warnings = config.warnings.clone();
+        warnings.sort_unstable_by(|a, b| b.cmp(a));
+        warnings.dedup();
+        self.send_instance_event(
+            InstanceEventInner::SystemMessage {
+                message: format!(
+                    "Server will {} in {}",
+                    action.verb(),
+                    format_countdown_time(warnings.first().copied().unwrap_or(0))
+                ),
+            },
+            caused_by.clone(),
+        )
+        .await;
+        let mut instance = self.clone();
+        let handle = tokio::task::spawn(async move {
+            let mut remaining = warnings.first().copied().unwrap_or(0);
+            for warning in warnings {
+                tokio::time::sleep(Duration::from_secs((remaining - warning) as u64)).await;
+                remaining = warning;
+                let message = config
+                    .message
+                    .replace("{action}", action.verb())
+                    .replace("{time}", &format_countdown_time(warning));
+                if let Err(e) = instance
+                    .broadcast_message(&message, config.use_tellraw)
+                    .await
+                {
+                    warn!(
+                        "[{}] Failed to broadcast countdown warning: {}",
+                        instance.uuid, e
+                    );
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(remaining as u64)).await;
+            // from here on the countdown can no longer be cancelled
+            instance.countdown.lock().await.take();
+            if instance.state().await != State::Running {
+                return;
+            }
+            let result = match action {
+                CountdownAction::Stop => instance.stop(caused_by, false).await,
+                CountdownAction::Restart => instance.restart(caused_by, false).await,
+            };
+            if let Err(e) = result {
+                error!(
+                    "[{}] Failed to {} instance after countdown: {}",
+                    instance.uuid,
+                    action.verb(),
+                    e
+                );
+            }
+        });
+        *countdown = Some((action, handle));
+        Ok(())
+    }
+
+    pub async fn cancel_countdown(&self, caused_by: CausedBy) -> Result<(), Error> {
+        let (action, handle) = self.countdown.lock().await.take().ok_or_else(|| Error {
+            kind: ErrorKind::BadRequest,
+            source: eyre!("No countdown is running"),
+        })?;
+        handle.abort();
+        let use_tellraw = self.config.lock().await.countdown.use_tellraw;
+        let message = format!("The server {} was cancelled", action.verb());
+        if let Err(e) = self.broadcast_message(&message, use_tellraw).await {
+            warn!(
+                "[{}] Failed to broadcast countdown cancellation: {}",
+                self.uuid, e
+            );
+        }
+        self.send_instance_event(InstanceEventInner::SystemMessage { message }, caused_by)
+            .await;
+        Ok(())
+    }
+
+    async fn broadcast_message(&self, message: &str, use_tellraw: bool) -> Result<(), Error> {
+        let command = if use_tellraw {
+            format!(
+                "tellraw @a {}",
+                json!({ "text": message, "color": "yellow" })
+            )
+        } else {
+            format!("say {message}")
+        };
+        self.send_server_command(&command).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_countdown_time() {
+        assert_eq!(format_countdown_time(300), "5 minutes");
+        assert_eq!(format_countdown_time(60), "1 minute");
+        assert_eq!(format_countdown_time(90), "1 minute 30 seconds");
+        assert_eq!(format_countdown_time(1), "1 second");
+        assert_eq!(format_countdown_time(10), "10 seconds");
+    }
+}
@@ -0,0 +1,400 @@
+//! Minecraft crash reports and JVM fatal error logs
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use color_eyre::eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::error::{Error, ErrorKind};
+
+use super::MinecraftInstance;
+
+const CRASH_REPORTS_DIR_NAME: &str = "crash-reports";
+const HS_ERR_PREFIX: &str = "hs_err_pid";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum CrashReportKind {
+    /// `crash-reports/crash-*.txt` written by the game
+    Minecraft,
+    /// `hs_err_pid*.log` written by the JVM when it dies
+    JvmFatalError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct CrashReportSummary {
+    pub file_name: String,
+    pub kind: CrashReportKind,
+    /// unix timestamp in seconds of when the report was written
+    pub time: i64,
+    pub description: Option<String>,
+    /// The exception for crash reports, the problematic frame for JVM errors
+    pub exception: Option<String>,
+    pub suspected_mods: Vec<String>,
+    pub java_version: Option<String>,
+    pub jvm: Option<String>,
+}
+
+impl Display for CrashReportSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Crash report {}: {}",
+            self.file_name,
+            self.description.as_deref().unwrap_or("no description")
+        )?;
+        if let Some(exception) = &self.exception {
+            write!(f, "\n{}", exception)?;
+        }
+        if !self.suspected_mods.is_empty() {
+            write!(f, "\nSuspected mods: {}", self.suspected_mods.join(", "))?;
+        }
+        if let Some(java_version) = &self.java_version {
+            write!(f, "\nJava: {}", java_version)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct CrashReport {
+    pub summary: CrashReportSummary,
+    pub content: String,
+}
+
+/// `key: value` lines, such as the ones under `Details:` in a crash report
+fn find_value<'a>(content: &'a str, key: &str) -> Option<&'a str> {
+    content.lines().find_map(|line| {
+        line.trim_start_matches(['\t', ' ', '#'])
+            .strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    })
+}
+
+/// The suspected mods are either listed inline or one per indented line below the key.
+/// Forge prints "Suspected Mod:" and Fabric "Suspected Mods:"
+fn suspected_mods(content: &str) -> Vec<String> {
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        let rest = match trimmed
+            .strip_prefix("Suspected Mods:")
+            .or_else(|| trimmed.strip_prefix("Suspected Mod:"))
+        {
+            Some(rest) => rest.trim(),
+            None => continue,
+        };
+        let indent = line.len() - trimmed.len();
+        let mods: Vec<String> = if rest.is_empty() {
+            lines
+                .take_while(|l| l.len() - l.trim_start().len() > indent && !l.trim().is_empty())
+                .filter(|l| l.len() - l.trim_start().len() == indent + 1)
+                .map(|l| l.trim().to_string())
+                .collect()
+        } else {
+            rest.split(", ").map(|m| m.trim().to_string()).collect()
+        };
+        return mods
+            .into_iter()
+            .filter(|m| !m.is_empty() && !m.eq_ignore_ascii_case("none"))
+            .filter(|m| !m.eq_ignore_ascii_case("unknown"))
+            .collect();
+    }
+    Vec::new()
+}
+
+/// The first line of the stack trace following the description
+fn minecraft_exception(content: &str) -> Option<String> {
+    content
+        .lines()
+        .skip_while(|line| !line.starts_with("Description:"))
+        .skip(1)
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .filter(|line| !line.starts_with("at ") && !line.starts_with("A detailed walkthrough"))
+        .map(str::to_string)
+}
+
+fn parse_minecraft_crash_report(content: &str) -> (Option<String>, Option<String>) {
+    (
+        find_value(content, "Description").map(str::to_string),
+        minecraft_exception(content),
+    )
+}
+
+/// hs_err files start with a `#` comment block describing the failure
+fn parse_hs_err(content: &str) -> (Option<String>, Option<String>) {
+    let header: Vec<&str> = content
+        .lines()
+        .take_while(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim())
+        .collect();
+    let description = header
+        .iter()
+        .skip_while(|line| !line.starts_with("A fatal error has been detected"))
+        .skip(1)
+        .find(|line| !line.is_empty())
+        .or_else(|| header.iter().find(|line| !line.is_empty()))
+        .map(|line| line.to_string());
+    let exception = header
+        .iter()
+        .skip_while(|line| !line.starts_with("Problematic frame:"))
+        .nth(1)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string());
+    (description, exception)
+}
+
+pub fn parse_crash_report(file_name: &str, time: i64, content: &str) -> CrashReportSummary {
+    if file_name.starts_with(HS_ERR_PREFIX) {
+        let (description, exception) = parse_hs_err(content);
+        CrashReportSummary {
+            file_name: file_name.to_string(),
+            kind: CrashReportKind::JvmFatalError,
+            time,
+            description,
+            exception,
+            suspected_mods: Vec::new(),
+            java_version: find_value(content, "JRE version").map(str::to_string),
+            jvm: find_value(content, "Java VM").map(str::to_string),
+        }
+    } else {
+        let (description, exception) = parse_minecraft_crash_report(content);
+        CrashReportSummary {
+            file_name: file_name.to_string(),
+            kind: CrashReportKind::Minecraft,
+            time,
+            description,
+            exception,
+            suspected_mods: suspected_mods(content),
+            java_version: find_value(content, "Java Version").map(str::to_string),
+            jvm: find_value(content, "Java VM Version").map(str::to_string),
+        }
+    }
+}
+
+fn is_crash_report_file(dir_is_crash_reports: bool, file_name: &str) -> bool {
+    if dir_is_crash_reports {
+        file_name.starts_with("crash-") && file_name.ends_with(".txt")
+    } else {
+        file_name.starts_with(HS_ERR_PREFIX) && file_name.ends_with(".log")
+    }
+}
+
+/// Crash report files in the instance, newest first
+fn crash_report_files(path_to_instance: &Path) -> Vec<(PathBuf, SystemTime)> {
+    let mut ret: Vec<(PathBuf, SystemTime)> = [
+        (path_to_instance.join(CRASH_REPORTS_DIR_NAME), true),
+        (path_to_instance.to_path_buf(), false),
+    ]
+    .into_iter()
+    .filter_map(|(dir, is_crash_reports)| {
+        Some(
+            std::fs::read_dir(dir)
+                .ok()?
+                .filter_map(|entry| entry.ok())
+                .filter(move |entry| {
+                    entry.file_type().map(|t| t.is_file()).unwrap_or(false)
+                        && is_crash_report_file(
+                            is_crash_reports,
+                            &entry.file_name().to_string_lossy(),
+                        )
+                }),
+        )
+    })
+    .flatten()
+    .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?.modified().ok()?)))
+    .collect();
+    ret.sort_by(|a, b| b.1.cmp(&a.1));
+    ret
+}
+
+fn read_crash_report(path: &Path, modified: SystemTime) -> Result<CrashReport, Error> {
+    let content = String::from_utf8_lossy(
+        &std::fs::read(path).context(format!("Failed to read {}", path.display()))?,
+    )
+    .into_owned();
+    let time = modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(CrashReport {
+        summary: parse_crash_report(&file_name, time, &content),
+        content,
+    })
+}
+
+impl MinecraftInstance {
+    pub async fn list_crash_reports(&self) -> Result<Vec<CrashReportSummary>, Error> {
+        let path_to_instance = self.path_to_instance.clone();
+        tokio::task::spawn_blocking(move || {
+            crash_report_files(&path_to_instance)
+                .into_iter()
+                .filter_map(|(path, modified)| read_crash_report(&path, modified).ok())
+                .map(|report| report.summary)
+                .collect()
+        })
+        .await
+        .context("Failed to list crash reports")
+        .map_err(Error::from)
+    }
+
+    pub async fn get_crash_report(&self, file_name: &str) -> Result<CrashReport, Error> {
+        let path_to_instance = self.path_to_instance.clone();
+        let file_name = file_name.to_string();
+        tokio::task::spawn_blocking(move || {
+            // only files that show up in the listing can be read
+            let (path, modified) = crash_report_files(&path_to_instance)
+                .into_iter()
+                .find(|(path, _)| {
+                    path.file_name()
+                        .map_or(false, |name| name == file_name.as_str())
+                })
+                .ok_or_else(|| Error {
+                    kind: ErrorKind::NotFound,
+                    source: eyre!("Crash report {} not found", file_name),
+                })?;
+            read_crash_report(&path, modified)
+        })
+        .await
+        .context("Failed to read crash report")?
+    }
+
+    /// Crash reports written since `since`, used to explain why the server died
+    pub(super) async fn crash_reports_since(&self, since: SystemTime) -> Vec<CrashReportSummary> {
+        let path_to_instance = self.path_to_instance.clone();
+        tokio::task::spawn_blocking(move || {
+            crash_report_files(&path_to_instance)
+                .into_iter()
+                .filter(|(_, modified)| *modified >= since)
+                .filter_map(|(path, modified)| read_crash_report(&path, modified).ok())
+                .map(|report| report.summary)
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRASH_REPORT: &str = r#"---- Minecraft Crash Report ----
+// Why did you do that?
+
+Time: 2023-06-12 12:00:00
+Description: Exception in server tick loop
+
+java.lang.NullPointerException: Cannot invoke "net.minecraft.world.entity.Entity.getId()" because "entity" is null
+	at net.minecraft.server.level.ServerLevel.tick(ServerLevel.java:123)
+	at net.minecraft.server.MinecraftServer.tickChildren(MinecraftServer.java:456)
+
+
+A detailed walkthrough of the error, its code path and all known details is as follows:
+---------------------------------------------------------------------------------------
+
+-- System Details --
+Details:
+	Minecraft Version: 1.20.1
+	Minecraft Version ID: 1.20.1
+	Operating System: Linux (amd64) version 6.1.0
+	Java Version: 17.0.7, Eclipse Adoptium
+	Java VM Version: OpenJDK 64-Bit Server VM (mixed mode, sharing), Eclipse Adoptium
+	Suspected Mods:
+		Lithium (lithium)
+			Issue tracker URL: https://github.com/CaffeineMC/lithium-fabric/issues
+		Fabric API (fabric-api)
+	Server Running: true
+"#;
+
+    const HS_ERR: &str = r#"#
+# A fatal error has been detected by the Java Runtime Environment:
+#
+#  SIGSEGV (0xb) at pc=0x00007f3a2c1d2e45, pid=4242, tid=4243
+#
+# JRE version: OpenJDK Runtime Environment Temurin-17.0.7+7 (17.0.7+7) (build 17.0.7+7)
+# Java VM: OpenJDK 64-Bit Server VM Temurin-17.0.7+7 (17.0.7+7, mixed mode, sharing, tiered, g1 gc, linux-amd64)
+# Problematic frame:
+# C  [libc.so.6+0x1a2e45]  __memmove_avx_unaligned_erms+0x45
+#
+
+---------------  S U M M A R Y ------------
+"#;
+
+    #[test]
+    fn test_parse_crash_report() {
+        let summary = parse_crash_report(
+            "crash-2023-06-12_12.00.00-server.txt",
+            1686571200,
+            CRASH_REPORT,
+        );
+        assert_eq!(
+            summary,
+            CrashReportSummary {
+                file_name: "crash-2023-06-12_12.00.00-server.txt".to_string(),
+                kind: CrashReportKind::Minecraft,
+                time: 1686571200,
+                description: Some("Exception in server tick loop".to_string()),
+                exception: Some("java.lang.NullPointerException: Cannot invoke \"net.minecraft.world.entity.Entity.getId()\" because \"entity\" is null".to_string()),
+                suspected_mods: vec!["Lithium (lithium)".to_string(), "Fabric API (fabric-api)".to_string()],
+                java_version: Some("17.0.7, Eclipse Adoptium".to_string()),
+                jvm: Some("OpenJDK 64-Bit Server VM (mixed mode, sharing), Eclipse Adoptium".to_string()),
+            }
+        );
+        assert_eq!(
+            suspected_mods("\tSuspected Mods: None\n"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            suspected_mods("\tSuspected Mods: Sodium (sodium), Iris (iris)\n"),
+            vec!["Sodium (sodium)".to_string(), "Iris (iris)".to_string()]
+        );
+
+        let summary = parse_crash_report("hs_err_pid4242.log", 0, HS_ERR);
+        assert_eq!(summary.kind, CrashReportKind::JvmFatalError);
+        assert_eq!(
+            summary.description.as_deref(),
+            Some("SIGSEGV (0xb) at pc=0x00007f3a2c1d2e45, pid=4242, tid=4243")
+        );
+        assert_eq!(
+            summary.exception.as_deref(),
+            Some("C  [libc.so.6+0x1a2e45]  __memmove_avx_unaligned_erms+0x45")
+        );
+        assert_eq!(
+            summary.java_version.as_deref(),
+            Some("OpenJDK Runtime Environment Temurin-17.0.7+7 (17.0.7+7) (build 17.0.7+7)")
+        );
+        assert!(summary.jvm.unwrap().starts_with("OpenJDK 64-Bit Server VM"));
+    }
+
+    #[test]
+    fn test_crash_report_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let crash_reports = temp_dir.path().join(CRASH_REPORTS_DIR_NAME);
+        std::fs::create_dir(&crash_reports).unwrap();
+        std::fs::write(crash_reports.join("crash-1-server.txt"), CRASH_REPORT).unwrap();
+        std::fs::write(crash_reports.join("notes.txt"), "").unwrap();
+        std::fs::write(temp_dir.path().join("hs_err_pid4242.log"), HS_ERR).unwrap();
+        std::fs::write(temp_dir.path().join("latest.log"), "").unwrap();
+
+        let mut names: Vec<String> = crash_report_files(temp_dir.path())
+            .into_iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["crash-1-server.txt", "hs_err_pid4242.log"]);
+    }
+}
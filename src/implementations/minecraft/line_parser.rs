@@ -1,75 +1,716 @@
+//! Turns console output into structured events with regex rules.
+//!
+//! A line is first split into its log4j header and message, rules are then matched against
+//! the message in order and the first match wins. Each flavour ships a default rule set,
+//! instances can put their own rules in front of it for chat plugins or localized servers.
+//! Lifecycle rules are matched before all others, so no custom rule can hide them.
+use color_eyre::eyre::eyre;
 use fancy_regex::Regex;
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use ts_rs::TS;
 
-pub struct PlayerMessage {
-    pub player: String,
-    pub message: String,
+use crate::error::{Error, ErrorKind};
+
+use super::{FlavourKind, MinecraftInstance, RestoreConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum LineRuleKind {
+    /// needs the `player` and `message` capture groups
+    Chat,
+    /// needs the `player` capture group
+    PlayerJoined,
+    /// needs the `player` capture group
+    PlayerLeft,
+    /// needs the `player` and `uuid` capture groups
+    PlayerUuid,
+    /// needs the `player` capture group, `message` defaults to the whole line
+    Death,
+    /// needs the `player` and `advancement` capture groups
+    Advancement,
+    ServerStarted,
+    WorldSaved,
+    /// `message` defaults to the whole line
+    LagWarning,
+    /// `message` defaults to the whole line
+    Exception,
 }
 
-pub fn parse_system_msg(msg: &str) -> Option<String> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"\[.+\]+: (?!<)(.+)").unwrap();
+impl LineRuleKind {
+    fn required_groups(&self) -> &'static [&'static str] {
+        match self {
+            LineRuleKind::Chat => &["player", "message"],
+            LineRuleKind::PlayerJoined | LineRuleKind::PlayerLeft | LineRuleKind::Death => {
+                &["player"]
+            }
+            LineRuleKind::PlayerUuid => &["player", "uuid"],
+            LineRuleKind::Advancement => &["player", "advancement"],
+            LineRuleKind::ServerStarted
+            | LineRuleKind::WorldSaved
+            | LineRuleKind::LagWarning
+            | LineRuleKind::Exception => &[],
+        }
     }
-    if RE.is_match(msg).ok()? {
-        RE.captures(msg)
-            .ok()?
-            .map(|caps| caps.get(1).unwrap().as_str().to_string())
-    } else {
-        None
+
+    /// Lifecycle lines drive the instance state and backups, so missing one is never acceptable
+    fn is_lifecycle(&self) -> bool {
+        matches!(self, LineRuleKind::ServerStarted | LineRuleKind::WorldSaved)
     }
 }
 
-pub fn parse_player_msg(msg: &str) -> Option<PlayerMessage> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"\[.+\]+: <(.+)> (.+)").unwrap();
+/// A regex matched against the message part of a console line, named capture groups supply the fields
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct LineRule {
+    pub kind: LineRuleKind,
+    pub pattern: String,
+}
+
+impl LineRule {
+    fn new(kind: LineRuleKind, pattern: &str) -> Self {
+        Self {
+            kind,
+            pattern: pattern.to_string(),
+        }
     }
-    if RE.is_match(msg).unwrap() {
-        if let Some(cap) = RE.captures(msg).ok()? {
-            Some(PlayerMessage {
-                player: cap.get(1)?.as_str().to_string(),
-                message: cap.get(2)?.as_str().to_string(),
-            })
-        } else {
-            None
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineEvent {
+    Chat { player: String, message: String },
+    PlayerJoined { player: String },
+    PlayerLeft { player: String },
+    PlayerUuid { player: String, uuid: String },
+    Death { player: String, message: String },
+    Advancement { player: String, advancement: String },
+    ServerStarted,
+    WorldSaved,
+    LagWarning { message: String },
+    Exception { message: String },
+}
+
+impl LineEvent {
+    /// The level of lines the rules recognized as a problem, for those printed without one
+    pub fn problem_level(&self) -> Option<ProblemLevel> {
+        match self {
+            LineEvent::LagWarning { .. } => Some(ProblemLevel::Warning),
+            LineEvent::Exception { .. } => Some(ProblemLevel::Error),
+            _ => None,
         }
-    } else {
-        None
     }
 }
 
-pub fn parse_player_joined(system_msg: &str) -> Option<String> {
+/// A console line split into its log4j header and the message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine<'a> {
+    pub thread: Option<&'a str>,
+    pub level: &'a str,
+    pub message: &'a str,
+}
+
+/// Handles the vanilla, Fabric and Forge layout `[time] [thread/LEVEL] (optional [logger]): message`
+/// and the Bukkit layout `[time LEVEL]: message`
+pub fn split_log_line(line: &str) -> Option<LogLine> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r"(.+) joined the game").unwrap();
+        static ref LOG4J: Regex = Regex::new(
+            r"^\[[^\]]+\] \[(?P<thread>[^\]]+)/(?P<level>[A-Z]+)\](?: \[[^\]]*\])?: (?P<message>.*)$"
+        )
+        .unwrap();
+        static ref BUKKIT: Regex =
+            Regex::new(r"^\[[\d:]+ (?P<level>[A-Z]+)\]: (?P<message>.*)$").unwrap();
     }
-    if RE.is_match(system_msg).unwrap() {
-        if let Some(cap) = RE.captures(system_msg).ok()? {
-            Some(cap.get(1)?.as_str().to_string())
-        } else {
-            None
+    let line = line.trim_end_matches(['\r', '\n']);
+    let caps = match LOG4J.captures(line).ok()? {
+        Some(caps) => caps,
+        None => BUKKIT.captures(line).ok()??,
+    };
+    Some(LogLine {
+        thread: caps.name("thread").map(|m| m.as_str()),
+        level: caps.name("level")?.as_str(),
+        message: caps.name("message")?.as_str(),
+    })
+}
+
+const PLAYER: &str = r"(?P<player>[A-Za-z0-9_]{1,16})";
+
+/// "Saved the world" on versions before 1.13
+const WORLD_SAVED: &str = r"Saved the (?:game|world)";
+
+/// Whether the output of `save-all` reports the save. RCON joins the messages of a command
+/// without a separator, so the output is searched instead of matched from the start
+pub fn reports_world_saved(output: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(WORLD_SAVED).unwrap();
+    }
+    RE.is_match(output).unwrap_or(false)
+}
+
+/// Rules shared by every flavour
+fn common_rules() -> Vec<LineRule> {
+    vec![
+        // chat goes before the other player events so players cannot fake them by typing them,
+        // lifecycle rules are tried before all of these, see `LineParser::parse`
+        LineRule::new(
+            LineRuleKind::Chat,
+            r"^(?:\[Not Secure\] )?<(?P<player>[^>]+)> (?P<message>.*)$",
+        ),
+        LineRule::new(
+            LineRuleKind::PlayerUuid,
+            &format!(
+                r"^UUID of player {PLAYER} is (?P<uuid>[0-9a-fA-F]{{8}}-?[0-9a-fA-F]{{4}}-?[0-9a-fA-F]{{4}}-?[0-9a-fA-F]{{4}}-?[0-9a-fA-F]{{12}})$"
+            ),
+        ),
+        LineRule::new(
+            LineRuleKind::PlayerJoined,
+            &format!(r"^{PLAYER} joined the game$"),
+        ),
+        LineRule::new(
+            LineRuleKind::PlayerLeft,
+            &format!(r"^{PLAYER} left the game$"),
+        ),
+        LineRule::new(
+            LineRuleKind::Advancement,
+            &format!(
+                r"^{PLAYER} has (?:made the advancement|completed the challenge|reached the goal) \[(?P<advancement>.+)\]$"
+            ),
+        ),
+        LineRule::new(LineRuleKind::ServerStarted, r"^Done \([^)]+\)!"),
+        // wrapped in "[Rcon: ...]" when issued over RCON
+        LineRule::new(
+            LineRuleKind::WorldSaved,
+            &format!(r"^(?:\[Rcon: )?{WORLD_SAVED}"),
+        ),
+        LineRule::new(
+            LineRuleKind::LagWarning,
+            r"^Can't keep up! Is the server overloaded\?",
+        ),
+        LineRule::new(
+            LineRuleKind::Exception,
+            r"^(?:Encountered an unexpected exception|Exception in server tick loop|Exception in thread |(?:[a-z_$][\w$]*\.)+[A-Z][\w$]*(?:Exception|Error)\b)",
+        ),
+        // death messages always start with the victim, this covers the vanilla ones
+        LineRule::new(
+            LineRuleKind::Death,
+            &format!(
+                r"^{PLAYER} (?:was |drowned|died|blew up|hit the ground too hard|fell |burned to death|went up in flames|went off with a bang|walked into|tried to swim in lava|suffocated|starved to death|experienced kinetic energy|withered away|froze to death|discovered the floor was lava|didn't want to live|left the confines of this world)"
+            ),
+        ),
+    ]
+}
+
+pub fn default_rules(flavour: &FlavourKind) -> Vec<LineRule> {
+    let mut rules = common_rules();
+    match flavour {
+        FlavourKind::Paper | FlavourKind::Spigot => {
+            rules.push(LineRule::new(
+                LineRuleKind::Exception,
+                r"^(?:Could not pass event \S+ to |Error occurred while (?:enabling|disabling) |Could not load ')",
+            ));
+        }
+        FlavourKind::Forge => {
+            rules.push(LineRule::new(
+                LineRuleKind::Exception,
+                r"^(?:Exception caught during firing event|Failed to load class |Caught exception from )",
+            ));
         }
-    } else {
-        None
+        FlavourKind::Fabric => {
+            rules.push(LineRule::new(
+                LineRuleKind::Exception,
+                r"^(?:Mixin apply for mod \S+ failed|Could not execute entrypoint )",
+            ));
+        }
+        FlavourKind::Vanilla => {}
     }
+    rules
 }
 
-pub fn parse_player_left(system_msg: &str) -> Option<String> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"(.+) left the game").unwrap();
+#[derive(Debug, Clone)]
+pub struct LineParser {
+    lifecycle_rules: Vec<(LineRuleKind, Regex)>,
+    rules: Vec<(LineRuleKind, Regex)>,
+}
+
+impl LineParser {
+    /// Custom rules are tried before the flavour's defaults
+    pub fn new(flavour: &FlavourKind, custom_rules: &[LineRule]) -> Result<Self, Error> {
+        let (lifecycle_rules, rules): (Vec<_>, Vec<_>) = custom_rules
+            .iter()
+            .chain(default_rules(flavour).iter())
+            .map(|rule| Ok((rule.kind, compile_rule(rule)?)))
+            .collect::<Result<Vec<_>, Error>>()?
+            .into_iter()
+            .partition(|(kind, _)| kind.is_lifecycle());
+        Ok(Self {
+            lifecycle_rules,
+            rules,
+        })
     }
-    if RE.is_match(system_msg).unwrap() {
-        if let Some(cap) = RE.captures(system_msg).ok()? {
-            Some(cap.get(1)?.as_str().to_string())
-        } else {
-            None
+
+    pub fn parse(&self, line: &str) -> Option<LineEvent> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        // lines without a recognizable header, such as stack traces, are matched as a whole
+        let message = split_log_line(line).map_or(line, |log_line| log_line.message);
+        self.lifecycle_rules
+            .iter()
+            .chain(self.rules.iter())
+            .find_map(|(kind, regex)| match_rule(*kind, regex, message))
+    }
+}
+
+/// Check that a rule compiles and has the capture groups its kind needs
+pub fn compile_rule(rule: &LineRule) -> Result<Regex, Error> {
+    let regex = Regex::new(&rule.pattern).map_err(|e| Error {
+        kind: ErrorKind::BadRequest,
+        source: eyre!("Invalid pattern {}: {}", rule.pattern, e),
+    })?;
+    for group in rule.kind.required_groups() {
+        if !regex.capture_names().any(|name| name == Some(*group)) {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!(
+                    "Pattern {} for {:?} is missing the named group `{}`",
+                    rule.pattern,
+                    rule.kind,
+                    group
+                ),
+            });
         }
-    } else {
-        None
     }
+    Ok(regex)
 }
 
-pub fn parse_server_started(system_msg: &str) -> bool {
+fn match_rule(kind: LineRuleKind, regex: &Regex, message: &str) -> Option<LineEvent> {
+    let caps = regex.captures(message).ok()??;
+    let group = |name: &str| caps.name(name).map(|m| m.as_str().to_string());
+    let message_or_line = || group("message").unwrap_or_else(|| message.to_string());
+    Some(match kind {
+        LineRuleKind::Chat => LineEvent::Chat {
+            player: group("player")?,
+            message: group("message")?,
+        },
+        LineRuleKind::PlayerJoined => LineEvent::PlayerJoined {
+            player: group("player")?,
+        },
+        LineRuleKind::PlayerLeft => LineEvent::PlayerLeft {
+            player: group("player")?,
+        },
+        LineRuleKind::PlayerUuid => LineEvent::PlayerUuid {
+            player: group("player")?,
+            uuid: group("uuid")?,
+        },
+        LineRuleKind::Death => LineEvent::Death {
+            player: group("player")?,
+            message: message_or_line(),
+        },
+        LineRuleKind::Advancement => LineEvent::Advancement {
+            player: group("player")?,
+            advancement: group("advancement")?,
+        },
+        LineRuleKind::ServerStarted => LineEvent::ServerStarted,
+        LineRuleKind::WorldSaved => LineEvent::WorldSaved,
+        LineRuleKind::LagWarning => LineEvent::LagWarning {
+            message: message_or_line(),
+        },
+        LineRuleKind::Exception => LineEvent::Exception {
+            message: message_or_line(),
+        },
+    })
+}
+
+/// Stack traces longer than this are cut short
+const MAX_PROBLEM_LINES: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemLevel {
+    Warning,
+    Error,
+}
+
+impl ProblemLevel {
+    /// Map a log4j level, `SEVERE` comes from java.util.logging used by older Bukkit plugins
+    pub fn from_log_level(level: &str) -> Option<Self> {
+        match level {
+            "WARN" | "WARNING" => Some(ProblemLevel::Warning),
+            "ERROR" | "FATAL" | "SEVERE" => Some(ProblemLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+/// A line that continues the stack trace of the line before it
+pub fn is_stack_trace_line(line: &str) -> bool {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r#"Done \(.+\)!"#).unwrap();
+        static ref RE: Regex = Regex::new(
+            r"^(?:\s+at \S|\s*\.\.\. \d+ (?:more|common frames omitted)|\s*Caused by: |\s*Suppressed: |(?:[a-z_$][\w$]*\.)+[A-Z][\w$]*(?:Exception|Error|Throwable)\b)"
+        )
+        .unwrap();
+    }
+    RE.is_match(line).unwrap_or(false)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub level: ProblemLevel,
+    /// The offending line followed by its stack trace, if any
+    pub message: String,
+}
+
+/// Groups WARN and ERROR lines with the stack trace lines that follow them.
+///
+/// A problem is only known to be complete once an unrelated line arrives,
+/// `flush` hands out the pending one when the console goes quiet instead
+#[derive(Debug, Default)]
+pub struct ProblemGrouper {
+    pending: Option<(ProblemLevel, Vec<String>, usize)>,
+}
+
+impl ProblemGrouper {
+    /// Feed a console line, `recognized_level` is the level of lines the rules recognized
+    /// as a problem, see [`LineEvent::problem_level`], so that those printed without a log level
+    /// are reported too. Returns the previous problem once it is complete
+    pub fn push(&mut self, line: &str, recognized_level: Option<ProblemLevel>) -> Option<Problem> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        let log_line = split_log_line(line);
+        if log_line.is_none() && is_stack_trace_line(line) {
+            if let Some((_, lines, omitted)) = self.pending.as_mut() {
+                if lines.len() < MAX_PROBLEM_LINES {
+                    lines.push(line.to_string());
+                } else {
+                    *omitted += 1;
+                }
+                return None;
+            }
+        }
+        let finished = self.flush();
+        let level = log_line
+            .and_then(|log_line| ProblemLevel::from_log_level(log_line.level))
+            .or(recognized_level);
+        if let Some(level) = level {
+            self.pending = Some((level, vec![line.to_string()], 0));
+        }
+        finished
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn flush(&mut self) -> Option<Problem> {
+        let (level, mut lines, omitted) = self.pending.take()?;
+        if omitted > 0 {
+            lines.push(format!("\t... {} more lines", omitted));
+        }
+        Some(Problem {
+            level,
+            message: lines.join("\n"),
+        })
+    }
+}
+
+impl MinecraftInstance {
+    pub(super) fn init_line_parser(config: &RestoreConfig) -> LineParser {
+        let flavour = FlavourKind::from(&config.flavour);
+        LineParser::new(&flavour, &config.line_rules).unwrap_or_else(|e| {
+            warn!(
+                "Ignoring the custom line rules of {}: {}",
+                config.name, e.source
+            );
+            LineParser::new(&flavour, &[]).expect("default line rules should compile")
+        })
+    }
+
+    pub async fn get_line_rules(&self) -> Vec<LineRule> {
+        self.config.lock().await.line_rules.clone()
+    }
+
+    /// Takes effect immediately, including for a running server
+    pub async fn set_line_rules(&self, line_rules: Vec<LineRule>) -> Result<(), Error> {
+        let flavour = FlavourKind::from(&self.config.lock().await.flavour);
+        *self.line_parser.lock().await = LineParser::new(&flavour, &line_rules)?;
+        self.config.lock().await.line_rules = line_rules;
+        self.write_config_to_file().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VANILLA_LOG: &str = r#"[12:00:00] [ServerMain/INFO]: Building unoptimized datafixer
+[12:00:03] [Server thread/INFO]: Starting minecraft server version 1.20.1
+[12:00:09] [Server thread/INFO]: Done (5.012s)! For help, type "help"
+[12:01:10] [User Authenticator #1/INFO]: UUID of player Notch is 069a79f4-44e9-4726-a5be-fca90e38aaf5
+[12:01:10] [Server thread/INFO]: Notch[/127.0.0.1:51234] logged in with entity id 123 at (8.5, 64.0, 8.5)
+[12:01:10] [Server thread/INFO]: Notch joined the game
+[12:01:15] [Server thread/INFO]: <Notch> Steve joined the game
+[12:01:20] [Server thread/INFO]: [Not Secure] <Notch> hello world
+[12:02:00] [Server thread/INFO]: Notch has made the advancement [Stone Age]
+[12:03:00] [Server thread/INFO]: Notch was slain by Zombie
+[12:03:30] [Server thread/INFO]: Notch fell from a high place
+[12:04:00] [Server thread/WARN]: Can't keep up! Is the server overloaded? Running 2012ms or 40 ticks behind
+[12:05:00] [Server thread/INFO]: Notch lost connection: Disconnected
+[12:05:00] [Server thread/INFO]: Notch left the game
+[12:06:00] [Server thread/INFO]: Saved the game
+[12:07:00] [Server thread/ERROR]: Encountered an unexpected exception
+java.lang.NullPointerException: Cannot invoke "Object.toString()" because "x" is null
+	at net.minecraft.server.MinecraftServer.tick(MinecraftServer.java:123)
+"#;
+
+    const PAPER_LOG: &str = r#"[12:00:00 INFO]: Starting minecraft server version 1.20.1
+[12:00:08 INFO]: Done (7.891s)! For help, type "help"
+[12:01:00 INFO]: UUID of player jeb_ is 853c80ef-3c37-49fd-aa49-938b674adae6
+[12:01:00 INFO]: jeb_ joined the game
+[12:01:05 INFO]: <jeb_> hi
+[12:01:30 ERROR]: Could not pass event PlayerJoinEvent to Essentials v2.20.0
+[12:02:00 INFO]: jeb_ left the game
+"#;
+
+    const FORGE_LOG: &str = r#"[12Jun2023 12:00:00.000] [main/INFO] [cpw.mods.modlauncher.Launcher/MODLAUNCHER]: ModLauncher running
+[12Jun2023 12:00:30.000] [Server thread/INFO] [net.minecraft.server.dedicated.DedicatedServer/]: Done (25.123s)! For help, type "help"
+[12Jun2023 12:01:00.000] [Server thread/INFO] [net.minecraft.server.MinecraftServer/]: Dev joined the game
+[12Jun2023 12:01:10.000] [Server thread/ERROR] [net.minecraftforge.eventbus.EventBus/EVENTBUS]: Exception caught during firing event: null
+"#;
+
+    fn parse_log(flavour: FlavourKind, log: &str) -> Vec<LineEvent> {
+        let parser = LineParser::new(&flavour, &[]).unwrap();
+        log.lines().filter_map(|line| parser.parse(line)).collect()
+    }
+
+    #[test]
+    fn test_split_log_line() {
+        assert_eq!(
+            split_log_line("[12:00:00] [Server thread/WARN]: Can't keep up!\n"),
+            Some(LogLine {
+                thread: Some("Server thread"),
+                level: "WARN",
+                message: "Can't keep up!",
+            })
+        );
+        assert_eq!(
+            split_log_line("[12:00:00 ERROR]: Could not load 'plugins/x.jar'"),
+            Some(LogLine {
+                thread: None,
+                level: "ERROR",
+                message: "Could not load 'plugins/x.jar'",
+            })
+        );
+        assert_eq!(
+            split_log_line("[12Jun2023 12:01:00.000] [Server thread/INFO] [net.minecraft.server.MinecraftServer/]: Dev joined the game")
+                .map(|l| l.message),
+            Some("Dev joined the game")
+        );
+        assert_eq!(split_log_line("\tat java.base/java.lang.Thread.run"), None);
+    }
+
+    #[test]
+    fn test_vanilla_log() {
+        let notch = || "Notch".to_string();
+        assert_eq!(
+            parse_log(FlavourKind::Vanilla, VANILLA_LOG),
+            vec![
+                LineEvent::ServerStarted,
+                LineEvent::PlayerUuid {
+                    player: notch(),
+                    uuid: "069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string()
+                },
+                LineEvent::PlayerJoined { player: notch() },
+                LineEvent::Chat {
+                    player: notch(),
+                    message: "Steve joined the game".to_string()
+                },
+                LineEvent::Chat {
+                    player: notch(),
+                    message: "hello world".to_string()
+                },
+                LineEvent::Advancement {
+                    player: notch(),
+                    advancement: "Stone Age".to_string()
+                },
+                LineEvent::Death {
+                    player: notch(),
+                    message: "Notch was slain by Zombie".to_string()
+                },
+                LineEvent::Death {
+                    player: notch(),
+                    message: "Notch fell from a high place".to_string()
+                },
+                LineEvent::LagWarning {
+                    message: "Can't keep up! Is the server overloaded? Running 2012ms or 40 ticks behind".to_string()
+                },
+                LineEvent::PlayerLeft { player: notch() },
+                LineEvent::WorldSaved,
+                LineEvent::Exception {
+                    message: "Encountered an unexpected exception".to_string()
+                },
+                LineEvent::Exception {
+                    message: "java.lang.NullPointerException: Cannot invoke \"Object.toString()\" because \"x\" is null".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flavour_logs() {
+        let jeb = || "jeb_".to_string();
+        assert_eq!(
+            parse_log(FlavourKind::Paper, PAPER_LOG),
+            vec![
+                LineEvent::ServerStarted,
+                LineEvent::PlayerUuid {
+                    player: jeb(),
+                    uuid: "853c80ef-3c37-49fd-aa49-938b674adae6".to_string()
+                },
+                LineEvent::PlayerJoined { player: jeb() },
+                LineEvent::Chat {
+                    player: jeb(),
+                    message: "hi".to_string()
+                },
+                LineEvent::Exception {
+                    message: "Could not pass event PlayerJoinEvent to Essentials v2.20.0"
+                        .to_string()
+                },
+                LineEvent::PlayerLeft { player: jeb() },
+            ]
+        );
+        // plugin errors are not recognized on vanilla
+        assert_eq!(parse_log(FlavourKind::Vanilla, PAPER_LOG).len(), 5);
+
+        assert_eq!(
+            parse_log(FlavourKind::Forge, FORGE_LOG),
+            vec![
+                LineEvent::ServerStarted,
+                LineEvent::PlayerJoined {
+                    player: "Dev".to_string()
+                },
+                LineEvent::Exception {
+                    message: "Exception caught during firing event: null".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let rules = [
+            LineRule::new(
+                LineRuleKind::Chat,
+                r"^\[(?P<world>\w+)\](?P<player>\w+): (?P<message>.*)$",
+            ),
+            LineRule::new(
+                LineRuleKind::PlayerJoined,
+                r"^(?P<player>\w+) a rejoint la partie$",
+            ),
+        ];
+        let parser = LineParser::new(&FlavourKind::Paper, &rules).unwrap();
+        assert_eq!(
+            parser.parse("[12:00:00 INFO]: [world]Notch: bonjour"),
+            Some(LineEvent::Chat {
+                player: "Notch".to_string(),
+                message: "bonjour".to_string()
+            })
+        );
+        assert_eq!(
+            parser.parse("[12:00:00 INFO]: Notch a rejoint la partie"),
+            Some(LineEvent::PlayerJoined {
+                player: "Notch".to_string()
+            })
+        );
+        // the defaults still apply
+        assert_eq!(
+            parser.parse("[12:00:00 INFO]: Notch left the game"),
+            Some(LineEvent::PlayerLeft {
+                player: "Notch".to_string()
+            })
+        );
+
+        // a custom rule can't swallow the lines the instance state depends on
+        let parser = LineParser::new(
+            &FlavourKind::Vanilla,
+            &[LineRule::new(LineRuleKind::Exception, r"^(?:Done|Saved)")],
+        )
+        .unwrap();
+        assert_eq!(
+            parser.parse("[12:00:09] [Server thread/INFO]: Done (5.012s)! For help, type \"help\""),
+            Some(LineEvent::ServerStarted)
+        );
+        assert_eq!(
+            parser.parse("[12:06:00] [Server thread/INFO]: Saved the game"),
+            Some(LineEvent::WorldSaved)
+        );
+
+        assert!(LineParser::new(
+            &FlavourKind::Vanilla,
+            &[LineRule::new(LineRuleKind::Chat, r"^(?P<player>\w+): .*$")]
+        )
+        .is_err());
+        assert!(LineParser::new(
+            &FlavourKind::Vanilla,
+            &[LineRule::new(LineRuleKind::ServerStarted, r"^Done (")]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_reports_world_saved() {
+        assert!(reports_world_saved(
+            "Saving the game (this may take a moment!)Saved the game"
+        ));
+        assert!(reports_world_saved("Saving...Saved the world"));
+        assert!(!reports_world_saved(
+            "Saving the game (this may take a moment!)"
+        ));
+    }
+
+    #[test]
+    fn test_problem_grouper() {
+        let mut grouper = ProblemGrouper::default();
+        let problems: Vec<Problem> = VANILLA_LOG
+            .lines()
+            .filter_map(|line| grouper.push(line, None))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![Problem {
+                level: ProblemLevel::Warning,
+                message: "[12:04:00] [Server thread/WARN]: Can't keep up! Is the server overloaded? Running 2012ms or 40 ticks behind".to_string()
+            }]
+        );
+        assert!(grouper.has_pending());
+        let trace = grouper.flush().unwrap();
+        assert_eq!(trace.level, ProblemLevel::Error);
+        assert_eq!(trace.message.lines().count(), 3);
+        assert!(trace
+            .message
+            .starts_with("[12:07:00] [Server thread/ERROR]: Encountered"));
+        assert!(!grouper.has_pending());
+
+        // a trace printed straight to stderr has no log level
+        assert_eq!(
+            grouper.push(
+                "Exception in thread \"main\" java.lang.IllegalStateException",
+                Some(ProblemLevel::Error)
+            ),
+            None
+        );
+        for _ in 0..MAX_PROBLEM_LINES + 5 {
+            assert_eq!(grouper.push("\tat Main.main(Main.java:1)", None), None);
+        }
+        let trace = grouper
+            .push("[12:00:00] [Server thread/INFO]: Stopping server", None)
+            .unwrap();
+        assert_eq!(trace.level, ProblemLevel::Error);
+        assert_eq!(trace.message.lines().count(), MAX_PROBLEM_LINES + 1);
+        assert!(trace.message.ends_with("... 6 more lines"));
+        assert!(!grouper.has_pending());
+        assert_eq!(grouper.flush(), None);
+
+        // lag warnings recognized by the rules are reported even without a log level
+        grouper.push(
+            "Can't keep up! Is the server overloaded? Running 2012ms or 40 ticks behind",
+            Some(ProblemLevel::Warning),
+        );
+        assert_eq!(grouper.flush().unwrap().level, ProblemLevel::Warning);
     }
-    RE.is_match(system_msg).unwrap()
 }
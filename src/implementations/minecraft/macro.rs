@@ -2,11 +2,13 @@ use std::{
     cell::RefCell,
     path::{Path, PathBuf},
     rc::Rc,
+    time::Duration,
 };
 
 use async_trait::async_trait;
 use color_eyre::eyre::{eyre, Context};
 use deno_core::{anyhow, op, OpState};
+use tracing::{error, info};
 
 use crate::{
     error::Error,
@@ -175,6 +177,85 @@ impl WorkerOptionGenerator for MinecraftMainWorkerGenerator {
     }
 }
 
+impl MinecraftInstance {
+    /// Run the `prelaunch`, `poststart`, `prestop`, `poststop` or `oncrash` script in the
+    /// instance directory, if there is one.
+    ///
+    /// Waits up to 5 seconds for the script to finish and kills it afterwards,
+    /// unless it contains `LODESTONE_LONG_RUNNING_MACRO`
+    pub(super) async fn run_lifecycle_macro(&self, hook: &str) {
+        let name = self.config.lock().await.name.clone();
+        let path_to_macro = match resolve_macro_invocation(&self.path_to_instance, hook) {
+            Some(path_to_macro) => path_to_macro,
+            None => {
+                info!("[{}] No {} script found, skipping", name, hook);
+                return;
+            }
+        };
+        let content = std::fs::read_to_string(&path_to_macro)
+            .map_err(|e| {
+                error!("Failed to read {} script: {}", hook, e);
+                e
+            })
+            .unwrap_or_default();
+
+        let is_long_running = content.contains("LODESTONE_LONG_RUNNING_MACRO");
+
+        let main_worker_generator = MinecraftMainWorkerGenerator::new(self.clone());
+        let res = self
+            .macro_executor
+            .spawn(
+                path_to_macro,
+                Vec::new(),
+                CausedBy::System,
+                Box::new(main_worker_generator),
+                None,
+                Some(self.uuid.clone()),
+                if is_long_running {
+                    None
+                } else {
+                    Some(Duration::from_secs(5))
+                },
+            )
+            .await;
+
+        match res {
+            Ok(SpawnResult {
+                macro_pid: pid,
+                exit_future,
+                ..
+            }) => {
+                self.pid_to_task_entry.lock().await.insert(
+                    pid,
+                    TaskEntry {
+                        pid,
+                        name: hook.to_string(),
+                        creation_time: chrono::Utc::now().timestamp(),
+                    },
+                );
+                if !is_long_running {
+                    info!(
+                        "[{}] Waiting for {} script to finish (5 seconds timeout)",
+                        name, hook
+                    );
+                    if exit_future.await.is_err() {
+                        info!("[{}] {} script timed out, killing it", name, hook);
+                        let _ = self.macro_executor.abort_macro(pid);
+                    }
+                } else {
+                    info!(
+                        "[{}] Long running {} script detected, skipping wait",
+                        name, hook
+                    );
+                }
+            }
+            Err(e) => {
+                error!("[{}] Failed to run {} script: {}", name, hook, e);
+            }
+        }
+    }
+}
+
 #[async_trait]
 impl TMacro for MinecraftInstance {
     async fn get_macro_list(&self) -> Result<Vec<MacroEntry>, Error> {
@@ -1,22 +1,34 @@
+pub mod access_list;
+pub mod backup;
+pub mod command;
 pub mod configurable;
+pub mod countdown;
+pub mod crash_report;
 pub mod fabric;
 mod forge;
-mod line_parser;
+pub mod line_parser;
 pub mod r#macro;
+pub mod mod_analysis;
+pub mod nbt;
 mod paper;
 pub mod player;
 mod players_manager;
+pub mod query;
 pub mod resource;
 pub mod server;
+pub mod server_list_ping;
+pub mod tick_monitor;
+pub mod user_cache;
 pub mod util;
 mod vanilla;
 pub mod versions;
+pub mod world;
 
 use color_eyre::eyre::{eyre, Context, ContextCompat};
 use enum_kinds::EnumKind;
 use indexmap::IndexMap;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::process::Stdio;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
@@ -29,7 +41,7 @@ use tokio::sync::Mutex;
 use ::serde::{Deserialize, Serialize};
 use serde_json::to_string_pretty;
 
-use tracing::error;
+use tracing::{error, warn};
 
 use tokio;
 use ts_rs::TS;
@@ -55,11 +67,20 @@ use crate::util::{
     UnzipOption,
 };
 
+use self::backup::default_max_backups;
 use self::configurable::{CmdArgSetting, ServerPropertySetting};
+use self::countdown::{CountdownAction, CountdownConfig};
 use self::fabric::get_fabric_minecraft_versions;
 use self::forge::get_forge_minecraft_versions;
+use self::line_parser::{LineParser, LineRule};
+use self::mod_analysis::default_check_mods_on_start;
 use self::paper::get_paper_minecraft_versions;
 use self::players_manager::PlayersManager;
+use self::query::QueryStat;
+use self::server::{default_stop_timeout, StopEscalation};
+use self::server_list_ping::ServerStatus;
+use self::tick_monitor::TickMonitor;
+use self::user_cache::{UserCache, USER_CACHE_FILE_NAME};
 use self::util::{get_jre_url, get_server_jar_url, read_properties_from_path};
 use self::vanilla::get_vanilla_minecraft_versions;
 
@@ -165,7 +186,21 @@ pub struct RestoreConfig {
     pub max_ram: u32,
     pub auto_start: bool,
     pub restart_on_crash: bool,
+    /// in minutes, `None` disables scheduled backups
     pub backup_period: Option<u32>,
+    #[serde(default = "default_max_backups")]
+    pub max_backups: u32,
+    /// seconds to wait for the server to exit after the stop command before escalating
+    #[serde(default = "default_stop_timeout")]
+    pub stop_timeout: u32,
+    #[serde(default)]
+    pub countdown: CountdownConfig,
+    /// warn about missing dependencies and conflicting mods before starting
+    #[serde(default = "default_check_mods_on_start")]
+    pub check_mods_on_start: bool,
+    /// console line rules tried before the flavour's defaults
+    #[serde(default)]
+    pub line_rules: Vec<LineRule>,
     pub jre_major_version: u64,
     pub has_started: bool,
 }
@@ -190,16 +225,34 @@ pub struct MinecraftInstance {
     // variables which can be changed at runtime
     auto_start: Arc<AtomicBool>,
     restart_on_crash: Arc<AtomicBool>,
-    backup_period: Option<u32>,
     process: Arc<Mutex<Option<Child>>>,
     stdin: Arc<Mutex<Option<tokio::process::ChildStdin>>>,
     system: Arc<Mutex<sysinfo::System>>,
     players_manager: Arc<Mutex<PlayersManager>>,
+    line_parser: Arc<Mutex<LineParser>>,
+    user_cache: Arc<Mutex<UserCache>>,
+    server_status: Arc<Mutex<Option<ServerStatus>>>,
+    query_stat: Arc<Mutex<Option<QueryStat>>>,
+    tick_monitor: Arc<Mutex<TickMonitor>>,
+    // tasks polling the running server, aborted when the process exits
+    status_tasks: Arc<Mutex<Vec<tokio::task::JoinHandle<()>>>>,
+    // expiries of temporary bans issued through the console, keyed by lowercase player name
+    pending_ban_expiries: Arc<Mutex<HashMap<String, chrono::DateTime<chrono::Local>>>>,
     configurable_manifest: Arc<Mutex<ConfigurableManifest>>,
     macro_executor: MacroExecutor,
     rcon_conn: Arc<Mutex<Option<rcon::Connection<tokio::net::TcpStream>>>>,
     macro_name_to_last_run: Arc<Mutex<HashMap<String, i64>>>,
     pid_to_task_entry: Arc<Mutex<IndexMap<MacroPID, TaskEntry>>>,
+    backup_lock: Arc<Mutex<()>>,
+    // set while a backup is being restored, the instance can't be started meanwhile
+    restoring_backup: Arc<AtomicBool>,
+    // notified by the stdout parser when the server reports the world was saved
+    world_saved: tokio::sync::broadcast::Sender<()>,
+    recent_crashes: Arc<Mutex<VecDeque<std::time::Instant>>>,
+    // used to tell crash reports of the current run apart from older ones
+    process_started_at: Arc<Mutex<Option<std::time::SystemTime>>>,
+    stop_escalation: Arc<Mutex<StopEscalation>>,
+    countdown: Arc<Mutex<Option<(CountdownAction, tokio::task::JoinHandle<()>)>>>,
 }
 
 #[tokio::test]
@@ -654,6 +707,11 @@ impl MinecraftInstance {
             auto_start: config.auto_start.unwrap_or(false),
             restart_on_crash: config.restart_on_crash.unwrap_or(false),
             backup_period: config.backup_period,
+            max_backups: default_max_backups(),
+            stop_timeout: default_stop_timeout(),
+            countdown: CountdownConfig::default(),
+            check_mods_on_start: default_check_mods_on_start(),
+            line_rules: Vec::new(),
             jre_major_version,
             has_started: false,
             java_cmd: Some(jre.to_string_lossy().to_string()),
@@ -722,17 +780,25 @@ impl MinecraftInstance {
             java_path.to_string_lossy().to_string(),
         )));
 
+        let line_parser = Self::init_line_parser(&restore_config);
+
         let mut instance = MinecraftInstance {
             state: Arc::new(Mutex::new(State::Stopped)),
             uuid: dot_lodestone_config.uuid().clone(),
             creation_time: dot_lodestone_config.creation_time(),
             auto_start: Arc::new(AtomicBool::new(restore_config.auto_start)),
             restart_on_crash: Arc::new(AtomicBool::new(restore_config.restart_on_crash)),
-            backup_period: restore_config.backup_period,
             players_manager: Arc::new(Mutex::new(PlayersManager::new(
                 event_broadcaster.clone(),
                 dot_lodestone_config.uuid().clone(),
             ))),
+            line_parser: Arc::new(Mutex::new(line_parser)),
+            user_cache: Arc::new(Mutex::new(UserCache::default())),
+            server_status: Arc::new(Mutex::new(None)),
+            query_stat: Arc::new(Mutex::new(None)),
+            tick_monitor: Arc::new(Mutex::new(TickMonitor::default())),
+            status_tasks: Arc::new(Mutex::new(Vec::new())),
+            pending_ban_expiries: Arc::new(Mutex::new(HashMap::new())),
             config: Arc::new(Mutex::new(restore_config)),
             path_to_instance,
             path_to_config,
@@ -749,11 +815,20 @@ impl MinecraftInstance {
             configurable_manifest,
             macro_name_to_last_run: Arc::new(Mutex::new(HashMap::new())),
             pid_to_task_entry: Arc::new(Mutex::new(IndexMap::new())),
+            backup_lock: Arc::new(Mutex::new(())),
+            restoring_backup: Arc::new(AtomicBool::new(false)),
+            world_saved: tokio::sync::broadcast::channel(16).0,
+            recent_crashes: Arc::new(Mutex::new(VecDeque::new())),
+            process_started_at: Arc::new(Mutex::new(None)),
+            stop_escalation: Arc::new(Mutex::new(StopEscalation::Command)),
+            countdown: Arc::new(Mutex::new(None)),
         };
         instance
             .read_properties()
             .await
             .context("Failed to read properties")?;
+        instance.load_user_cache().await;
+        instance.spawn_backup_task();
         Ok(instance)
     }
 
@@ -771,6 +846,14 @@ impl MinecraftInstance {
         Ok(())
     }
 
+    /// Seed the name to UUID cache from the server's own `usercache.json`
+    async fn load_user_cache(&self) {
+        let path = self.path_to_instance.join(USER_CACHE_FILE_NAME);
+        if let Err(e) = self.user_cache.lock().await.load_usercache(&path).await {
+            warn!("Failed to load {}: {}", path.display(), e);
+        }
+    }
+
     async fn read_properties(&mut self) -> Result<(), Error> {
         let properties = read_properties_from_path(&self.path_to_properties).await?;
         let mut lock = self.configurable_manifest.lock().await;
@@ -796,6 +879,17 @@ impl MinecraftInstance {
         Ok(())
     }
 
+    /// Set a single property, even if it is missing from `server.properties`
+    async fn set_server_property(&mut self, setting: ServerPropertySetting) -> Result<(), Error> {
+        // the properties file may not exist yet if the server has never been started
+        let _ = self.read_properties().await;
+        self.configurable_manifest
+            .lock()
+            .await
+            .set_setting(ServerPropertySetting::get_section_id(), setting.into())?;
+        self.write_properties_to_file().await
+    }
+
     async fn write_properties_to_file(&self) -> Result<(), Error> {
         // open the file in write-only mode, returns `io::Result<File>`
         let mut file = tokio::fs::File::create(&self.path_to_properties)
@@ -892,20 +986,25 @@ impl MinecraftInstance {
         );
     }
 
+    /// Reconnects once when the connection dropped or was never established
     pub async fn send_rcon(&self, cmd: &str) -> Result<String, Error> {
-        let a = self
-            .rcon_conn
-            .clone()
-            .lock()
+        // only connecting is retried, a command that went out may already have run
+        self.ensure_rcon_connected()
             .await
+            .context("Failed to send rcon command, rcon connection is not available")?;
+        let mut rcon_conn = self.rcon_conn.lock().await;
+        let result = rcon_conn
             .as_mut()
             .ok_or_else(|| {
                 eyre!("Failed to send rcon command, rcon connection is not initialized")
             })?
             .cmd(cmd)
-            .await
-            .context("Failed to send rcon command")?;
-        Ok(a)
+            .await;
+        if result.is_err() {
+            // the connection is in an unknown state, the next command reconnects
+            rcon_conn.take();
+        }
+        Ok(result.context("Failed to send rcon command")?)
     }
 }
 
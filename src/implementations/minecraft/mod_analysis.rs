@@ -0,0 +1,604 @@
+use std::{cmp::Ordering, collections::HashMap, fmt::Display};
+
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    error::{Error, ErrorKind},
+    events::{CausedBy, InstanceEventInner},
+};
+
+use super::{
+    resource::{DependencyKind, MinecraftResource, MinecraftResourceKind, ModLoader},
+    util::{compare_versions, version_components},
+    FabricLoaderVersion, Flavour, ForgeBuildVersion, MinecraftInstance,
+};
+
+/// The check is opt-in, existing instances keep starting as before when they are migrated
+pub fn default_check_mods_on_start() -> bool {
+    false
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[serde(tag = "type")]
+#[ts(export)]
+pub enum ModIssue {
+    DuplicateMod {
+        id: String,
+        file_names: Vec<String>,
+    },
+    MissingDependency {
+        file_name: String,
+        mod_id: String,
+        dependency_id: String,
+        version_range: Option<String>,
+    },
+    /// Also covers mods made for another Minecraft or loader version
+    DependencyVersionMismatch {
+        file_name: String,
+        mod_id: String,
+        dependency_id: String,
+        version_range: String,
+        found_version: String,
+    },
+    Incompatible {
+        file_name: String,
+        mod_id: String,
+        incompatible_id: String,
+        incompatible_file_name: String,
+    },
+    WrongLoader {
+        file_name: String,
+        mod_id: String,
+        loader: ModLoader,
+    },
+}
+
+impl Display for ModIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModIssue::DuplicateMod { id, file_names } => write!(
+                f,
+                "Mod {} is installed more than once ({})",
+                id,
+                file_names.join(", ")
+            ),
+            ModIssue::MissingDependency {
+                file_name,
+                mod_id,
+                dependency_id,
+                version_range,
+            } => write!(
+                f,
+                "{} ({}) requires {}{}, which is not installed",
+                mod_id,
+                file_name,
+                dependency_id,
+                version_range
+                    .as_ref()
+                    .map(|range| format!(" {range}"))
+                    .unwrap_or_default()
+            ),
+            ModIssue::DependencyVersionMismatch {
+                file_name,
+                mod_id,
+                dependency_id,
+                version_range,
+                found_version,
+            } => write!(
+                f,
+                "{mod_id} ({file_name}) requires {dependency_id} {version_range}, but {found_version} is installed"
+            ),
+            ModIssue::Incompatible {
+                file_name,
+                mod_id,
+                incompatible_id,
+                incompatible_file_name,
+            } => write!(
+                f,
+                "{mod_id} ({file_name}) is incompatible with {incompatible_id} ({incompatible_file_name})"
+            ),
+            ModIssue::WrongLoader {
+                file_name,
+                mod_id,
+                loader,
+            } => write!(
+                f,
+                "{mod_id} ({file_name}) is a {loader:?} mod and will not be loaded by this server"
+            ),
+        }
+    }
+}
+
+/// What the mods of an instance run on
+pub struct ModEnvironment {
+    pub loader: ModLoader,
+    pub minecraft_version: String,
+    pub loader_version: Option<String>,
+    pub java_version: u64,
+}
+
+impl ModEnvironment {
+    /// Ids that are satisfied without installing a mod, and their versions
+    fn builtin_mods(&self) -> Vec<(&'static str, Option<String>)> {
+        let loader_id = match self.loader {
+            ModLoader::Fabric => "fabricloader",
+            ModLoader::Forge => "forge",
+            ModLoader::Bukkit => "bukkit",
+        };
+        vec![
+            ("minecraft", Some(self.minecraft_version.clone())),
+            ("java", Some(self.java_version.to_string())),
+            (loader_id, self.loader_version.clone()),
+        ]
+    }
+}
+
+/// Matches a single Fabric version predicate such as `>=1.19`, `~0.14.2`, `1.19.x` or `*`.
+///
+/// Returns `None` if the predicate can't be understood
+fn fabric_predicate_matches(predicate: &str, version: &[u64]) -> Option<bool> {
+    if predicate == "*" {
+        return Some(true);
+    }
+    let (op, target) = [">=", "<=", ">", "<", "=", "~", "^"]
+        .iter()
+        .find_map(|op| predicate.strip_prefix(op).map(|target| (*op, target)))
+        .unwrap_or(("=", predicate));
+    let wildcard = target
+        .split('.')
+        .any(|part| matches!(part, "x" | "X" | "*"));
+    let target = version_components(target);
+    if target.is_empty() {
+        return None;
+    }
+    // the smallest version above the range of `~` and `^`
+    let bump = |index: usize| {
+        let mut bumped = target[..=index.min(target.len() - 1)].to_vec();
+        if let Some(last) = bumped.last_mut() {
+            *last += 1;
+        }
+        bumped
+    };
+    let ordering = compare_versions(version, &target);
+    Some(match op {
+        ">=" => ordering.is_ge(),
+        "<=" => ordering.is_le(),
+        ">" => ordering.is_gt(),
+        "<" => ordering.is_lt(),
+        "~" => ordering.is_ge() && compare_versions(version, &bump(1)).is_lt(),
+        "^" => ordering.is_ge() && compare_versions(version, &bump(0)).is_lt(),
+        _ if wildcard => (0..target.len()).all(|i| version.get(i).unwrap_or(&0) == &target[i]),
+        _ => ordering.is_eq(),
+    })
+}
+
+/// Matches a Fabric version range, where predicates separated by spaces must all match
+/// and alternatives are separated by `||`
+pub fn fabric_version_matches(range: &str, version: &str) -> Option<bool> {
+    let version = version_components(version);
+    if version.is_empty() {
+        return None;
+    }
+    let mut ret = Some(false);
+    for alternative in range.split("||") {
+        let mut matches = Some(true);
+        for predicate in alternative.split_whitespace() {
+            match fabric_predicate_matches(predicate, &version) {
+                Some(true) => {}
+                Some(false) => {
+                    matches = Some(false);
+                    break;
+                }
+                None => matches = None,
+            }
+        }
+        match matches {
+            Some(true) => return Some(true),
+            Some(false) => {}
+            None => ret = None,
+        }
+    }
+    ret
+}
+
+/// Matches a Maven version range as used by Forge, e.g. `[1.19,1.20)` or `[40,)`.
+///
+/// A bare version is only a recommendation in Maven, so it matches anything
+pub fn maven_version_matches(range: &str, version: &str) -> Option<bool> {
+    let range = range.trim();
+    if !range.starts_with(['[', '(']) {
+        return Some(true);
+    }
+    let version = version_components(version);
+    if version.is_empty() {
+        return None;
+    }
+    let bound_matches = |bound: &str, accept: fn(Ordering) -> bool| {
+        if bound.trim().is_empty() {
+            return Some(true);
+        }
+        let bound = version_components(bound);
+        if bound.is_empty() {
+            return None;
+        }
+        Some(accept(compare_versions(&version, &bound)))
+    };
+    let mut rest = range;
+    while let Some(start) = rest.find(['[', '(']) {
+        let end = start + rest[start..].find([']', ')'])?;
+        let inclusive_lower = &rest[start..start + 1] == "[";
+        let inclusive_upper = &rest[end..end + 1] == "]";
+        let matches = match rest[start + 1..end].split_once(',') {
+            Some((lower, upper)) => {
+                bound_matches(
+                    lower,
+                    if inclusive_lower {
+                        Ordering::is_ge
+                    } else {
+                        Ordering::is_gt
+                    },
+                )? && bound_matches(
+                    upper,
+                    if inclusive_upper {
+                        Ordering::is_le
+                    } else {
+                        Ordering::is_lt
+                    },
+                )?
+            }
+            None => {
+                let exact = version_components(&rest[start + 1..end]);
+                if exact.is_empty() {
+                    return None;
+                }
+                compare_versions(&version, &exact).is_eq()
+            }
+        };
+        if matches {
+            return Some(true);
+        }
+        rest = &rest[end + 1..];
+    }
+    Some(false)
+}
+
+/// Checks the enabled mods of an instance against each other and against the environment
+pub fn analyze_mods(
+    resources: &[MinecraftResource],
+    environment: &ModEnvironment,
+) -> Vec<ModIssue> {
+    let mut issues = Vec::new();
+    let mods: Vec<_> = resources
+        .iter()
+        .filter(|resource| resource.enabled && resource.kind == MinecraftResourceKind::Mod)
+        .flat_map(|resource| {
+            resource
+                .metadata
+                .iter()
+                .map(move |metadata| (resource.file_name.as_str(), metadata))
+        })
+        .collect();
+
+    let mut file_names_by_id: HashMap<&str, Vec<&str>> = HashMap::new();
+    for &(file_name, metadata) in mods.iter() {
+        let file_names = file_names_by_id.entry(metadata.id.as_str()).or_default();
+        if !file_names.contains(&file_name) {
+            file_names.push(file_name);
+        }
+    }
+    let mut duplicates: Vec<_> = file_names_by_id
+        .iter()
+        .filter(|(_, file_names)| file_names.len() > 1)
+        .collect();
+    duplicates.sort();
+    for (id, file_names) in duplicates {
+        issues.push(ModIssue::DuplicateMod {
+            id: id.to_string(),
+            file_names: file_names.iter().map(|name| name.to_string()).collect(),
+        });
+    }
+
+    // what each id resolves to: the file providing it and its version, if known
+    let mut installed: HashMap<&str, (Option<&str>, Option<String>)> = HashMap::new();
+    let builtin_mods = environment.builtin_mods();
+    for (id, version) in builtin_mods.iter() {
+        installed.insert(*id, (None, version.clone()));
+    }
+    for &(file_name, metadata) in mods.iter() {
+        for provided in metadata.provides.iter() {
+            installed
+                .entry(provided.as_str())
+                .or_insert((Some(file_name), None));
+        }
+    }
+    for &(file_name, metadata) in mods.iter() {
+        installed.insert(
+            metadata.id.as_str(),
+            (Some(file_name), metadata.version.clone()),
+        );
+    }
+
+    for &(file_name, metadata) in mods.iter() {
+        if metadata.loader != environment.loader {
+            issues.push(ModIssue::WrongLoader {
+                file_name: file_name.to_string(),
+                mod_id: metadata.id.clone(),
+                loader: metadata.loader,
+            });
+            continue;
+        }
+        let version_matches = |range: &str, version: &str| match metadata.loader {
+            ModLoader::Fabric => fabric_version_matches(range, version),
+            _ => maven_version_matches(range, version),
+        };
+        for dependency in metadata.dependencies.iter() {
+            let found = installed.get(dependency.id.as_str());
+            // a mod version of `${file.jarVersion}` that couldn't be resolved is not a version
+            let found_version = found
+                .and_then(|(_, version)| version.as_deref())
+                .filter(|version| !version.starts_with("${"));
+            let range_matches = match (&dependency.version_range, found_version) {
+                (Some(range), Some(version)) => version_matches(range, version),
+                _ => None,
+            };
+            match (dependency.kind, found) {
+                (DependencyKind::Required, None) => issues.push(ModIssue::MissingDependency {
+                    file_name: file_name.to_string(),
+                    mod_id: metadata.id.clone(),
+                    dependency_id: dependency.id.clone(),
+                    version_range: dependency.version_range.clone(),
+                }),
+                (DependencyKind::Required, Some(_)) | (DependencyKind::Optional, Some(_))
+                    if range_matches == Some(false) =>
+                {
+                    issues.push(ModIssue::DependencyVersionMismatch {
+                        file_name: file_name.to_string(),
+                        mod_id: metadata.id.clone(),
+                        dependency_id: dependency.id.clone(),
+                        version_range: dependency.version_range.clone().unwrap_or_default(),
+                        found_version: found_version.unwrap_or_default().to_string(),
+                    })
+                }
+                (DependencyKind::Incompatible, Some((Some(incompatible_file_name), _)))
+                    if range_matches != Some(false) && *incompatible_file_name != file_name =>
+                {
+                    issues.push(ModIssue::Incompatible {
+                        file_name: file_name.to_string(),
+                        mod_id: metadata.id.clone(),
+                        incompatible_id: dependency.id.clone(),
+                        incompatible_file_name: incompatible_file_name.to_string(),
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+    issues
+}
+
+impl MinecraftInstance {
+    /// Only Fabric and Forge instances have mods to analyze
+    async fn mod_environment(&self) -> Option<ModEnvironment> {
+        let config = self.config.lock().await;
+        let (loader, loader_version) = match &config.flavour {
+            Flavour::Fabric { loader_version, .. } => (
+                ModLoader::Fabric,
+                loader_version
+                    .as_ref()
+                    .map(|FabricLoaderVersion(version)| version.clone()),
+            ),
+            Flavour::Forge { build_version } => (
+                ModLoader::Forge,
+                // build versions look like `1.19.2-43.2.0`
+                build_version.as_ref().map(|ForgeBuildVersion(version)| {
+                    version
+                        .split_once('-')
+                        .map(|(_, forge_version)| forge_version)
+                        .unwrap_or(version)
+                        .to_string()
+                }),
+            ),
+            _ => return None,
+        };
+        Some(ModEnvironment {
+            loader,
+            minecraft_version: config.version.clone(),
+            loader_version,
+            java_version: config.jre_major_version,
+        })
+    }
+
+    pub async fn analyze_mods(&self) -> Result<Vec<ModIssue>, Error> {
+        let environment = self.mod_environment().await.ok_or_else(|| Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("Mod analysis is only supported for Fabric and Forge instances"),
+        })?;
+        Ok(analyze_mods(&self.list_resources().await, &environment))
+    }
+
+    pub async fn set_check_mods_on_start(&self, check_mods_on_start: bool) -> Result<(), Error> {
+        self.config.lock().await.check_mods_on_start = check_mods_on_start;
+        self.write_config_to_file().await
+    }
+
+    /// Emits an `InstanceWarning` for every problem found with the installed mods
+    pub(super) async fn warn_about_mod_issues(&self) {
+        if let Ok(issues) = self.analyze_mods().await {
+            for issue in issues {
+                self.send_instance_event(
+                    InstanceEventInner::InstanceWarning {
+                        message: issue.to_string(),
+                    },
+                    CausedBy::System,
+                )
+                .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::implementations::minecraft::resource::{ModDependency, ModMetadata};
+
+    fn resource(file_name: &str, enabled: bool, metadata: Vec<ModMetadata>) -> MinecraftResource {
+        MinecraftResource {
+            file_name: file_name.to_string(),
+            kind: MinecraftResourceKind::Mod,
+            enabled,
+            size: 0,
+            metadata,
+            error: None,
+        }
+    }
+
+    fn fabric_mod(id: &str, version: &str, dependencies: Vec<ModDependency>) -> ModMetadata {
+        ModMetadata {
+            loader: ModLoader::Fabric,
+            id: id.to_string(),
+            name: id.to_string(),
+            version: Some(version.to_string()),
+            authors: Vec::new(),
+            dependencies,
+            provides: Vec::new(),
+        }
+    }
+
+    fn dependency(id: &str, version_range: &str, kind: DependencyKind) -> ModDependency {
+        ModDependency {
+            id: id.to_string(),
+            version_range: Some(version_range.to_string()),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_fabric_version_matches() {
+        assert_eq!(fabric_version_matches("*", "1.19.2"), Some(true));
+        assert_eq!(fabric_version_matches(">=1.19", "1.19.2"), Some(true));
+        assert_eq!(fabric_version_matches(">=1.19.3-", "1.19.2"), Some(false));
+        assert_eq!(fabric_version_matches("1.19.x", "1.19.2"), Some(true));
+        assert_eq!(fabric_version_matches("1.19.x", "1.20"), Some(false));
+        assert_eq!(fabric_version_matches("~1.19.2", "1.19.4"), Some(true));
+        assert_eq!(fabric_version_matches("~1.19.2", "1.20"), Some(false));
+        assert_eq!(fabric_version_matches("^0.14.0", "0.14.19"), Some(true));
+        assert_eq!(fabric_version_matches("^0.14.0", "1.0.0"), Some(false));
+        assert_eq!(fabric_version_matches(">=1.18 <1.19", "1.18.2"), Some(true));
+        assert_eq!(
+            fabric_version_matches("1.19.3 || 1.19.4", "1.19.4"),
+            Some(true)
+        );
+        assert_eq!(
+            fabric_version_matches(">=0.76.0", "0.75.1+1.19.2"),
+            Some(false)
+        );
+        assert_eq!(fabric_version_matches("1.19.2", "23w13a"), None);
+    }
+
+    #[test]
+    fn test_maven_version_matches() {
+        assert_eq!(maven_version_matches("[40,)", "43.2.0"), Some(true));
+        assert_eq!(maven_version_matches("[1.19,1.20)", "1.19.2"), Some(true));
+        assert_eq!(maven_version_matches("[1.19,1.20)", "1.20"), Some(false));
+        assert_eq!(maven_version_matches("(,1.18.2]", "1.18.2"), Some(true));
+        assert_eq!(maven_version_matches("[1.18.2]", "1.19"), Some(false));
+        assert_eq!(
+            maven_version_matches("[1.16,1.17),[1.18,)", "1.19.2"),
+            Some(true)
+        );
+        assert_eq!(maven_version_matches("1.19.2", "1.12.2"), Some(true));
+    }
+
+    #[test]
+    fn test_analyze_mods() {
+        let environment = ModEnvironment {
+            loader: ModLoader::Fabric,
+            minecraft_version: "1.19.2".to_string(),
+            loader_version: Some("0.14.19".to_string()),
+            java_version: 17,
+        };
+        let resources = vec![
+            resource(
+                "sodium.jar",
+                true,
+                vec![fabric_mod(
+                    "sodium",
+                    "0.4.4",
+                    vec![
+                        dependency("minecraft", "1.19.2", DependencyKind::Required),
+                        dependency("fabricloader", ">=0.12.0", DependencyKind::Required),
+                        dependency("fabric-api", "*", DependencyKind::Required),
+                    ],
+                )],
+            ),
+            resource(
+                "iris.jar",
+                true,
+                vec![fabric_mod(
+                    "iris",
+                    "1.6.0",
+                    vec![dependency("minecraft", "1.19.4", DependencyKind::Required)],
+                )],
+            ),
+            resource(
+                "optifabric.jar",
+                true,
+                vec![fabric_mod(
+                    "optifabric",
+                    "1.13.0",
+                    vec![dependency("sodium", "*", DependencyKind::Incompatible)],
+                )],
+            ),
+            resource(
+                "sodium-old.jar",
+                true,
+                vec![fabric_mod("sodium", "0.4.2", Vec::new())],
+            ),
+            resource(
+                "disabled.jar",
+                false,
+                vec![fabric_mod(
+                    "disabled",
+                    "1.0.0",
+                    vec![dependency("missing", "*", DependencyKind::Required)],
+                )],
+            ),
+        ];
+        let issues = analyze_mods(&resources, &environment);
+        assert_eq!(issues.len(), 4);
+        assert!(issues.contains(&ModIssue::DuplicateMod {
+            id: "sodium".to_string(),
+            file_names: vec!["sodium.jar".to_string(), "sodium-old.jar".to_string()],
+        }));
+        assert!(issues.contains(&ModIssue::MissingDependency {
+            file_name: "sodium.jar".to_string(),
+            mod_id: "sodium".to_string(),
+            dependency_id: "fabric-api".to_string(),
+            version_range: Some("*".to_string()),
+        }));
+        assert!(issues.contains(&ModIssue::DependencyVersionMismatch {
+            file_name: "iris.jar".to_string(),
+            mod_id: "iris".to_string(),
+            dependency_id: "minecraft".to_string(),
+            version_range: "1.19.4".to_string(),
+            found_version: "1.19.2".to_string(),
+        }));
+        assert!(issues.iter().any(
+            |issue| matches!(issue, ModIssue::Incompatible { mod_id, .. } if mod_id == "optifabric")
+        ));
+
+        let mut forge_mod = fabric_mod("jei", "11.6.0", Vec::new());
+        forge_mod.loader = ModLoader::Forge;
+        assert_eq!(
+            analyze_mods(&[resource("jei.jar", true, vec![forge_mod])], &environment),
+            vec![ModIssue::WrongLoader {
+                file_name: "jei.jar".to_string(),
+                mod_id: "jei".to_string(),
+                loader: ModLoader::Forge,
+            }]
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! A small reader for the Named Binary Tag format Minecraft uses for `level.dat`
+use std::{
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use color_eyre::eyre::{eyre, Context};
+use flate2::read::GzDecoder;
+use indexmap::IndexMap;
+
+use crate::error::Error;
+
+/// Deeper nesting than this is treated as a corrupted file
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    End,
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(IndexMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    /// Look up a child of a compound tag
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(map) => map.get(key),
+            _ => None,
+        }
+    }
+
+    /// Any integer tag, widened to i64
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Tag::Byte(v) => Some(*v as i64),
+            Tag::Short(v) => Some(*v as i64),
+            Tag::Int(v) => Some(*v as i64),
+            Tag::Long(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+fn read_exact<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], Error> {
+    let mut buf = [0u8; N];
+    reader
+        .read_exact(&mut buf)
+        .context("Unexpected end of NBT data")?;
+    Ok(buf)
+}
+
+fn read_len(reader: &mut impl Read) -> Result<usize, Error> {
+    let len = i32::from_be_bytes(read_exact(reader)?);
+    usize::try_from(len).map_err(|_| eyre!("Negative length {} in NBT data", len).into())
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, Error> {
+    let len = u16::from_be_bytes(read_exact(reader)?) as usize;
+    let mut buf = vec![0u8; len];
+    reader
+        .read_exact(&mut buf)
+        .context("Unexpected end of NBT data")?;
+    // NBT uses modified UTF-8, which only differs from UTF-8 for null and supplementary characters
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Read `len` elements without trusting `len` for the allocation up front
+fn read_array<R: Read, T>(
+    reader: &mut R,
+    mut read_element: impl FnMut(&mut R) -> Result<T, Error>,
+) -> Result<Vec<T>, Error> {
+    let len = read_len(reader)?;
+    let mut ret = Vec::with_capacity(len.min(4096));
+    for _ in 0..len {
+        ret.push(read_element(reader)?);
+    }
+    Ok(ret)
+}
+
+fn read_payload(reader: &mut impl Read, tag_id: u8, depth: usize) -> Result<Tag, Error> {
+    if depth > MAX_DEPTH {
+        return Err(eyre!("NBT data is nested too deeply").into());
+    }
+    Ok(match tag_id {
+        0 => Tag::End,
+        1 => Tag::Byte(i8::from_be_bytes(read_exact(reader)?)),
+        2 => Tag::Short(i16::from_be_bytes(read_exact(reader)?)),
+        3 => Tag::Int(i32::from_be_bytes(read_exact(reader)?)),
+        4 => Tag::Long(i64::from_be_bytes(read_exact(reader)?)),
+        5 => Tag::Float(f32::from_be_bytes(read_exact(reader)?)),
+        6 => Tag::Double(f64::from_be_bytes(read_exact(reader)?)),
+        7 => Tag::ByteArray(read_array(reader, |r| {
+            Ok(i8::from_be_bytes(read_exact(r)?))
+        })?),
+        8 => Tag::String(read_string(reader)?),
+        9 => {
+            let element_id = u8::from_be_bytes(read_exact(reader)?);
+            Tag::List(read_array(reader, |r| {
+                read_payload(r, element_id, depth + 1)
+            })?)
+        }
+        10 => {
+            let mut map = IndexMap::new();
+            loop {
+                let child_id = u8::from_be_bytes(read_exact(reader)?);
+                if child_id == 0 {
+                    break;
+                }
+                let name = read_string(reader)?;
+                map.insert(name, read_payload(reader, child_id, depth + 1)?);
+            }
+            Tag::Compound(map)
+        }
+        11 => Tag::IntArray(read_array(reader, |r| {
+            Ok(i32::from_be_bytes(read_exact(r)?))
+        })?),
+        12 => Tag::LongArray(read_array(reader, |r| {
+            Ok(i64::from_be_bytes(read_exact(r)?))
+        })?),
+        _ => return Err(eyre!("Unknown NBT tag id {}", tag_id).into()),
+    })
+}
+
+/// Read an uncompressed NBT document, returning the name and value of the root tag
+pub fn read_nbt(reader: &mut impl Read) -> Result<(String, Tag), Error> {
+    let tag_id = u8::from_be_bytes(read_exact(reader)?);
+    if tag_id == 0 {
+        return Ok((String::new(), Tag::End));
+    }
+    let name = read_string(reader)?;
+    Ok((name, read_payload(reader, tag_id, 0)?))
+}
+
+/// Read an NBT file such as `level.dat`, which may or may not be gzipped
+pub fn read_nbt_file(path: &Path) -> Result<(String, Tag), Error> {
+    let content = std::fs::read(path).context(format!("Failed to read {}", path.display()))?;
+    if content.starts_with(&[0x1f, 0x8b]) {
+        read_nbt(&mut BufReader::new(GzDecoder::new(content.as_slice())))
+    } else {
+        read_nbt(&mut content.as_slice())
+    }
+    .map_err(|e| {
+        eyre!(
+            "Failed to parse NBT file {}: {:#}",
+            path.display(),
+            e.source
+        )
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn write_string(buf: &mut Vec<u8>, s: &str) {
+        buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    #[test]
+    fn test_read_nbt() {
+        let mut buf = Vec::new();
+        buf.push(10);
+        write_string(&mut buf, "");
+        buf.push(10);
+        write_string(&mut buf, "Data");
+        buf.push(8);
+        write_string(&mut buf, "LevelName");
+        write_string(&mut buf, "My World");
+        buf.push(4);
+        write_string(&mut buf, "RandomSeed");
+        buf.extend_from_slice(&(-42_i64).to_be_bytes());
+        buf.push(1);
+        write_string(&mut buf, "Difficulty");
+        buf.push(2);
+        buf.push(9);
+        write_string(&mut buf, "Pos");
+        buf.push(6);
+        buf.extend_from_slice(&2_i32.to_be_bytes());
+        buf.extend_from_slice(&1.5_f64.to_be_bytes());
+        buf.extend_from_slice(&(-3.0_f64).to_be_bytes());
+        buf.push(11);
+        write_string(&mut buf, "Ids");
+        buf.extend_from_slice(&1_i32.to_be_bytes());
+        buf.extend_from_slice(&7_i32.to_be_bytes());
+        buf.push(0);
+        buf.push(0);
+
+        let (name, root) = read_nbt(&mut buf.as_slice()).unwrap();
+        assert_eq!(name, "");
+        let data = root.get("Data").unwrap();
+        assert_eq!(
+            data.get("LevelName").and_then(Tag::as_str),
+            Some("My World")
+        );
+        assert_eq!(data.get("RandomSeed").and_then(Tag::as_i64), Some(-42));
+        assert_eq!(data.get("Difficulty").and_then(Tag::as_i64), Some(2));
+        assert_eq!(
+            data.get("Pos"),
+            Some(&Tag::List(vec![Tag::Double(1.5), Tag::Double(-3.0)]))
+        );
+        assert_eq!(data.get("Ids"), Some(&Tag::IntArray(vec![7])));
+
+        // truncated data and unknown tags are errors rather than panics
+        assert!(read_nbt(&mut &buf[..buf.len() - 3]).is_err());
+        assert!(read_nbt(&mut [13_u8, 0, 0].as_slice()).is_err());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("level.dat");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&buf).unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+        assert_eq!(read_nbt_file(&path).unwrap(), (name, root));
+    }
+}
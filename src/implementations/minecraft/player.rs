@@ -3,10 +3,16 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use color_eyre::eyre::eyre;
+
+use crate::error::ErrorKind;
+use crate::events::CausedBy;
 use crate::traits::t_player::Player;
 use crate::traits::t_player::{TPlayer, TPlayerManagement};
+use crate::traits::t_server::TServer;
 use crate::Error;
 
+use super::access_list::{single_line, validate_player_name};
 use super::configurable::ServerPropertySetting;
 use super::MinecraftInstance;
 
@@ -21,23 +27,40 @@ impl MinecraftPlayer {
     pub fn new(name: String, uuid: Option<String>) -> Self {
         Self { name, uuid }
     }
+
+    /// Plugins fill the ping sample with fake entries to show extra lines on hover, and hidden
+    /// players show up as "Anonymous Player" with the nil uuid. Only a valid name with an
+    /// offline (version 3) or online (version 4) uuid is a real player
+    pub fn is_real_player(&self) -> bool {
+        validate_player_name(&self.name).is_ok()
+            && match &self.uuid {
+                Some(uuid) => matches!(
+                    uuid::Uuid::parse_str(uuid).map(|uuid| uuid.get_version_num()),
+                    Ok(3) | Ok(4)
+                ),
+                // query only reports names, players missing from the user cache have no uuid
+                None => true,
+            }
+    }
 }
 
 impl PartialEq for MinecraftPlayer {
     fn eq(&self, other: &Self) -> bool {
-        // if uuid is not set, compare by name
-        if self.uuid.is_none() || other.uuid.is_none() {
-            self.name == other.name
-        } else {
-            self.uuid == other.uuid
-        }
+        // names are unique on a server at any given time,
+        // the uuid only disambiguates when both sides know it
+        self.name == other.name
+            && match (&self.uuid, &other.uuid) {
+                (Some(uuid), Some(other_uuid)) => uuid.eq_ignore_ascii_case(other_uuid),
+                _ => true,
+            }
     }
 }
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 impl Hash for MinecraftPlayer {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.uuid.hash(state);
+        // must agree with eq, where a player without a uuid equals the same player with one
+        self.name.hash(state);
     }
 }
 
@@ -54,6 +77,10 @@ impl TPlayer for MinecraftPlayer {
 #[async_trait]
 impl TPlayerManagement for MinecraftInstance {
     async fn get_player_count(&self) -> Result<u32, Error> {
+        // the server's own count does not drift when a join or leave line is missed
+        if let Some(status) = self.server_status.lock().await.as_ref() {
+            return Ok(status.online);
+        }
         Ok(self.players_manager.lock().await.count())
     }
 
@@ -67,6 +94,121 @@ impl TPlayerManagement for MinecraftInstance {
     }
 
     async fn get_player_list(&self) -> Result<HashSet<Player>, Error> {
+        // query lists every player, the players manager relies on log lines
+        if let Some(query_stat) = self.query_stat.lock().await.as_ref() {
+            let user_cache = self.user_cache.lock().await;
+            return Ok(query_stat
+                .players
+                .iter()
+                .map(|name| {
+                    Player::MinecraftPlayer(MinecraftPlayer::new(
+                        name.clone(),
+                        user_cache.uuid_of(name),
+                    ))
+                })
+                .collect());
+        }
         Ok(self.players_manager.lock().await.clone().into())
     }
+
+    async fn kick_player(
+        &mut self,
+        player_name: &str,
+        reason: Option<String>,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        validate_player_name(player_name)?;
+        let command = match single_line(reason) {
+            Some(reason) => format!("kick {} {}", player_name, reason),
+            None => format!("kick {}", player_name),
+        };
+        self.send_command(&command, caused_by).await
+    }
+
+    async fn ban_player(
+        &mut self,
+        player_name: &str,
+        reason: Option<String>,
+        duration: Option<u32>,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        let expires = duration
+            .map(|duration| chrono::Local::now() + chrono::Duration::seconds(duration as i64));
+        self.add_ban(player_name, reason, expires, caused_by).await
+    }
+
+    async fn pardon_player(&mut self, player_name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        self.remove_ban(player_name, caused_by).await
+    }
+
+    async fn op_player(&mut self, player_name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        self.add_op(player_name, caused_by).await
+    }
+
+    async fn deop_player(&mut self, player_name: &str, caused_by: CausedBy) -> Result<(), Error> {
+        self.remove_op(player_name, caused_by).await
+    }
+
+    async fn message_player(
+        &mut self,
+        player_name: &str,
+        message: &str,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        validate_player_name(player_name)?;
+        let message = single_line(Some(message.to_string())).ok_or_else(|| Error {
+            kind: ErrorKind::BadRequest,
+            source: eyre!("Message cannot be empty"),
+        })?;
+        self.send_command(&format!("tell {} {}", player_name, message), caused_by)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_player_identity() {
+        let with_uuid = MinecraftPlayer::new(
+            "Notch".to_string(),
+            Some("069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string()),
+        );
+        let without_uuid = MinecraftPlayer::new("Notch".to_string(), None);
+        assert_eq!(with_uuid, without_uuid);
+
+        let players = HashSet::from([with_uuid.clone()]);
+        assert!(players.contains(&without_uuid));
+        assert!(!players.contains(&MinecraftPlayer::new("Jeb_".to_string(), None)));
+        assert_ne!(
+            with_uuid,
+            MinecraftPlayer::new(
+                "Notch".to_string(),
+                Some("853c80ef-3c37-49fd-aa49-938b674adae6".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn test_is_real_player() {
+        let player = |name: &str, uuid: Option<&str>| {
+            MinecraftPlayer::new(name.to_string(), uuid.map(|uuid| uuid.to_string()))
+        };
+        assert!(player("Notch", Some("069a79f4-44e9-4726-a5be-fca90e38aaf5")).is_real_player());
+        // offline mode
+        assert!(player("Notch", Some("b50ad385-829d-3141-a216-7e7d7539ba7f")).is_real_player());
+        assert!(player("Notch", None).is_real_player());
+        assert!(!player(
+            "Anonymous Player",
+            Some("00000000-0000-0000-0000-000000000000")
+        )
+        .is_real_player());
+        assert!(
+            !player("§aWelcome!", Some("069a79f4-44e9-4726-a5be-fca90e38aaf5")).is_real_player()
+        );
+        // version 1, made up by a plugin
+        assert!(!player("Notch", Some("c232ab00-9414-11ec-b3c8-9e6bdeced846")).is_real_player());
+        assert!(!player("Notch", Some("not-a-uuid")).is_real_player());
+    }
 }
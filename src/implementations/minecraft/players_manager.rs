@@ -77,6 +77,54 @@ impl PlayersManager {
         }
     }
 
+    /// Bring the tracked players in line with what the server reports over Server List Ping.
+    /// The sample is only a handful of players, so players are only dropped when it covers everyone online
+    pub fn reconcile(&mut self, online: u32, sample: &[MinecraftPlayer], instance_name: String) {
+        // plugins pad the sample with fake entries, they neither join nor count towards `online`
+        let sample: Vec<MinecraftPlayer> = sample
+            .iter()
+            .filter(|player| player.is_real_player())
+            .cloned()
+            .collect();
+        let players_joined: HashSet<MinecraftPlayer> = sample
+            .iter()
+            .filter(|player| !self.players.contains(player))
+            .cloned()
+            .collect();
+        let players_left: HashSet<MinecraftPlayer> = if sample.len() as u32 == online {
+            self.players
+                .iter()
+                .filter(|player| !sample.contains(player))
+                .cloned()
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        if players_joined.is_empty() && players_left.is_empty() {
+            return;
+        }
+        for player in &players_left {
+            self.players.remove(player);
+        }
+        self.players.extend(players_joined.iter().cloned());
+        self.event_broadcaster.send(Event {
+            event_inner: EventInner::InstanceEvent(InstanceEvent {
+                instance_uuid: self.instance_uuid.clone(),
+                instance_name,
+                instance_event_inner: InstanceEventInner::PlayerChange {
+                    player_list: self.players.iter().map(|p| p.clone().into()).collect(),
+                    players_joined: players_joined.into_iter().map(|p| p.into()).collect(),
+                    players_left: players_left.into_iter().map(|p| p.into()).collect(),
+                },
+            }),
+            details: "".to_string(),
+            snowflake: Snowflake::default(),
+            caused_by: CausedBy::Instance {
+                instance_uuid: self.instance_uuid.clone(),
+            },
+        });
+    }
+
     pub fn count(&self) -> u32 {
         self.players.len() as u32
     }
@@ -265,4 +313,83 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_reconcile() {
+        use crate::implementations::minecraft::util::offline_player_uuid;
+        use crate::types::InstanceUuid;
+        use crate::{events::InstanceEventInner, traits::t_player::Player};
+        use std::collections::HashSet;
+
+        let player = |name: &str| super::MinecraftPlayer {
+            name: name.to_string(),
+            uuid: Some(offline_player_uuid(name)),
+        };
+        let (tx, mut rx) = EventBroadcaster::new(10);
+        let mut players_manager = super::PlayersManager::new(tx, InstanceUuid::default());
+        players_manager.add_player(player("player1"), "mock_instance".to_string());
+        players_manager.add_player(player("player2"), "mock_instance".to_string());
+        rx.recv().await.unwrap();
+        rx.recv().await.unwrap();
+
+        // partial sample, the missed join is picked up but nobody is dropped
+        players_manager.reconcile(
+            5,
+            &[player("player1"), player("player3")],
+            "mock_instance".to_string(),
+        );
+        assert_eq!(players_manager.count(), 3);
+        // nothing changed, no event
+        players_manager.reconcile(5, &[player("player3")], "mock_instance".to_string());
+        // complete sample, the missed leave of player2 is picked up
+        // fake entries are neither added nor make the sample look incomplete
+        players_manager.reconcile(
+            2,
+            &[
+                player("player1"),
+                player("player3"),
+                super::MinecraftPlayer::new("§aJoin our Discord!".to_string(), None),
+            ],
+            "mock_instance".to_string(),
+        );
+        assert_eq!(players_manager.count(), 2);
+        players_manager.reconcile(0, &[], "mock_instance".to_string());
+        assert_eq!(players_manager.count(), 0);
+
+        let expected = vec![
+            (
+                HashSet::from([Player::MinecraftPlayer(player("player3"))]),
+                HashSet::new(),
+            ),
+            (
+                HashSet::new(),
+                HashSet::from([Player::MinecraftPlayer(player("player2"))]),
+            ),
+            (
+                HashSet::new(),
+                HashSet::from([
+                    Player::MinecraftPlayer(player("player1")),
+                    Player::MinecraftPlayer(player("player3")),
+                ]),
+            ),
+        ];
+        for (expected_joined, expected_left) in expected {
+            match rx.recv().await.unwrap().event_inner {
+                crate::events::EventInner::InstanceEvent(instance_event) => {
+                    match instance_event.instance_event_inner {
+                        InstanceEventInner::PlayerChange {
+                            players_joined,
+                            players_left,
+                            ..
+                        } => {
+                            assert_eq!(players_joined, expected_joined);
+                            assert_eq!(players_left, expected_left);
+                        }
+                        _ => panic!("Unexpected event"),
+                    }
+                }
+                _ => panic!("Unexpected event"),
+            }
+        }
+    }
 }
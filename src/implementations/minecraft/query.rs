@@ -0,0 +1,274 @@
+//! Client for the GameSpy4 based UDP Query protocol, enabled with `enable-query`
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use tokio::net::UdpSocket;
+use ts_rs::TS;
+
+use crate::error::Error;
+
+use super::configurable::ServerPropertySetting;
+use super::MinecraftInstance;
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+const QUERY_MAGIC: [u8; 2] = [0xFE, 0xFD];
+const TYPE_HANDSHAKE: u8 = 0x09;
+const TYPE_STAT: u8 = 0x00;
+/// The server only looks at the lower 4 bits of each byte of the session id
+const SESSION_ID_MASK: i32 = 0x0F0F0F0F;
+/// Constant padding after the header of a full stat response
+const KEY_VALUE_PADDING: &[u8] = b"splitnum\x00\x80\x00";
+const PLAYER_PADDING: &[u8] = b"\x01player_\x00\x00";
+const MAX_RESPONSE_LENGTH: usize = 65535;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct QueryStat {
+    pub motd: String,
+    pub game_type: String,
+    pub version: String,
+    /// The server software, e.g. "Paper on 1.20.1", `None` on vanilla
+    pub server_mod: Option<String>,
+    pub plugins: Vec<String>,
+    pub map: String,
+    pub online: u32,
+    pub max: u32,
+    /// Every online player, unlike the sample from Server List Ping
+    pub players: Vec<String>,
+}
+
+fn request(kind: u8, session_id: i32, payload: &[u8]) -> Vec<u8> {
+    let mut buf = QUERY_MAGIC.to_vec();
+    buf.push(kind);
+    buf.extend_from_slice(&session_id.to_be_bytes());
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// Strips the type and session id that prefix every response
+fn response_body(response: &[u8], kind: u8, session_id: i32) -> Result<&[u8], Error> {
+    if response.len() < 5 || response[0] != kind {
+        return Err(eyre!("Unexpected query response type").into());
+    }
+    if response[1..5] != session_id.to_be_bytes() {
+        return Err(eyre!("Query response is for another session").into());
+    }
+    Ok(&response[5..])
+}
+
+/// Splits off a null terminated string
+fn read_c_string<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let end = buf
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or_else(|| eyre!("Query response is truncated"))?;
+    let (string, rest) = buf.split_at(end);
+    *buf = &rest[1..];
+    Ok(string)
+}
+
+pub fn parse_challenge_token(body: &[u8]) -> Result<i32, Error> {
+    let mut body = body;
+    let token = String::from_utf8_lossy(read_c_string(&mut body)?).to_string();
+    token
+        .trim()
+        .parse::<i32>()
+        .context(format!("Invalid challenge token {}", token))
+        .map_err(Error::from)
+}
+
+/// The plugins value looks like "Paper on 1.20.1: WorldEdit 7.2.15; LuckPerms 5.4.98"
+fn parse_plugins(plugins: &str) -> (Option<String>, Vec<String>) {
+    if plugins.trim().is_empty() {
+        return (None, Vec::new());
+    }
+    match plugins.split_once(": ") {
+        Some((server_mod, plugins)) => (
+            Some(server_mod.trim().to_string()),
+            plugins
+                .split("; ")
+                .map(|plugin| plugin.trim().to_string())
+                .filter(|plugin| !plugin.is_empty())
+                .collect(),
+        ),
+        None => (Some(plugins.trim().to_string()), Vec::new()),
+    }
+}
+
+pub fn parse_full_stat(body: &[u8]) -> Result<QueryStat, Error> {
+    let mut body = body
+        .strip_prefix(KEY_VALUE_PADDING)
+        .ok_or_else(|| eyre!("Unexpected full stat response"))?;
+    let mut values = std::collections::HashMap::new();
+    loop {
+        let key = read_c_string(&mut body)?;
+        if key.is_empty() {
+            break;
+        }
+        let value = read_c_string(&mut body)?;
+        values.insert(
+            String::from_utf8_lossy(key).to_string(),
+            String::from_utf8_lossy(value).to_string(),
+        );
+    }
+    let mut body = body
+        .strip_prefix(PLAYER_PADDING)
+        .ok_or_else(|| eyre!("Unexpected full stat response"))?;
+    let mut players = Vec::new();
+    loop {
+        let player = read_c_string(&mut body)?;
+        if player.is_empty() {
+            break;
+        }
+        players.push(String::from_utf8_lossy(player).to_string());
+    }
+
+    let value = |key: &str| values.get(key).cloned().unwrap_or_default();
+    let (server_mod, plugins) = parse_plugins(&value("plugins"));
+    Ok(QueryStat {
+        motd: value("hostname"),
+        game_type: value("gametype"),
+        version: value("version"),
+        server_mod,
+        plugins,
+        map: value("map"),
+        online: value("numplayers").parse().unwrap_or(players.len() as u32),
+        max: value("maxplayers").parse().unwrap_or_default(),
+        players,
+    })
+}
+
+async fn send_and_receive(socket: &UdpSocket, request: &[u8]) -> Result<Vec<u8>, Error> {
+    socket
+        .send(request)
+        .await
+        .context("Failed to send query request")?;
+    let mut buf = vec![0; MAX_RESPONSE_LENGTH];
+    let len = socket
+        .recv(&mut buf)
+        .await
+        .context("Failed to receive query response")?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+pub async fn query_full_stat(host: &str, port: u16) -> Result<QueryStat, Error> {
+    tokio::time::timeout(QUERY_TIMEOUT, async {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))
+            .await
+            .context("Failed to bind query socket")?;
+        socket
+            .connect((host, port))
+            .await
+            .context(format!("Failed to connect to {}:{}", host, port))?;
+        let session_id = rand::random::<i32>() & SESSION_ID_MASK;
+
+        let response = send_and_receive(&socket, &request(TYPE_HANDSHAKE, session_id, &[])).await?;
+        let challenge_token =
+            parse_challenge_token(response_body(&response, TYPE_HANDSHAKE, session_id)?)?;
+
+        // the 4 trailing bytes ask for the full stat instead of the basic one
+        let mut payload = challenge_token.to_be_bytes().to_vec();
+        payload.extend_from_slice(&[0; 4]);
+        let response = send_and_receive(&socket, &request(TYPE_STAT, session_id, &payload)).await?;
+        parse_full_stat(response_body(&response, TYPE_STAT, session_id)?)
+    })
+    .await
+    .context("Query timed out")?
+}
+
+impl MinecraftInstance {
+    /// The query port when `enable-query` is on, it defaults to the game port
+    pub async fn query_port(&self) -> Option<u32> {
+        let lock = self.configurable_manifest.lock().await;
+        let enabled = lock
+            .get_unique_setting_key(&ServerPropertySetting::EnableQuery(false).get_identifier())
+            .and_then(|v| v.get_value().map(|v| v.try_as_boolean().ok()))
+            .flatten()
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+        let query_port = lock
+            .get_unique_setting_key(&ServerPropertySetting::QueryPort(0).get_identifier())
+            .and_then(|v| v.get_value().map(|v| v.try_as_unsigned_integer().ok()))
+            .flatten();
+        drop(lock);
+        match query_port {
+            Some(port) => Some(port),
+            None => Some(self.config.lock().await.port),
+        }
+    }
+
+    /// The stat from the last successful query, `None` when the server is not running
+    /// or query is disabled
+    pub async fn get_query_stat(&self) -> Option<QueryStat> {
+        self.query_stat.lock().await.clone()
+    }
+
+    pub(super) async fn refresh_query_stat(&self, port: u32) -> Result<QueryStat, Error> {
+        let stat = query_full_stat("127.0.0.1", port as u16).await?;
+        self.query_stat.lock().await.replace(stat.clone());
+        Ok(stat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_stat() {
+        let session_id = 0x01020304 & SESSION_ID_MASK;
+        let handshake = [
+            &[TYPE_HANDSHAKE][..],
+            &session_id.to_be_bytes(),
+            b"9513307\x00",
+        ]
+        .concat();
+        assert_eq!(
+            parse_challenge_token(response_body(&handshake, TYPE_HANDSHAKE, session_id).unwrap())
+                .unwrap(),
+            9513307
+        );
+        assert!(response_body(&handshake, TYPE_HANDSHAKE, session_id + 1).is_err());
+        assert_eq!(
+            request(TYPE_STAT, session_id, &[0, 0x91, 0x29, 0x5b, 0, 0, 0, 0]),
+            vec![0xfe, 0xfd, 0x00, 0x01, 0x02, 0x03, 0x04, 0x00, 0x91, 0x29, 0x5b, 0, 0, 0, 0]
+        );
+
+        let stat = [
+            &[TYPE_STAT][..],
+            &session_id.to_be_bytes(),
+            KEY_VALUE_PADDING,
+            b"hostname\x00A Minecraft Server\x00gametype\x00SMP\x00game_id\x00MINECRAFT\x00",
+            b"version\x001.20.1\x00plugins\x00Paper on 1.20.1: WorldEdit 7.2.15; LuckPerms 5.4.98\x00",
+            b"map\x00world\x00numplayers\x002\x00maxplayers\x0020\x00hostport\x0025565\x00",
+            b"hostip\x00127.0.0.1\x00\x00",
+            PLAYER_PADDING,
+            b"Notch\x00jeb_\x00\x00",
+        ]
+        .concat();
+        assert_eq!(
+            parse_full_stat(response_body(&stat, TYPE_STAT, session_id).unwrap()).unwrap(),
+            QueryStat {
+                motd: "A Minecraft Server".to_string(),
+                game_type: "SMP".to_string(),
+                version: "1.20.1".to_string(),
+                server_mod: Some("Paper on 1.20.1".to_string()),
+                plugins: vec![
+                    "WorldEdit 7.2.15".to_string(),
+                    "LuckPerms 5.4.98".to_string()
+                ],
+                map: "world".to_string(),
+                online: 2,
+                max: 20,
+                players: vec!["Notch".to_string(), "jeb_".to_string()],
+            }
+        );
+
+        assert_eq!(parse_plugins(""), (None, Vec::new()));
+        assert!(parse_full_stat(b"splitnum\x00\x80\x00hostname\x00trunc").is_err());
+    }
+}
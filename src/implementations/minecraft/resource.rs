@@ -1,24 +1,692 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::{Path, PathBuf},
+};
+
 use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use ts_rs::TS;
 
-use crate::{error::Error, traits::t_resource::TResourceManagement};
+use crate::{
+    error::{Error, ErrorKind},
+    traits::t_resource::TResourceManagement,
+};
 
 use super::MinecraftInstance;
 
+/// Jars moved here are not loaded by the server
+const DISABLED_DIR_NAME: &str = "disabled";
+/// How deep to look into jars bundled inside other jars
+const MAX_NESTED_JAR_DEPTH: u32 = 3;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub enum MinecraftResourceKind {
+    Mod,
+    Plugin,
+}
+
+impl MinecraftResourceKind {
+    fn dir_name(&self) -> &'static str {
+        match self {
+            MinecraftResourceKind::Mod => "mods",
+            MinecraftResourceKind::Plugin => "plugins",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub enum ModLoader {
+    Fabric,
+    Forge,
+    Bukkit,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub enum DependencyKind {
+    Required,
+    Optional,
+    Incompatible,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct ModDependency {
+    pub id: String,
+    /// In the loader's own syntax, e.g. `>=0.14.0` for Fabric or `[40,)` for Forge
+    pub version_range: Option<String>,
+    pub kind: DependencyKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct ModMetadata {
+    pub loader: ModLoader,
+    pub id: String,
+    pub name: String,
+    pub version: Option<String>,
+    pub authors: Vec<String>,
+    pub dependencies: Vec<ModDependency>,
+    /// Other mod ids this mod satisfies dependencies on, including the mods bundled in its jar
+    pub provides: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct MinecraftResource {
+    pub file_name: String,
+    pub kind: MinecraftResourceKind,
+    pub enabled: bool,
+    pub size: u64,
+    /// A Forge jar can contain several mods
+    pub metadata: Vec<ModMetadata>,
+    /// Set if the jar could not be read
+    pub error: Option<String>,
+}
+
+fn parse_fabric_mod_json(content: &str) -> Result<ModMetadata, Error> {
+    let json: serde_json::Value = serde_json::from_str(content.trim_start_matches('\u{feff}'))
+        .context("Failed to parse fabric.mod.json")?;
+    let id = json["id"]
+        .as_str()
+        .ok_or_else(|| eyre!("fabric.mod.json is missing the mod id"))?
+        .to_string();
+    let authors = json["authors"]
+        .as_array()
+        .map(|authors| {
+            authors
+                .iter()
+                .filter_map(|author| author.as_str().or_else(|| author["name"].as_str()))
+                .map(|author| author.to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut dependencies = Vec::new();
+    for (field, kind) in [
+        ("depends", DependencyKind::Required),
+        ("recommends", DependencyKind::Optional),
+        ("suggests", DependencyKind::Optional),
+        ("breaks", DependencyKind::Incompatible),
+        ("conflicts", DependencyKind::Incompatible),
+    ] {
+        if let Some(entries) = json[field].as_object() {
+            for (dependency_id, version_range) in entries {
+                // an array of version predicates matches if any of them does
+                let version_range = match version_range {
+                    serde_json::Value::String(range) => Some(range.clone()),
+                    serde_json::Value::Array(ranges) => Some(
+                        ranges
+                            .iter()
+                            .filter_map(|range| range.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" || "),
+                    ),
+                    _ => None,
+                };
+                dependencies.push(ModDependency {
+                    id: dependency_id.clone(),
+                    version_range,
+                    kind,
+                });
+            }
+        }
+    }
+    Ok(ModMetadata {
+        loader: ModLoader::Fabric,
+        name: json["name"].as_str().unwrap_or(&id).to_string(),
+        id,
+        version: json["version"].as_str().map(|version| version.to_string()),
+        authors,
+        dependencies,
+        provides: json["provides"]
+            .as_array()
+            .map(|provides| {
+                provides
+                    .iter()
+                    .filter_map(|id| id.as_str().map(|id| id.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+/// A field that is either a single string or a list of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct ModsToml {
+    #[serde(default)]
+    mods: Vec<ModsTomlMod>,
+    /// Keyed by the id of the mod that has the dependencies
+    #[serde(default)]
+    dependencies: HashMap<String, Vec<ModsTomlDependency>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsTomlMod {
+    mod_id: String,
+    version: Option<String>,
+    display_name: Option<String>,
+    /// Usually a comma separated string, some mods use a list instead
+    authors: Option<OneOrMany>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModsTomlDependency {
+    mod_id: String,
+    // newer versions of Forge replaced `mandatory` with `type`
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    mandatory: Option<bool>,
+    version_range: Option<String>,
+}
+
+/// `implementation_version` replaces the `${file.jarVersion}` placeholder
+fn parse_mods_toml(
+    content: &str,
+    implementation_version: Option<&str>,
+) -> Result<Vec<ModMetadata>, Error> {
+    let mut mods_toml: ModsToml = toml::from_str(content.trim_start_matches('\u{feff}'))
+        .context("Failed to parse mods.toml")?;
+    Ok(mods_toml
+        .mods
+        .into_iter()
+        .map(|mod_entry| {
+            let version =
+                mod_entry
+                    .version
+                    .map(|version| match (version.as_str(), implementation_version) {
+                        ("${file.jarVersion}", Some(implementation_version)) => {
+                            implementation_version.to_string()
+                        }
+                        _ => version,
+                    });
+            let authors = match mod_entry.authors {
+                Some(OneOrMany::One(authors)) => authors
+                    .split(',')
+                    .map(|author| author.trim().to_string())
+                    .filter(|author| !author.is_empty())
+                    .collect(),
+                Some(OneOrMany::Many(authors)) => authors,
+                None => Vec::new(),
+            };
+            let dependencies = mods_toml
+                .dependencies
+                .remove(&mod_entry.mod_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|dependency| ModDependency {
+                    kind: match dependency.kind.map(|kind| kind.to_lowercase()) {
+                        Some(kind) if kind == "required" => DependencyKind::Required,
+                        Some(kind) if kind == "incompatible" => DependencyKind::Incompatible,
+                        Some(_) => DependencyKind::Optional,
+                        None if dependency.mandatory.unwrap_or(false) => DependencyKind::Required,
+                        None => DependencyKind::Optional,
+                    },
+                    id: dependency.mod_id,
+                    version_range: dependency.version_range,
+                })
+                .collect();
+            ModMetadata {
+                loader: ModLoader::Forge,
+                name: mod_entry
+                    .display_name
+                    .unwrap_or_else(|| mod_entry.mod_id.clone()),
+                id: mod_entry.mod_id,
+                version,
+                authors,
+                dependencies,
+                provides: Vec::new(),
+            }
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct PluginYml {
+    name: String,
+    /// Often written unquoted, so YAML may read it as a number
+    version: Option<serde_yaml::Value>,
+    author: Option<String>,
+    authors: Option<Vec<String>>,
+    depend: Option<Vec<String>>,
+    softdepend: Option<Vec<String>>,
+}
+
+/// Reads a Bukkit `plugin.yml`
+fn parse_plugin_yml(content: &str) -> Result<ModMetadata, Error> {
+    let plugin_yml: PluginYml = serde_yaml::from_str(content.trim_start_matches('\u{feff}'))
+        .context("Failed to parse plugin.yml")?;
+    let mut authors: Vec<String> = plugin_yml.author.into_iter().collect();
+    authors.extend(plugin_yml.authors.unwrap_or_default());
+    let mut dependencies = Vec::new();
+    for (ids, kind) in [
+        (plugin_yml.depend, DependencyKind::Required),
+        (plugin_yml.softdepend, DependencyKind::Optional),
+    ] {
+        for id in ids.unwrap_or_default() {
+            dependencies.push(ModDependency {
+                id,
+                version_range: None,
+                kind,
+            });
+        }
+    }
+    Ok(ModMetadata {
+        loader: ModLoader::Bukkit,
+        // Bukkit identifies plugins by their name
+        id: plugin_yml.name.clone(),
+        name: plugin_yml.name,
+        version: plugin_yml.version.and_then(|version| match version {
+            serde_yaml::Value::String(version) => Some(version),
+            serde_yaml::Value::Number(version) => Some(version.to_string()),
+            _ => None,
+        }),
+        authors,
+        dependencies,
+        provides: Vec::new(),
+    })
+}
+
+fn read_zip_entry_bytes<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Option<Vec<u8>> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut content = Vec::new();
+    entry.read_to_end(&mut content).ok()?;
+    Some(content)
+}
+
+pub(super) fn read_zip_entry<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Option<String> {
+    read_zip_entry_bytes(archive, name).map(|content| String::from_utf8_lossy(&content).to_string())
+}
+
+/// The paths in a list of bundled jars, e.g. `"jars": [{ "file": "META-INF/jars/lib.jar" }]`
+fn nested_jar_paths(content: &str, key: &str) -> Vec<String> {
+    serde_json::from_str::<serde_json::Value>(content.trim_start_matches('\u{feff}'))
+        .ok()
+        .and_then(|json| {
+            json["jars"].as_array().map(|jars| {
+                jars.iter()
+                    .filter_map(|jar| jar[key].as_str().map(|path| path.to_string()))
+                    .collect()
+            })
+        })
+        .unwrap_or_default()
+}
+
+fn read_archive_metadata<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    depth: u32,
+) -> Result<Vec<ModMetadata>, Error> {
+    let (mut metadata, nested_jars) =
+        if let Some(content) = read_zip_entry(archive, "fabric.mod.json") {
+            (
+                vec![parse_fabric_mod_json(&content)?],
+                nested_jar_paths(&content, "file"),
+            )
+        } else if let Some(content) = read_zip_entry(archive, "META-INF/mods.toml") {
+            let implementation_version =
+                read_zip_entry(archive, "META-INF/MANIFEST.MF").and_then(|manifest| {
+                    manifest.lines().find_map(|line| {
+                        line.strip_prefix("Implementation-Version:")
+                            .map(|version| version.trim().to_string())
+                    })
+                });
+            (
+                parse_mods_toml(&content, implementation_version.as_deref())?,
+                read_zip_entry(archive, "META-INF/jarjar/metadata.json")
+                    .map(|content| nested_jar_paths(&content, "path"))
+                    .unwrap_or_default(),
+            )
+        } else if let Some(content) = read_zip_entry(archive, "plugin.yml") {
+            return Ok(vec![parse_plugin_yml(&content)?]);
+        } else {
+            return Ok(Vec::new());
+        };
+    if depth < MAX_NESTED_JAR_DEPTH {
+        let mut provided = Vec::new();
+        for path in nested_jars {
+            let nested_metadata = read_zip_entry_bytes(archive, &path)
+                .and_then(|content| zip::ZipArchive::new(Cursor::new(content)).ok())
+                .and_then(|mut nested| read_archive_metadata(&mut nested, depth + 1).ok())
+                .unwrap_or_default();
+            for nested in nested_metadata {
+                provided.push(nested.id);
+                provided.extend(nested.provides);
+            }
+        }
+        if let Some(metadata) = metadata.first_mut() {
+            metadata.provides.extend(provided);
+        }
+    }
+    Ok(metadata)
+}
+
+pub fn read_jar_metadata(path: &Path) -> Result<Vec<ModMetadata>, Error> {
+    let file = File::open(path).context(format!("Failed to open {}", path.display()))?;
+    let mut archive =
+        zip::ZipArchive::new(file).context(format!("Failed to read {}", path.display()))?;
+    read_archive_metadata(&mut archive, 0)
+}
+
+fn list_jars(dir: &Path) -> Vec<PathBuf> {
+    let mut ret: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.is_file()
+                        && path
+                            .extension()
+                            .map(|ext| ext.eq_ignore_ascii_case("jar"))
+                            .unwrap_or(false)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    ret.sort();
+    ret
+}
+
+fn read_resource(path: &Path, kind: MinecraftResourceKind, enabled: bool) -> MinecraftResource {
+    let (metadata, error) = match read_jar_metadata(path) {
+        Ok(metadata) => (metadata, None),
+        Err(e) => (Vec::new(), Some(format!("{:#}", e.source))),
+    };
+    MinecraftResource {
+        file_name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        kind,
+        enabled,
+        size: path.metadata().map(|m| m.len()).unwrap_or(0),
+        metadata,
+        error,
+    }
+}
+
+impl MinecraftInstance {
+    /// Every jar in `mods/` and `plugins/`, including disabled ones
+    pub async fn list_resources(&self) -> Vec<MinecraftResource> {
+        let path_to_instance = self.path_to_instance.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut ret = Vec::new();
+            for kind in [MinecraftResourceKind::Mod, MinecraftResourceKind::Plugin] {
+                let dir = path_to_instance.join(kind.dir_name());
+                for path in list_jars(&dir) {
+                    ret.push(read_resource(&path, kind, true));
+                }
+                for path in list_jars(&dir.join(DISABLED_DIR_NAME)) {
+                    ret.push(read_resource(&path, kind, false));
+                }
+            }
+            ret
+        })
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to list resources: {}", e);
+            Vec::new()
+        })
+    }
+
+    /// Returns the path to the jar named `file_name` and whether it is enabled
+    fn find_resource(&self, file_name: &str) -> Result<(PathBuf, bool), Error> {
+        if Path::new(file_name).file_name() != Some(OsStr::new(file_name)) {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Invalid resource name"),
+            });
+        }
+        for kind in [MinecraftResourceKind::Mod, MinecraftResourceKind::Plugin] {
+            let dir = self.path_to_instance.join(kind.dir_name());
+            if dir.join(file_name).is_file() {
+                return Ok((dir.join(file_name), true));
+            }
+            let disabled = dir.join(DISABLED_DIR_NAME).join(file_name);
+            if disabled.is_file() {
+                return Ok((disabled, false));
+            }
+        }
+        Err(Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Resource not found"),
+        })
+    }
+}
+
 #[async_trait]
 impl TResourceManagement for MinecraftInstance {
     async fn list(&self) -> Vec<serde_json::Value> {
-        todo!()
+        self.list_resources()
+            .await
+            .into_iter()
+            .filter_map(|resource| serde_json::to_value(resource).ok())
+            .collect()
     }
 
-    async fn load(&mut self, _resource: &str) -> Result<(), Error> {
-        todo!()
+    async fn load(&mut self, resource: &str) -> Result<(), Error> {
+        let (path, enabled) = self.find_resource(resource)?;
+        if enabled {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Resource is already enabled"),
+            });
+        }
+        let destination = path
+            .parent()
+            .and_then(|disabled_dir| disabled_dir.parent())
+            .ok_or_else(|| eyre!("Failed to find the directory of {}", resource))?
+            .join(resource);
+        if destination.exists() {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("An enabled resource with the same name already exists"),
+            });
+        }
+        crate::util::fs::rename(&path, &destination).await
+    }
+
+    async fn unload(&mut self, resource: &str) -> Result<(), Error> {
+        let (path, enabled) = self.find_resource(resource)?;
+        if !enabled {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Resource is already disabled"),
+            });
+        }
+        let disabled_dir = path
+            .parent()
+            .ok_or_else(|| eyre!("Failed to find the directory of {}", resource))?
+            .join(DISABLED_DIR_NAME);
+        let destination = disabled_dir.join(resource);
+        if destination.exists() {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("A disabled resource with the same name already exists"),
+            });
+        }
+        crate::util::fs::create_dir_all(&disabled_dir).await?;
+        crate::util::fs::rename(&path, &destination).await
+    }
+
+    async fn delete(&mut self, resource: &str) -> Result<(), Error> {
+        let (path, _) = self.find_resource(resource)?;
+        crate::util::fs::remove_file(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fabric_mod_json() {
+        let metadata = parse_fabric_mod_json(
+            r#"{
+                "schemaVersion": 1,
+                "id": "sodium",
+                "version": "0.4.10",
+                "name": "Sodium",
+                "authors": ["JellySquid", { "name": "IMS" }],
+                "provides": ["rubidium"],
+                "depends": { "fabricloader": ">=0.12.0", "minecraft": ["1.19.3", "1.19.4"] },
+                "breaks": { "optifabric": "*" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.loader, ModLoader::Fabric);
+        assert_eq!(metadata.id, "sodium");
+        assert_eq!(metadata.name, "Sodium");
+        assert_eq!(metadata.version.as_deref(), Some("0.4.10"));
+        assert_eq!(metadata.authors, vec!["JellySquid", "IMS"]);
+        assert_eq!(metadata.provides, vec!["rubidium"]);
+        assert!(metadata.dependencies.contains(&ModDependency {
+            id: "minecraft".to_string(),
+            version_range: Some("1.19.3 || 1.19.4".to_string()),
+            kind: DependencyKind::Required,
+        }));
+        assert!(metadata.dependencies.contains(&ModDependency {
+            id: "optifabric".to_string(),
+            version_range: Some("*".to_string()),
+            kind: DependencyKind::Incompatible,
+        }));
+        assert!(parse_fabric_mod_json(r#"{ "name": "No id" }"#).is_err());
     }
 
-    async fn unload(&mut self, _resource: &str) -> Result<(), Error> {
-        todo!()
+    #[test]
+    fn test_parse_mods_toml() {
+        let metadata = parse_mods_toml(
+            r#"
+modLoader="javafml" # the loader
+loaderVersion="[40,)"
+license='MIT'
+[[mods]]
+modId="jei"
+version="${file.jarVersion}"
+displayName="Just Enough Items"
+authors="mezz, Bernie"
+description='''
+Item and recipe viewing mod.
+[[dependencies.fake]] is not a table
+'''
+logoFile = "logo.png"
+modproperties = { catalogue = { icon = "icon.png" } }
+[[dependencies.jei]]
+    modId="forge"
+    mandatory=true
+    versionRange="[40.1.0,)"
+    ordering="NONE"
+    side="BOTH"
+[[dependencies."jei"]]
+    modId="optifine"
+    type="incompatible"
+"#,
+            Some("10.2.1.1002"),
+        )
+        .unwrap();
+        assert_eq!(
+            metadata,
+            vec![ModMetadata {
+                loader: ModLoader::Forge,
+                id: "jei".to_string(),
+                name: "Just Enough Items".to_string(),
+                version: Some("10.2.1.1002".to_string()),
+                authors: vec!["mezz".to_string(), "Bernie".to_string()],
+                dependencies: vec![
+                    ModDependency {
+                        id: "forge".to_string(),
+                        version_range: Some("[40.1.0,)".to_string()),
+                        kind: DependencyKind::Required,
+                    },
+                    ModDependency {
+                        id: "optifine".to_string(),
+                        version_range: None,
+                        kind: DependencyKind::Incompatible,
+                    },
+                ],
+                provides: Vec::new(),
+            }]
+        );
+        assert!(parse_mods_toml("[[mods]]\nmodId=", None).is_err());
     }
 
-    async fn delete(&mut self, _resource: &str) -> Result<(), Error> {
-        todo!()
+    #[test]
+    fn test_parse_plugin_yml() {
+        let metadata = parse_plugin_yml(
+            r#"
+name: WorldEdit
+main: com.sk89q.worldedit.bukkit.WorldEditPlugin
+version: "7.2.14"
+author: sk89q
+authors:
+  - wizjany
+  - me4502
+softdepend: [Vault, 'PlaceholderAPI']
+depend:
+- ProtocolLib
+description: >
+  In-game map editor
+  with: colons
+commands:
+  worldedit:
+    description: WorldEdit commands
+    aliases: [we]
+"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.loader, ModLoader::Bukkit);
+        assert_eq!(metadata.id, "WorldEdit");
+        assert_eq!(metadata.version.as_deref(), Some("7.2.14"));
+        assert_eq!(metadata.authors, vec!["sk89q", "wizjany", "me4502"]);
+        assert_eq!(
+            metadata.dependencies,
+            vec![
+                ModDependency {
+                    id: "ProtocolLib".to_string(),
+                    version_range: None,
+                    kind: DependencyKind::Required,
+                },
+                ModDependency {
+                    id: "Vault".to_string(),
+                    version_range: None,
+                    kind: DependencyKind::Optional,
+                },
+                ModDependency {
+                    id: "PlaceholderAPI".to_string(),
+                    version_range: None,
+                    kind: DependencyKind::Optional,
+                },
+            ]
+        );
+        assert_eq!(
+            parse_plugin_yml("name: Legacy\nversion: 1.5")
+                .unwrap()
+                .version
+                .as_deref(),
+            Some("1.5")
+        );
+        assert!(parse_plugin_yml("version: 1.0").is_err());
     }
 }
@@ -1,37 +1,83 @@
 use std::path::PathBuf;
 use std::process::Stdio;
-use std::time::Duration;
+use std::sync::atomic;
+use std::time::{Duration, Instant, SystemTime};
 
 use color_eyre::eyre::{eyre, Context};
-use sysinfo::{Pid, PidExt, ProcessExt, SystemExt};
+use sysinfo::{Pid, PidExt, ProcessExt, Signal, SystemExt};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::Command;
+use tokio::sync::broadcast;
 
 use crate::error::{Error, ErrorKind};
 use crate::events::{CausedBy, Event, EventInner, InstanceEvent, InstanceEventInner};
 use crate::implementations::minecraft::line_parser::{
-    parse_player_joined, parse_player_left, parse_player_msg, parse_server_started,
-    parse_system_msg, PlayerMessage,
+    split_log_line, LineEvent, Problem, ProblemGrouper, ProblemLevel,
 };
 use crate::implementations::minecraft::player::MinecraftPlayer;
-use crate::implementations::minecraft::util::name_to_uuid;
-use crate::macro_executor::SpawnResult;
 use crate::traits::t_configurable::TConfigurable;
-use crate::traits::t_macro::TaskEntry;
 use crate::traits::t_server::{MonitorReport, State, StateAction, TServer};
 
-use crate::types::Snowflake;
+use crate::types::{InstanceUuid, Snowflake};
 use crate::util::{dont_spawn_terminal, list_dir};
 
-use super::r#macro::{resolve_macro_invocation, MinecraftMainWorkerGenerator};
+use super::command::is_stop_command;
 use super::{Flavour, ForgeBuildVersion, MinecraftInstance};
 use tracing::{error, info, warn};
 
+/// Crashes older than this no longer count towards giving up on restarting
+const CRASH_WINDOW: Duration = Duration::from_secs(10 * 60);
+const MAX_CRASHES_IN_WINDOW: u32 = 5;
+const CRASH_RESTART_BASE_DELAY: Duration = Duration::from_secs(5);
+/// How long to wait for the server to exit after SIGTERM before killing it
+const SIGTERM_TIMEOUT: Duration = Duration::from_secs(10);
+/// A warning or error is reported once the console has been quiet for this long,
+/// unless an unrelated line ends its stack trace earlier
+const PROBLEM_GROUPING_DELAY: Duration = Duration::from_millis(500);
+
+pub fn default_stop_timeout() -> u32 {
+    60
+}
+
+/// The last step taken to stop the server process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum StopEscalation {
+    Command,
+    Sigterm,
+    Kill,
+}
+
+/// Wait until the instance moves to `State::Stopped` or `State::Error`
+async fn wait_for_exit(rx: &mut broadcast::Receiver<Event>, uuid: &InstanceUuid) {
+    loop {
+        match rx.recv().await {
+            Ok(Event {
+                event_inner:
+                    EventInner::InstanceEvent(InstanceEvent {
+                        instance_uuid,
+                        instance_event_inner: InstanceEventInner::StateTransition { to },
+                        ..
+                    }),
+                ..
+            }) if &instance_uuid == uuid && (to == State::Stopped || to == State::Error) => return,
+            Err(broadcast::error::RecvError::Closed) => return,
+            _ => {}
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl TServer for MinecraftInstance {
     async fn start(&mut self, cause_by: CausedBy, block: bool) -> Result<(), Error> {
         let config = self.config.lock().await.clone();
-        self.state.lock().await.try_transition(
+        let mut state = self.state.lock().await;
+        if self.restoring_backup.load(atomic::Ordering::SeqCst) {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Instance can't be started while a backup is being restored"),
+            });
+        }
+        state.try_transition(
             StateAction::UserStart,
             Some(&|state| {
                 self.event_broadcaster.send(Event {
@@ -46,6 +92,7 @@ impl TServer for MinecraftInstance {
                 });
             }),
         )?;
+        drop(state);
 
         if !port_scanner::local_port_available(config.port as u16) {
             return Err(Error {
@@ -54,77 +101,15 @@ impl TServer for MinecraftInstance {
             });
         }
 
-        let prelaunch = resolve_macro_invocation(&self.path_to_instance, "prelaunch");
-        if let Some(prelaunch) = prelaunch {
-            // read prelaunch script
-            let content = std::fs::read_to_string(&prelaunch)
-                .map_err(|e| {
-                    error!("Failed to read prelaunch script: {}", e);
-                    e
-                })
-                .unwrap_or_default();
-
-            let is_long_running = content.contains("LODESTONE_LONG_RUNNING_MACRO");
-
-            let main_worker_generator = MinecraftMainWorkerGenerator::new(self.clone());
-            let res = self
-                .macro_executor
-                .spawn(
-                    prelaunch,
-                    Vec::new(),
-                    CausedBy::System,
-                    Box::new(main_worker_generator),
-                    None,
-                    Some(self.uuid.clone()),
-                    if is_long_running {
-                        None
-                    } else {
-                        Some(Duration::from_secs(5))
-                    },
-                )
-                .await;
-
-            if let Ok(SpawnResult {
-                macro_pid: pid,
-                exit_future,
-                ..
-            }) = res
-            {
-                self.pid_to_task_entry.lock().await.insert(
-                    pid,
-                    TaskEntry {
-                        pid,
-                        name: "prelaunch".to_string(),
-                        creation_time: chrono::Utc::now().timestamp(),
-                    },
-                );
-                if !is_long_running {
-                    info!(
-                        "[{}] Waiting for prelaunch script to finish (5 seconds timeout)",
-                        config.name.clone()
-                    );
-                    if exit_future.await.is_err() {
-                        // kill the prelaunch script
-                        info!(
-                            "[{}] prelaunch script timed out, killing it",
-                            config.name.clone()
-                        );
-                        let _ = self.macro_executor.abort_macro(pid);
-                    }
-                } else {
-                    info!(
-                        "[{}] Long running prelaunch script detected, skipping wait",
-                        config.name.clone()
-                    );
-                }
-            }
-        } else {
-            info!(
-                "[{}] No prelaunch script found, skipping",
-                config.name.clone()
-            );
+        if config.check_mods_on_start {
+            self.warn_about_mod_issues().await;
         }
 
+        self.run_lifecycle_macro("prelaunch").await;
+
+        // pick up players the server has cached since the last start
+        self.load_user_cache().await;
+
         let jre = if let Some(jre) = &config.java_cmd {
             PathBuf::from(jre)
         } else {
@@ -237,6 +222,10 @@ impl TServer for MinecraftInstance {
             .spawn()
         {
             Ok(mut proc) => {
+                self.process_started_at
+                    .lock()
+                    .await
+                    .replace(SystemTime::now());
                 let stdin = proc.stdin.take().ok_or_else(|| {
                     error!(
                         "[{}] Failed to take stdin during startup",
@@ -265,39 +254,47 @@ impl TServer for MinecraftInstance {
                     let uuid = self.uuid.clone();
                     let name = config.name.clone();
                     let players_manager = self.players_manager.clone();
+                    let user_cache = self.user_cache.clone();
                     let mut __self = self.clone();
                     async move {
                         let mut did_start = false;
+                        let mut problem_grouper = ProblemGrouper::default();
+                        // re-armed with every line, fires once the console goes quiet
+                        let problem_flush_timer = tokio::time::sleep(PROBLEM_GROUPING_DELAY);
+                        tokio::pin!(problem_flush_timer);
 
                         let mut stdout_reader = BufReader::new(stdout);
                         let mut stderr_reader = BufReader::new(stderr);
+                        // read_until keeps a partial line in its buffer when the other branch
+                        // wins, so the buffers have to outlive each select
+                        let mut stdout_line = Vec::new();
+                        let mut stderr_line = Vec::new();
 
                         loop {
-                            let (line_res, is_stdout) = tokio::select!(
-                                line_res = async {
-                                    let mut line = Vec::new();
-                                    match stdout_reader.read_until(b'\n', &mut line).await {
-                                        Ok(0) => return Ok(None),
-                                        Err(e) => return Err(e),
-                                        Ok(_) => {}
-
-                                    };
-                                    Ok(Some(line))
-                                } => {
-                                    (line_res, true)
+                            let (read_res, is_stdout) = tokio::select!(
+                                read_res = stdout_reader.read_until(b'\n', &mut stdout_line) => {
+                                    (read_res, true)
                                 },
-                                line_res = async {
-                                    let mut line = Vec::new();
-                                    match stderr_reader.read_until(b'\n', &mut line).await {
-                                        Ok(0) => return Ok(None),
-                                        Err(e) => return Err(e),
-                                        Ok(_) => {}
-                                    };
-                                    Ok(Some(line))
-                                } => {
-                                    (line_res, false)
+                                read_res = stderr_reader.read_until(b'\n', &mut stderr_line) => {
+                                    (read_res, false)
+                                },
+                                () = &mut problem_flush_timer, if problem_grouper.has_pending() => {
+                                    if let Some(problem) = problem_grouper.flush() {
+                                        self.send_problem(problem).await;
+                                    }
+                                    continue;
                                 }
                             );
+                            let line_buf = if is_stdout {
+                                &mut stdout_line
+                            } else {
+                                &mut stderr_line
+                            };
+                            let line_res = match read_res {
+                                Ok(0) if line_buf.is_empty() => Ok(None),
+                                Ok(_) => Ok(Some(std::mem::take(line_buf))),
+                                Err(e) => Err(e),
+                            };
                             let _ = line_res.as_ref().map_err(|e| {
                                 error!("[{}] Failed to read from stdout/stderr: {}", name, e);
                             });
@@ -323,7 +320,33 @@ impl TServer for MinecraftInstance {
                                         caused_by: CausedBy::System,
                                     });
 
-                                    if parse_server_started(&line) && !did_start {
+                                    let line_event = self.line_parser.lock().await.parse(&line);
+
+                                    let problem = problem_grouper.push(
+                                        &line,
+                                        line_event.as_ref().and_then(LineEvent::problem_level),
+                                    );
+                                    if let Some(problem) = problem {
+                                        self.send_problem(problem).await;
+                                    }
+                                    if problem_grouper.has_pending() {
+                                        problem_flush_timer.as_mut().reset(
+                                            tokio::time::Instant::now() + PROBLEM_GROUPING_DELAY,
+                                        );
+                                    }
+
+                                    if let Some(LineEvent::LagWarning { message }) = &line_event {
+                                        self.tick_monitor
+                                            .lock()
+                                            .await
+                                            .record_lag_warning(message, Instant::now());
+                                    }
+
+                                    if line_event == Some(LineEvent::WorldSaved) {
+                                        let _ = self.world_saved.send(());
+                                    }
+
+                                    if line_event == Some(LineEvent::ServerStarted) && !did_start {
                                         did_start = true;
                                         self.state
                                             .lock()
@@ -349,56 +372,21 @@ impl TServer for MinecraftInstance {
                                                 }),
                                             )
                                             .unwrap();
+                                        self.spawn_server_list_ping_task().await;
+                                        self.spawn_tick_monitor_task().await;
 
-                                        if let (Some(true), Some(rcon_psw), Some(rcon_port)) = {
-                                            let lock = self.configurable_manifest.lock().await;
-
-                                            let a = lock
-                                                .get_unique_setting_key("enable-rcon")
-                                                .and_then(|v| {
-                                                    v.get_value().map(|v| v.try_as_boolean().ok())
-                                                })
-                                                .flatten();
-
-                                            let b = lock
-                                                .get_unique_setting_key("rcon.password")
-                                                .and_then(|v| {
-                                                    v.get_value().map(|v| v.try_as_string().ok())
-                                                })
-                                                .flatten()
-                                                .cloned();
-
-                                            let c = lock
-                                                .get_unique_setting_key("rcon.port")
-                                                .and_then(|v| {
-                                                    v.get_value()
-                                                        .map(|v| v.try_as_unsigned_integer().ok())
-                                                })
-                                                .flatten();
-                                            (a, b, c)
-                                        } {
+                                        if self.rcon_settings().await.is_some() {
                                             let max_retry = 3;
                                             for i in 0..max_retry {
-                                                let rcon =
-                                                <rcon::Connection<tokio::net::TcpStream>>::builder(
-                                                )
-                                                .enable_minecraft_quirks(true)
-                                                .connect(
-                                                    &format!("localhost:{}", rcon_port),
-                                                    &rcon_psw,
-                                                )
-                                                .await
-                                                .map_err(|e| {
-                                                    warn!(
-                                                    "Failed to connect to RCON: {}, retry {}/{}",
-                                                    e, i, max_retry
-                                                );
-                                                    e
-                                                });
-                                                if let Ok(rcon) = rcon {
-                                                    info!("Connected to RCON");
-                                                    self.rcon_conn.lock().await.replace(rcon);
-                                                    break;
+                                                match self.connect_rcon().await {
+                                                    Ok(()) => {
+                                                        info!("Connected to RCON");
+                                                        break;
+                                                    }
+                                                    Err(e) => warn!(
+                                                        "Failed to connect to RCON: {}, retry {}/{}",
+                                                        e, i, max_retry
+                                                    ),
                                                 }
                                                 tokio::time::sleep(Duration::from_secs(
                                                     2_u64.pow(i),
@@ -409,8 +397,18 @@ impl TServer for MinecraftInstance {
                                             warn!("RCON is not enabled or misconfigured, skipping");
                                             self.rcon_conn.lock().await.take();
                                         }
+                                        // don't hold up reading the server output
+                                        tokio::task::spawn({
+                                            let instance = self.clone();
+                                            async move {
+                                                instance.run_lifecycle_macro("poststart").await
+                                            }
+                                        });
                                     }
-                                    if let Some(system_msg) = parse_system_msg(&line) {
+                                    // chat is reported as a player message instead
+                                    if split_log_line(&line).is_some()
+                                        && !matches!(line_event, Some(LineEvent::Chat { .. }))
+                                    {
                                         let _ = event_broadcaster.send(Event {
                                             event_inner: EventInner::InstanceEvent(InstanceEvent {
                                                 instance_uuid: uuid.clone(),
@@ -424,34 +422,65 @@ impl TServer for MinecraftInstance {
                                             snowflake: Snowflake::default(),
                                             caused_by: CausedBy::System,
                                         });
-                                        if let Some(player_name) = parse_player_joined(&system_msg)
-                                        {
+                                    }
+                                    let instance_event_inner = match line_event {
+                                        Some(LineEvent::PlayerUuid {
+                                            player,
+                                            uuid: player_uuid,
+                                        }) => {
+                                            user_cache.lock().await.insert(&player, &player_uuid);
+                                            None
+                                        }
+                                        Some(LineEvent::PlayerJoined { player }) => {
+                                            let player_uuid =
+                                                user_cache.lock().await.uuid_of(&player);
                                             players_manager.lock().await.add_player(
                                                 MinecraftPlayer {
-                                                    name: player_name.clone(),
-                                                    uuid: name_to_uuid(&player_name).await,
+                                                    name: player,
+                                                    uuid: player_uuid,
                                                 },
                                                 self.name().await,
                                             );
-                                        } else if let Some(player_name) =
-                                            parse_player_left(&system_msg)
-                                        {
+                                            None
+                                        }
+                                        Some(LineEvent::PlayerLeft { player }) => {
                                             players_manager
                                                 .lock()
                                                 .await
-                                                .remove_by_name(&player_name, self.name().await);
+                                                .remove_by_name(&player, self.name().await);
+                                            None
                                         }
-                                    } else if let Some(PlayerMessage { player, message }) =
-                                        parse_player_msg(&line)
-                                    {
+                                        Some(LineEvent::Chat { player, message }) => {
+                                            Some(InstanceEventInner::PlayerMessage {
+                                                player,
+                                                player_message: message,
+                                            })
+                                        }
+                                        Some(LineEvent::Death { player, message }) => {
+                                            Some(InstanceEventInner::PlayerDeath {
+                                                player,
+                                                message,
+                                            })
+                                        }
+                                        Some(LineEvent::Advancement {
+                                            player,
+                                            advancement,
+                                        }) => Some(InstanceEventInner::PlayerAdvancement {
+                                            player,
+                                            advancement,
+                                        }),
+                                        // reported with their log level and stack trace above
+                                        Some(LineEvent::LagWarning { .. })
+                                        | Some(LineEvent::Exception { .. })
+                                        | Some(LineEvent::ServerStarted)
+                                        | Some(LineEvent::WorldSaved)
+                                        | None => None,
+                                    };
+                                    if let Some(instance_event_inner) = instance_event_inner {
                                         let _ = event_broadcaster.send(Event {
                                             event_inner: EventInner::InstanceEvent(InstanceEvent {
                                                 instance_uuid: uuid.clone(),
-                                                instance_event_inner:
-                                                    InstanceEventInner::PlayerMessage {
-                                                        player,
-                                                        player_message: message,
-                                                    },
+                                                instance_event_inner,
                                                 instance_name: name.clone(),
                                             }),
                                             details: "".to_string(),
@@ -464,29 +493,11 @@ impl TServer for MinecraftInstance {
                                 }
                             }
                         }
+                        if let Some(problem) = problem_grouper.flush() {
+                            self.send_problem(problem).await;
+                        }
                         info!("Instance {} process shutdown", name);
-                        self.state
-                            .lock()
-                            .await
-                            .try_transition(
-                                StateAction::InstanceStop,
-                                Some(&|state| {
-                                    self.event_broadcaster.send(Event {
-                                        event_inner: EventInner::InstanceEvent(InstanceEvent {
-                                            instance_name: config.name.clone(),
-                                            instance_uuid: self.uuid.clone(),
-                                            instance_event_inner:
-                                                InstanceEventInner::StateTransition { to: state },
-                                        }),
-                                        snowflake: Snowflake::default(),
-                                        details: "Instance stopping as server process exited"
-                                            .to_string(),
-                                        caused_by: cause_by.clone(),
-                                    });
-                                }),
-                            )
-                            .unwrap();
-                        self.players_manager.lock().await.clear(name);
+                        self.on_process_exit(cause_by).await;
                     }
                 });
                 self.config.lock().await.has_started = true;
@@ -505,7 +516,7 @@ impl TServer for MinecraftInstance {
                             if instance_uuid == event_instance_uuid {
                                 if to == State::Running {
                                     return Ok(()); // Instance started successfully
-                                } else if to == State::Stopped {
+                                } else if to == State::Stopped || to == State::Error {
                                     return Err(eyre!(
                                         "Instance exited unexpectedly before starting"
                                     )
@@ -550,7 +561,9 @@ impl TServer for MinecraftInstance {
     async fn stop(&mut self, cause_by: CausedBy, block: bool) -> Result<(), Error> {
         let config = self.config.lock().await.clone();
 
-        self.state.lock().await.try_transition(
+        let mut state_lock = self.state.lock().await;
+        let crashed = *state_lock == State::Error;
+        state_lock.try_transition(
             StateAction::UserStop,
             Some(&|state| {
                 self.event_broadcaster.send(Event {
@@ -560,30 +573,50 @@ impl TServer for MinecraftInstance {
                         instance_event_inner: InstanceEventInner::StateTransition { to: state },
                     }),
                     snowflake: Snowflake::default(),
-                    details: "Stopping server".to_string(),
+                    details: if crashed {
+                        "Clearing crashed state".to_string()
+                    } else {
+                        "Stopping server".to_string()
+                    },
                     caused_by: cause_by.clone(),
                 });
             }),
         )?;
+        drop(state_lock);
+        if crashed {
+            // the process already exited, there is nothing left to stop
+            return Ok(());
+        }
+        self.run_lifecycle_macro("prestop").await;
         let name = config.name.clone();
-        let _uuid = self.uuid.clone();
-        self.stdin
+        *self.stop_escalation.lock().await = StopEscalation::Command;
+        // subscribe before sending the command so a quick exit can't be missed
+        let watchdog_rx = self.event_broadcaster.subscribe();
+        let mut rx = self.event_broadcaster.subscribe();
+        let grace_period = match self
+            .stdin
             .lock()
             .await
             .as_mut()
-            .ok_or_else(|| {
-                error!("[{}] Failed to stop instance: stdin not available", name);
-                eyre!("Failed to stop instance: stdin not available")
-            })?
-            .write_all(b"stop\n")
-            .await
-            .context("Failed to write to stdin")
-            .map_err(|e| {
-                error!("[{}] Failed to stop instance: {}", name, e);
-                e
-            })?;
+            .ok_or_else(|| eyre!("stdin not available"))
+        {
+            Ok(stdin) => match stdin.write_all(b"stop\n").await {
+                Ok(_) => Duration::from_secs(config.stop_timeout as u64),
+                Err(e) => {
+                    error!("[{}] Failed to send stop command: {}", name, e);
+                    Duration::ZERO
+                }
+            },
+            Err(e) => {
+                error!("[{}] Failed to send stop command: {}", name, e);
+                Duration::ZERO
+            }
+        };
         self.rcon_conn.lock().await.take();
-        let mut rx = self.event_broadcaster.subscribe();
+        tokio::task::spawn({
+            let __self = self.clone();
+            async move { self.escalate_stop(watchdog_rx, grace_period).await }
+        });
         let instance_uuid = self.uuid.clone();
 
         if block {
@@ -594,7 +627,9 @@ impl TServer for MinecraftInstance {
                     ..
                 }) = event.event_inner
                 {
-                    if instance_uuid == event_instance_uuid && to == State::Stopped {
+                    if instance_uuid == event_instance_uuid
+                        && (to == State::Stopped || to == State::Error)
+                    {
                         return Ok(());
                     }
                 }
@@ -624,13 +659,33 @@ impl TServer for MinecraftInstance {
         }
     }
 
-    async fn kill(&mut self, _cause_by: CausedBy) -> Result<(), Error> {
+    async fn kill(&mut self, cause_by: CausedBy) -> Result<(), Error> {
         let config = self.config.lock().await.clone();
 
-        if self.state().await == State::Stopped {
-            warn!("[{}] Instance is already stopped", config.name.clone());
-            return Err(eyre!("Instance is already stopped").into());
-        }
+        // move to Stopping first so the exit isn't mistaken for a crash
+        self.state
+            .lock()
+            .await
+            .try_transition(
+                StateAction::UserKill,
+                Some(&|state| {
+                    self.event_broadcaster.send(Event {
+                        event_inner: EventInner::InstanceEvent(InstanceEvent {
+                            instance_name: config.name.clone(),
+                            instance_uuid: self.uuid.clone(),
+                            instance_event_inner: InstanceEventInner::StateTransition { to: state },
+                        }),
+                        snowflake: Snowflake::default(),
+                        details: "Killing server".to_string(),
+                        caused_by: cause_by.clone(),
+                    });
+                }),
+            )
+            .map_err(|e| {
+                warn!("[{}] Failed to kill instance: {}", config.name.clone(), e);
+                e
+            })?;
+        *self.stop_escalation.lock().await = StopEscalation::Kill;
         self.process
             .lock()
             .await
@@ -663,7 +718,7 @@ impl TServer for MinecraftInstance {
         } else {
             match self.stdin.lock().await.as_mut() {
                 Some(stdin) => match {
-                    if command == "stop" {
+                    if is_stop_command(command) {
                         self.state.lock().await.try_new_state(
                             StateAction::UserStop,
                             Some(&|state| {
@@ -722,6 +777,7 @@ impl TServer for MinecraftInstance {
                     disk_usage: Some(disk_usage.into()),
                     cpu_usage: Some(cpu_usage),
                     start_time: Some(start_time),
+                    tick: Some(self.tick_monitor.lock().await.report(Instant::now())),
                 }
             } else {
                 MonitorReport::default()
@@ -731,3 +787,264 @@ impl TServer for MinecraftInstance {
         }
     }
 }
+
+impl MinecraftInstance {
+    /// Called by the output reader once the server process closed its stdout.
+    ///
+    /// An exit that wasn't requested by a stop or kill and didn't end with a success status
+    /// is treated as a crash: the instance moves to `State::Error` and, if `restart_on_crash`
+    /// is set, is restarted with exponential backoff until it crashes too often.
+    async fn on_process_exit(&mut self, cause_by: CausedBy) {
+        let name = self.config.lock().await.name.clone();
+        let exit_status = match self.process.lock().await.as_mut() {
+            Some(proc) => tokio::time::timeout(Duration::from_secs(5), proc.wait())
+                .await
+                .ok()
+                .and_then(|status| status.ok()),
+            None => None,
+        };
+        for task in self.status_tasks.lock().await.drain(..) {
+            task.abort();
+            // wait for it to wind down so it can't repopulate what is cleared below
+            let _ = task.await;
+        }
+        self.server_status.lock().await.take();
+        self.query_stat.lock().await.take();
+        self.tick_monitor.lock().await.clear();
+        self.write_pending_ban_expiries().await;
+        let stop_requested = *self.state.lock().await == State::Stopping;
+        let crashed = !stop_requested && !exit_status.map(|s| s.success()).unwrap_or(false);
+
+        if !crashed {
+            let stop_details = match *self.stop_escalation.lock().await {
+                StopEscalation::Command => "Instance stopping as server process exited",
+                StopEscalation::Sigterm => {
+                    "Instance stopping as server process exited after receiving SIGTERM"
+                }
+                StopEscalation::Kill => "Instance stopping as server process was killed",
+            };
+            self.state
+                .lock()
+                .await
+                .try_transition(
+                    StateAction::InstanceStop,
+                    Some(&|state| {
+                        self.event_broadcaster.send(Event {
+                            event_inner: EventInner::InstanceEvent(InstanceEvent {
+                                instance_name: name.clone(),
+                                instance_uuid: self.uuid.clone(),
+                                instance_event_inner: InstanceEventInner::StateTransition {
+                                    to: state,
+                                },
+                            }),
+                            snowflake: Snowflake::default(),
+                            details: stop_details.to_string(),
+                            caused_by: cause_by.clone(),
+                        });
+                    }),
+                )
+                .unwrap();
+            self.players_manager.lock().await.clear(name);
+            self.run_lifecycle_macro("poststop").await;
+            return;
+        }
+
+        let crash_message = match exit_status {
+            Some(status) => format!("Server process exited unexpectedly ({status})"),
+            None => "Server process exited unexpectedly".to_string(),
+        };
+        error!("[{}] {}", name, crash_message);
+        let caused_by = CausedBy::Instance {
+            instance_uuid: self.uuid.clone(),
+        };
+        self.state
+            .lock()
+            .await
+            .try_transition(
+                StateAction::InstanceCrash,
+                Some(&|state| {
+                    self.event_broadcaster.send(Event {
+                        event_inner: EventInner::InstanceEvent(InstanceEvent {
+                            instance_name: name.clone(),
+                            instance_uuid: self.uuid.clone(),
+                            instance_event_inner: InstanceEventInner::StateTransition { to: state },
+                        }),
+                        snowflake: Snowflake::default(),
+                        details: crash_message.clone(),
+                        caused_by: caused_by.clone(),
+                    });
+                }),
+            )
+            .unwrap();
+        self.players_manager.lock().await.clear(name.clone());
+        let process_started_at = *self.process_started_at.lock().await;
+        let crash_reports = match process_started_at {
+            Some(process_started_at) => self.crash_reports_since(process_started_at).await,
+            None => Vec::new(),
+        };
+        let mut error_message = crash_message.clone();
+        for crash_report in crash_reports {
+            error!("[{}] {}", name, crash_report);
+            error_message.push_str(&format!("\n{}", crash_report));
+        }
+        self.send_instance_event(
+            InstanceEventInner::InstanceError {
+                message: error_message,
+            },
+            caused_by.clone(),
+        )
+        .await;
+        self.run_lifecycle_macro("oncrash").await;
+
+        if !self.restart_on_crash.load(atomic::Ordering::Relaxed) {
+            return;
+        }
+        let crash_count = {
+            let mut recent_crashes = self.recent_crashes.lock().await;
+            let now = Instant::now();
+            recent_crashes.retain(|t| now.duration_since(*t) < CRASH_WINDOW);
+            recent_crashes.push_back(now);
+            recent_crashes.len() as u32
+        };
+        if crash_count >= MAX_CRASHES_IN_WINDOW {
+            self.send_instance_event(
+                InstanceEventInner::InstanceError {
+                    message: format!(
+                        "Instance crashed {} times within {} minutes, giving up on restarting it",
+                        crash_count,
+                        CRASH_WINDOW.as_secs() / 60
+                    ),
+                },
+                caused_by,
+            )
+            .await;
+            return;
+        }
+        let backoff = CRASH_RESTART_BASE_DELAY * 2_u32.pow(crash_count - 1);
+        self.send_instance_event(
+            InstanceEventInner::InstanceWarning {
+                message: format!(
+                    "Restarting crashed instance in {} seconds (attempt {}/{})",
+                    backoff.as_secs(),
+                    crash_count,
+                    MAX_CRASHES_IN_WINDOW - 1
+                ),
+            },
+            caused_by.clone(),
+        )
+        .await;
+        tokio::time::sleep(backoff).await;
+        // the user may have started the instance or cleared the error in the meantime
+        if self.state().await != State::Error {
+            return;
+        }
+        if let Err(e) = self.start(CausedBy::System, false).await {
+            self.send_instance_event(
+                InstanceEventInner::InstanceError {
+                    message: format!("Failed to restart crashed instance: {e}"),
+                },
+                caused_by,
+            )
+            .await;
+        }
+    }
+
+    /// Wait for the server to exit after a stop command, sending SIGTERM once the grace period
+    /// runs out and killing the process if that doesn't work either
+    async fn escalate_stop(&self, mut rx: broadcast::Receiver<Event>, grace_period: Duration) {
+        if tokio::time::timeout(grace_period, wait_for_exit(&mut rx, &self.uuid))
+            .await
+            .is_ok()
+        {
+            return;
+        }
+        // events may have been dropped if the receiver lagged behind
+        if self.state().await != State::Stopping {
+            return;
+        }
+
+        *self.stop_escalation.lock().await = StopEscalation::Sigterm;
+        self.send_instance_event(
+            InstanceEventInner::InstanceWarning {
+                message: format!(
+                    "Server did not stop within {} seconds, sending SIGTERM",
+                    grace_period.as_secs()
+                ),
+            },
+            CausedBy::System,
+        )
+        .await;
+        if self.send_sigterm().await
+            && tokio::time::timeout(SIGTERM_TIMEOUT, wait_for_exit(&mut rx, &self.uuid))
+                .await
+                .is_ok()
+        {
+            return;
+        }
+        if self.state().await != State::Stopping {
+            return;
+        }
+
+        *self.stop_escalation.lock().await = StopEscalation::Kill;
+        self.send_instance_event(
+            InstanceEventInner::InstanceWarning {
+                message: "Server did not respond to SIGTERM, killing it".to_string(),
+            },
+            CausedBy::System,
+        )
+        .await;
+        if let Some(proc) = self.process.lock().await.as_mut() {
+            if let Err(e) = proc.kill().await {
+                error!("Failed to kill instance {}: {}", self.uuid, e);
+            }
+        }
+    }
+
+    /// Returns false if the signal could not be sent, e.g. on platforms without SIGTERM
+    async fn send_sigterm(&self) -> bool {
+        let pid = match self.process.lock().await.as_ref().and_then(|p| p.id()) {
+            Some(pid) => Pid::from_u32(pid),
+            None => return false,
+        };
+        let mut sys = self.system.lock().await;
+        sys.refresh_process(pid);
+        sys.process(pid)
+            .and_then(|proc| proc.kill_with(Signal::Term))
+            .unwrap_or(false)
+    }
+
+    pub async fn set_stop_timeout(&self, stop_timeout: u32) -> Result<(), Error> {
+        self.config.lock().await.stop_timeout = stop_timeout;
+        self.write_config_to_file().await
+    }
+
+    async fn send_problem(&self, problem: Problem) {
+        let instance_event_inner = match problem.level {
+            ProblemLevel::Warning => InstanceEventInner::InstanceWarning {
+                message: problem.message,
+            },
+            ProblemLevel::Error => InstanceEventInner::InstanceError {
+                message: problem.message,
+            },
+        };
+        self.send_instance_event(instance_event_inner, CausedBy::System)
+            .await;
+    }
+
+    pub(super) async fn send_instance_event(
+        &self,
+        instance_event_inner: InstanceEventInner,
+        caused_by: CausedBy,
+    ) {
+        self.event_broadcaster.send(Event {
+            event_inner: EventInner::InstanceEvent(InstanceEvent {
+                instance_name: self.config.lock().await.name.clone(),
+                instance_uuid: self.uuid.clone(),
+                instance_event_inner,
+            }),
+            snowflake: Snowflake::default(),
+            details: "".to_string(),
+            caused_by,
+        });
+    }
+}
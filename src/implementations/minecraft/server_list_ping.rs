@@ -0,0 +1,396 @@
+//! Client for the Server List Ping protocol, the one the multiplayer menu uses
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::debug;
+use ts_rs::TS;
+
+use crate::error::Error;
+use crate::traits::t_server::State;
+
+use super::player::MinecraftPlayer;
+use super::MinecraftInstance;
+
+pub const SERVER_LIST_PING_INTERVAL: Duration = Duration::from_secs(10);
+const SERVER_LIST_PING_TIMEOUT: Duration = Duration::from_secs(5);
+/// -1 asks the server to report its own protocol version
+const STATUS_PROTOCOL_VERSION: i32 = -1;
+const MAX_PACKET_LENGTH: i32 = 1 << 21;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ServerStatus {
+    pub version: String,
+    pub protocol: i32,
+    pub online: u32,
+    pub max: u32,
+    /// The MOTD with formatting codes stripped
+    pub motd: String,
+    /// Only a sample of the online players, at most 12 on vanilla
+    pub sample: Vec<MinecraftPlayer>,
+    /// round trip time in milliseconds
+    pub latency: u32,
+}
+
+#[derive(Deserialize)]
+struct StatusVersion {
+    name: String,
+    protocol: i32,
+}
+
+#[derive(Deserialize)]
+struct StatusPlayer {
+    name: String,
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct StatusPlayers {
+    max: u32,
+    online: u32,
+    #[serde(default)]
+    sample: Vec<StatusPlayer>,
+}
+
+#[derive(Deserialize)]
+struct StatusResponse {
+    version: StatusVersion,
+    players: Option<StatusPlayers>,
+    #[serde(default)]
+    description: serde_json::Value,
+}
+
+fn write_var_int(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn read_var_int_from_slice(buf: &mut &[u8]) -> Result<i32, Error> {
+    let mut value: u32 = 0;
+    for i in 0..5 {
+        let (byte, rest) = buf
+            .split_first()
+            .ok_or_else(|| eyre!("Unexpected end of packet while reading a VarInt"))?;
+        *buf = rest;
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(eyre!("VarInt is too big").into())
+}
+
+async fn read_var_int(reader: &mut (impl AsyncRead + Unpin)) -> Result<i32, Error> {
+    let mut value: u32 = 0;
+    for i in 0..5 {
+        let byte = reader
+            .read_u8()
+            .await
+            .context("Failed to read from server")?;
+        value |= ((byte & 0x7F) as u32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(eyre!("VarInt is too big").into())
+}
+
+fn write_string(buf: &mut Vec<u8>, value: &str) {
+    write_var_int(buf, value.len() as i32);
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Prefix the packet id and data with their length
+fn packet(id: i32, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_var_int(&mut body, id);
+    body.extend_from_slice(data);
+    let mut packet = Vec::new();
+    write_var_int(&mut packet, body.len() as i32);
+    packet.extend_from_slice(&body);
+    packet
+}
+
+fn handshake_packet(host: &str, port: u16) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_var_int(&mut data, STATUS_PROTOCOL_VERSION);
+    write_string(&mut data, host);
+    data.extend_from_slice(&port.to_be_bytes());
+    // next state: status
+    write_var_int(&mut data, 1);
+    packet(0x00, &data)
+}
+
+/// Returns the packet id and the rest of the packet
+async fn read_packet(reader: &mut (impl AsyncRead + Unpin)) -> Result<(i32, Vec<u8>), Error> {
+    let length = read_var_int(reader).await?;
+    if !(1..=MAX_PACKET_LENGTH).contains(&length) {
+        return Err(eyre!("Invalid packet length {}", length).into());
+    }
+    let mut buf = vec![0; length as usize];
+    reader
+        .read_exact(&mut buf)
+        .await
+        .context("Failed to read from server")?;
+    let mut slice = buf.as_slice();
+    let id = read_var_int_from_slice(&mut slice)?;
+    Ok((id, slice.to_vec()))
+}
+
+/// The description is either a plain string or a chat component
+fn flatten_chat_component(component: &serde_json::Value) -> String {
+    match component {
+        serde_json::Value::String(text) => text.clone(),
+        serde_json::Value::Array(components) => {
+            components.iter().map(flatten_chat_component).collect()
+        }
+        serde_json::Value::Object(object) => {
+            let mut text = object
+                .get("text")
+                .and_then(|text| text.as_str())
+                .unwrap_or_default()
+                .to_string();
+            if let Some(extra) = object.get("extra") {
+                text.push_str(&flatten_chat_component(extra));
+            }
+            text
+        }
+        _ => String::new(),
+    }
+}
+
+pub(super) fn strip_formatting_codes(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            ret.push(c);
+        }
+    }
+    ret
+}
+
+pub fn parse_status_response(json: &str, latency: u32) -> Result<ServerStatus, Error> {
+    let response: StatusResponse =
+        serde_json::from_str(json).context("Failed to parse server list ping response")?;
+    let (online, max, sample) = match response.players {
+        Some(players) => (players.online, players.max, players.sample),
+        None => (0, 0, Vec::new()),
+    };
+    Ok(ServerStatus {
+        version: response.version.name,
+        protocol: response.version.protocol,
+        online,
+        max,
+        motd: strip_formatting_codes(&flatten_chat_component(&response.description)),
+        sample: sample
+            .into_iter()
+            .map(|player| MinecraftPlayer::new(player.name, Some(player.id)))
+            .filter(MinecraftPlayer::is_real_player)
+            .collect(),
+        latency,
+    })
+}
+
+async fn ping_stream(stream: &mut TcpStream, host: &str, port: u16) -> Result<ServerStatus, Error> {
+    let request_sent = Instant::now();
+    let mut request = handshake_packet(host, port);
+    request.extend(packet(0x00, &[]));
+    stream
+        .write_all(&request)
+        .await
+        .context("Failed to send status request")?;
+    let (id, data) = read_packet(stream).await?;
+    if id != 0x00 {
+        return Err(eyre!("Unexpected packet id {} in status response", id).into());
+    }
+    let mut status_rtt = request_sent.elapsed();
+    let mut data = data.as_slice();
+    let json_length = read_var_int_from_slice(&mut data)?;
+    let json = data
+        .get(..json_length.max(0) as usize)
+        .ok_or_else(|| eyre!("Status response is truncated"))?;
+    let json = std::str::from_utf8(json).context("Status response is not valid UTF-8")?;
+
+    // some servers close the connection right after the status, so the ping is best effort
+    let payload = request_sent.elapsed().as_millis() as i64;
+    let ping_sent = Instant::now();
+    if stream
+        .write_all(&packet(0x01, &payload.to_be_bytes()))
+        .await
+        .is_ok()
+    {
+        if let Ok((0x01, _)) = read_packet(stream).await {
+            status_rtt = ping_sent.elapsed();
+        }
+    }
+    parse_status_response(json, status_rtt.as_millis() as u32)
+}
+
+pub async fn ping(host: &str, port: u16) -> Result<ServerStatus, Error> {
+    tokio::time::timeout(SERVER_LIST_PING_TIMEOUT, async {
+        let mut stream = TcpStream::connect((host, port))
+            .await
+            .context(format!("Failed to connect to {}:{}", host, port))?;
+        ping_stream(&mut stream, host, port).await
+    })
+    .await
+    .context("Server list ping timed out")?
+}
+
+impl MinecraftInstance {
+    /// The status from the last successful ping, `None` when the server is not running
+    pub async fn get_server_status(&self) -> Option<ServerStatus> {
+        self.server_status.lock().await.clone()
+    }
+
+    async fn refresh_server_status(&self) -> Result<ServerStatus, Error> {
+        let port = self.config.lock().await.port as u16;
+        let status = ping("127.0.0.1", port).await?;
+        self.server_status.lock().await.replace(status.clone());
+        Ok(status)
+    }
+
+    /// Ping and query the server until it stops running,
+    /// the full player list from query is preferred over the sample from ping
+    pub(super) async fn spawn_server_list_ping_task(&self) {
+        let instance = self.clone();
+        let task = tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(SERVER_LIST_PING_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if *instance.state.lock().await != State::Running {
+                    break;
+                }
+                let status = instance
+                    .refresh_server_status()
+                    .await
+                    .map_err(|e| debug!("Server list ping failed: {}", e))
+                    .ok();
+                let query_stat = match instance.query_port().await {
+                    Some(port) => instance
+                        .refresh_query_stat(port)
+                        .await
+                        .map_err(|e| debug!("Query failed: {}", e))
+                        .ok(),
+                    None => None,
+                };
+                let (online, players) = match (query_stat, status) {
+                    (Some(query_stat), _) => {
+                        let user_cache = instance.user_cache.lock().await;
+                        let players: Vec<MinecraftPlayer> = query_stat
+                            .players
+                            .into_iter()
+                            .map(|name| {
+                                let uuid = user_cache.uuid_of(&name);
+                                MinecraftPlayer::new(name, uuid)
+                            })
+                            .collect();
+                        (query_stat.online, players)
+                    }
+                    (None, Some(status)) => (status.online, status.sample),
+                    (None, None) => continue,
+                };
+                let name = instance.config.lock().await.name.clone();
+                instance
+                    .players_manager
+                    .lock()
+                    .await
+                    .reconcile(online, &players, name);
+            }
+            instance.server_status.lock().await.take();
+            instance.query_stat.lock().await.take();
+        });
+        self.status_tasks.lock().await.push(task);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_var_int() {
+        for (value, bytes) in [
+            (0, vec![0x00]),
+            (1, vec![0x01]),
+            (127, vec![0x7f]),
+            (128, vec![0x80, 0x01]),
+            (25565, vec![0xdd, 0xc7, 0x01]),
+            (2147483647, vec![0xff, 0xff, 0xff, 0xff, 0x07]),
+            (-1, vec![0xff, 0xff, 0xff, 0xff, 0x0f]),
+        ] {
+            let mut buf = Vec::new();
+            write_var_int(&mut buf, value);
+            assert_eq!(buf, bytes);
+            assert_eq!(read_var_int_from_slice(&mut buf.as_slice()).unwrap(), value);
+        }
+        assert!(read_var_int_from_slice(&mut [0x80].as_slice()).is_err());
+        assert_eq!(
+            handshake_packet("localhost", 25565),
+            vec![
+                0x13, 0x00, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x09, b'l', b'o', b'c', b'a', b'l', b'h',
+                b'o', b's', b't', 0x63, 0xdd, 0x01
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_status_response() {
+        let status = parse_status_response(
+            r#"{
+                "version": {"name": "Paper 1.20.1", "protocol": 763},
+                "players": {
+                    "max": 20,
+                    "online": 3,
+                    "sample": [
+                        {"name": "Notch", "id": "069a79f4-44e9-4726-a5be-fca90e38aaf5"},
+                        {"name": "Anonymous Player", "id": "00000000-0000-0000-0000-000000000000"},
+                        {"name": "§6Join our Discord!", "id": "00000000-0000-0000-0000-000000000001"}
+                    ]
+                },
+                "description": {"text": "§aA ", "extra": [{"text": "Lodestone"}, " server"]},
+                "enforcesSecureChat": true
+            }"#,
+            12,
+        )
+        .unwrap();
+        assert_eq!(
+            status,
+            ServerStatus {
+                version: "Paper 1.20.1".to_string(),
+                protocol: 763,
+                online: 3,
+                max: 20,
+                motd: "A Lodestone server".to_string(),
+                sample: vec![MinecraftPlayer::new(
+                    "Notch".to_string(),
+                    Some("069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string())
+                )],
+                latency: 12,
+            }
+        );
+
+        let status = parse_status_response(
+            r#"{"version":{"name":"1.8.9","protocol":47},"players":{"max":10,"online":0},"description":"§4Old §lserver"}"#,
+            0,
+        )
+        .unwrap();
+        assert_eq!(status.motd, "Old server");
+        assert!(status.sample.is_empty());
+        assert!(parse_status_response("{}", 0).is_err());
+    }
+}
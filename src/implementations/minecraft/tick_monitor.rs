@@ -0,0 +1,237 @@
+//! Tick health from `tps`/`mspt` on Paper, `forge tps` on Forge and lag warnings everywhere
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::error::Error;
+use crate::traits::t_server::{State, TickReport};
+
+use super::server_list_ping::strip_formatting_codes;
+use super::{FlavourKind, MinecraftInstance};
+
+pub const TICK_POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// How far back "Can't keep up!" warnings are counted
+const LAG_WARNING_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Default)]
+pub struct TickMonitor {
+    tps: Option<f32>,
+    mspt: Option<f32>,
+    // when each warning was logged and how many ticks the server fell behind
+    lag_warnings: VecDeque<(Instant, u64)>,
+}
+
+impl TickMonitor {
+    pub fn record_lag_warning(&mut self, message: &str, now: Instant) {
+        self.lag_warnings
+            .push_back((now, ticks_behind(message).unwrap_or(0)));
+        self.prune(now);
+    }
+
+    pub fn set_tick_times(&mut self, tps: Option<f32>, mspt: Option<f32>) {
+        self.tps = tps;
+        self.mspt = mspt;
+    }
+
+    pub fn clear(&mut self) {
+        *self = TickMonitor::default();
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some((time, _)) = self.lag_warnings.front() {
+            if now.duration_since(*time) < LAG_WARNING_WINDOW {
+                break;
+            }
+            self.lag_warnings.pop_front();
+        }
+    }
+
+    pub fn report(&mut self, now: Instant) -> TickReport {
+        self.prune(now);
+        TickReport {
+            tps: self.tps,
+            mspt: self.mspt,
+            lag_warnings: self.lag_warnings.len() as u32,
+            ticks_behind: self.lag_warnings.iter().map(|(_, ticks)| ticks).sum(),
+        }
+    }
+}
+
+/// The leading number of `text`, ignoring a `*` Paper prints when the value is capped
+fn leading_number(text: &str) -> Option<f32> {
+    let text = text.trim_start().trim_start_matches('*');
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    text[..end].parse().ok()
+}
+
+/// "Can't keep up! Is the server overloaded? Running 2034ms or 40 ticks behind"
+pub fn ticks_behind(message: &str) -> Option<u64> {
+    let before = message.split(" ticks behind").next()?;
+    before.rsplit(' ').next()?.parse().ok()
+}
+
+/// "TPS from last 1m, 5m, 15m: 19.98, 20.0, *20.0", returns the 1 minute average
+pub fn parse_paper_tps(output: &str) -> Option<f32> {
+    let output = strip_formatting_codes(output);
+    let (_, values) = output.split_once("TPS from last")?.1.split_once(':')?;
+    leading_number(values)
+}
+
+/// "Server tick times (avg/min/max) from last 5s, 10s, 1m:" followed by
+/// "◴ 1.2/0.5/3.4, 1.3/0.5/4.1, 1.1/0.4/9.8", returns the 5 second average
+pub fn parse_paper_mspt(output: &str) -> Option<f32> {
+    let output = strip_formatting_codes(output);
+    let (_, values) = output.split_once("tick times")?;
+    let (_, values) = values.split_once(':')?;
+    leading_number(values.trim_start().trim_start_matches('◴'))
+}
+
+/// Older Forge prints "Overall : Mean tick time: 0.789 ms. Mean TPS: 20.000",
+/// newer versions "Overall: 20.000 TPS (0.789 ms/tick)". Returns the TPS and MSPT
+pub fn parse_forge_tps(output: &str) -> Option<(f32, f32)> {
+    let output = strip_formatting_codes(output);
+    let overall = output
+        .lines()
+        .find(|line| line.trim_start().starts_with("Overall"))?;
+    let (_, values) = overall.split_once(':')?;
+    if let (Some((_, mspt)), Some((_, tps))) = (
+        values.split_once("Mean tick time:"),
+        values.split_once("Mean TPS:"),
+    ) {
+        return Some((leading_number(tps)?, leading_number(mspt)?));
+    }
+    let tps = leading_number(values)?;
+    let (_, mspt) = values.split_once('(')?;
+    Some((tps, leading_number(mspt)?))
+}
+
+impl MinecraftInstance {
+    /// Ask the server for its tick times, this needs RCON as the output is the command's response
+    async fn poll_tick_times(&self) -> Result<(), Error> {
+        if self.rcon_settings().await.is_none() {
+            return Ok(());
+        }
+        let flavour = FlavourKind::from(&self.config.lock().await.flavour);
+        let (tps, mspt) = match flavour {
+            FlavourKind::Paper => (
+                parse_paper_tps(&self.send_rcon("tps").await?),
+                parse_paper_mspt(&self.send_rcon("mspt").await?),
+            ),
+            FlavourKind::Spigot => (parse_paper_tps(&self.send_rcon("tps").await?), None),
+            FlavourKind::Forge => match parse_forge_tps(&self.send_rcon("forge tps").await?) {
+                Some((tps, mspt)) => (Some(tps), Some(mspt)),
+                None => (None, None),
+            },
+            // vanilla and Fabric have no such command, only the lag warnings are counted
+            FlavourKind::Vanilla | FlavourKind::Fabric => return Ok(()),
+        };
+        self.tick_monitor.lock().await.set_tick_times(tps, mspt);
+        Ok(())
+    }
+
+    /// Poll the tick times until the server stops running
+    pub(super) async fn spawn_tick_monitor_task(&self) {
+        let instance = self.clone();
+        let task = tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(TICK_POLL_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                if *instance.state.lock().await != State::Running {
+                    break;
+                }
+                if let Err(e) = instance.poll_tick_times().await {
+                    debug!("Failed to poll tick times: {}", e);
+                }
+            }
+        });
+        self.status_tasks.lock().await.push(task);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tick_times() {
+        assert_eq!(
+            parse_paper_tps("§6TPS from last 1m, 5m, 15m: §a19.98, §a*20.0, §a*20.0"),
+            Some(19.98)
+        );
+        assert_eq!(
+            parse_paper_tps("§6TPS from last 1m, 5m, 15m: §a*20.0, §a*20.0, §a*20.0"),
+            Some(20.0)
+        );
+        assert_eq!(
+            parse_paper_mspt(
+                "§6Server tick times §e(§7avg§e/§7min§e/§7max§e)§6 from last 5s§7,§6 10s§7,§6 1m§e:\n§6◴ §a1.2§7/§a0.5§7/§a3.4§7, §a1.3§7/§a0.5§7/§a4.1§7, §a1.1§7/§a0.4§7/§a9.8"
+            ),
+            Some(1.2)
+        );
+        assert_eq!(
+            parse_forge_tps(
+                "Dim minecraft:overworld (minecraft:overworld): Mean tick time: 0.423 ms. Mean TPS: 20.000\nOverall : Mean tick time: 0.789 ms. Mean TPS: 20.000"
+            ),
+            Some((20.0, 0.789))
+        );
+        assert_eq!(
+            parse_forge_tps(
+                "minecraft:overworld: 20.000 TPS (0.423 ms/tick)\nOverall: 18.500 TPS (54.054 ms/tick)"
+            ),
+            Some((18.5, 54.054))
+        );
+        assert_eq!(parse_paper_tps("Unknown command"), None);
+        assert_eq!(parse_forge_tps("Unknown command"), None);
+    }
+
+    #[test]
+    fn test_tick_monitor() {
+        assert_eq!(
+            ticks_behind(
+                "Can't keep up! Is the server overloaded? Running 2034ms or 40 ticks behind"
+            ),
+            Some(40)
+        );
+        assert_eq!(
+            ticks_behind("Can't keep up! Is the server overloaded?"),
+            None
+        );
+
+        let mut tick_monitor = TickMonitor::default();
+        let start = Instant::now();
+        tick_monitor.record_lag_warning(
+            "Can't keep up! Is the server overloaded? Running 2034ms or 40 ticks behind",
+            start,
+        );
+        tick_monitor.record_lag_warning(
+            "Can't keep up! Is the server overloaded? Running 5000ms or 100 ticks behind",
+            start + Duration::from_secs(60),
+        );
+        tick_monitor.set_tick_times(Some(19.5), None);
+        assert_eq!(
+            tick_monitor.report(start + Duration::from_secs(120)),
+            TickReport {
+                tps: Some(19.5),
+                mspt: None,
+                lag_warnings: 2,
+                ticks_behind: 140,
+            }
+        );
+        // the first warning fell out of the window
+        assert_eq!(
+            tick_monitor
+                .report(start + LAG_WARNING_WINDOW + Duration::from_secs(1))
+                .ticks_behind,
+            100
+        );
+        tick_monitor.clear();
+        assert_eq!(
+            tick_monitor.report(start + Duration::from_secs(120)),
+            TickReport::default()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Name to UUID lookups that work without network access
+use std::{collections::HashMap, path::Path};
+
+use color_eyre::eyre::Context;
+use serde::Deserialize;
+
+use crate::error::Error;
+
+use super::access_list::hyphenate_uuid;
+
+/// The file the server itself keeps its name to UUID cache in
+pub const USER_CACHE_FILE_NAME: &str = "usercache.json";
+
+#[derive(Deserialize)]
+struct UserCacheEntry {
+    name: String,
+    uuid: String,
+}
+
+/// Names are case insensitive, UUIDs are stored hyphenated and lowercase
+#[derive(Debug, Clone, Default)]
+pub struct UserCache {
+    uuid_by_name: HashMap<String, String>,
+    name_by_uuid: HashMap<String, String>,
+}
+
+impl UserCache {
+    pub fn insert(&mut self, name: &str, uuid: &str) {
+        let uuid = hyphenate_uuid(&uuid.to_ascii_lowercase());
+        // a player that changed their name keeps their UUID
+        if let Some(old_name) = self.name_by_uuid.insert(uuid.clone(), name.to_string()) {
+            if !old_name.eq_ignore_ascii_case(name) {
+                self.uuid_by_name.remove(&old_name.to_ascii_lowercase());
+            }
+        }
+        if let Some(old_uuid) = self
+            .uuid_by_name
+            .insert(name.to_ascii_lowercase(), uuid.clone())
+        {
+            if old_uuid != uuid {
+                self.name_by_uuid.remove(&old_uuid);
+            }
+        }
+    }
+
+    pub fn uuid_of(&self, name: &str) -> Option<String> {
+        self.uuid_by_name.get(&name.to_ascii_lowercase()).cloned()
+    }
+
+    pub fn name_of(&self, uuid: &str) -> Option<String> {
+        self.name_by_uuid
+            .get(&hyphenate_uuid(&uuid.to_ascii_lowercase()))
+            .cloned()
+    }
+
+    /// Merge in the entries of a `usercache.json`, a missing file is not an error
+    pub async fn load_usercache(&mut self, path: &Path) -> Result<(), Error> {
+        if !tokio::fs::metadata(path)
+            .await
+            .map(|metadata| metadata.is_file())
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .context(format!("Failed to read {}", path.display()))?;
+        let entries: Vec<UserCacheEntry> = serde_json::from_str(&content)
+            .context(format!("Failed to parse {}", path.display()))?;
+        for entry in entries {
+            self.insert(&entry.name, &entry.uuid);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_user_cache() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(USER_CACHE_FILE_NAME);
+        std::fs::write(
+            &path,
+            r#"[{"name":"Notch","uuid":"069a79f4-44e9-4726-a5be-fca90e38aaf5","expiresOn":"2023-04-01 12:00:00 +0000"}]"#,
+        )
+        .unwrap();
+
+        let mut cache = UserCache::default();
+        cache
+            .load_usercache(&temp_dir.path().join("missing.json"))
+            .await
+            .unwrap();
+        cache.load_usercache(&path).await.unwrap();
+        assert_eq!(
+            cache.uuid_of("notch"),
+            Some("069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string())
+        );
+        assert_eq!(
+            cache.name_of("069A79F444E94726A5BEFCA90E38AAF5"),
+            Some("Notch".to_string())
+        );
+
+        cache.insert("Notch2", "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert_eq!(cache.uuid_of("Notch"), None);
+        assert_eq!(
+            cache.name_of("069a79f4-44e9-4726-a5be-fca90e38aaf5"),
+            Some("Notch2".to_string())
+        );
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(cache.load_usercache(&path).await.is_err());
+    }
+}
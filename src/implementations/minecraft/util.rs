@@ -1,7 +1,8 @@
 use color_eyre::eyre::{eyre, Context, ContextCompat};
 use indexmap::IndexMap;
+use md5::Digest;
 use serde_json::{self, Value};
-use std::{collections::BTreeMap, path::Path, str::FromStr};
+use std::{cmp::Ordering, collections::BTreeMap, path::Path, str::FromStr};
 use tokio::io::AsyncBufReadExt;
 
 use super::{
@@ -440,6 +441,39 @@ pub async fn name_to_uuid(name: impl AsRef<str>) -> Option<String> {
     Some(res["id"].as_str()?.to_owned())
 }
 
+/// The UUID an offline mode server gives a player, Java's
+/// `UUID.nameUUIDFromBytes("OfflinePlayer:" + name)`. Unlike `Uuid::new_v3`, Java hashes
+/// the name without a namespace
+pub fn offline_player_uuid(name: &str) -> String {
+    let mut bytes: [u8; 16] = md5::Md5::digest(format!("OfflinePlayer:{}", name)).into();
+    // version 3, IETF variant
+    bytes[6] = (bytes[6] & 0x0f) | 0x30;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    uuid::Uuid::from_bytes(bytes).hyphenated().to_string()
+}
+
+/// Numeric components of a version, e.g. `[1, 19, 2]` for `1.19.2-pre1` or `0.76.0+1.19.2`
+pub fn version_components(version: &str) -> Vec<u64> {
+    version
+        .trim()
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+pub fn compare_versions(a: &[u64], b: &[u64]) -> Ordering {
+    for i in 0..a.len().max(b.len()) {
+        let ordering = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
 #[cfg(test)]
 mod tests {
     use crate::minecraft::{
@@ -448,6 +482,18 @@ mod tests {
     };
     use tokio;
 
+    #[test]
+    fn test_offline_player_uuid() {
+        assert_eq!(
+            offline_player_uuid("Notch"),
+            "b50ad385-829d-3141-a216-7e7d7539ba7f"
+        );
+        assert_eq!(
+            offline_player_uuid("jeb_"),
+            "a762f560-4fce-3236-812a-b80efff0b62b"
+        );
+    }
+
     #[tokio::test]
     async fn test_get_vanilla_jar_url() {
         assert_eq!(super::get_vanilla_jar_url("1.18.2").await, Some(("https://piston-data.mojang.com/v1/objects/c8f83c5655308435b3dcf03c06d9fe8740a77469/server.jar".to_string(), Flavour::Vanilla)));
@@ -0,0 +1,556 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::{
+    error::{Error, ErrorKind},
+    events::CausedBy,
+    prelude::path_to_tmp,
+    traits::t_server::{State, TServer},
+    util::{resolve_path_conflict, unzip_file_async, UnzipOption},
+};
+
+use super::{
+    configurable::{Difficulty, Gamemode, ServerPropertySetting},
+    nbt::{read_nbt_file, Tag},
+    resource::read_zip_entry,
+    util::{compare_versions, read_properties_from_path, version_components},
+    MinecraftInstance,
+};
+
+/// Name of the directory inside `resources` that holds the worlds that are not in the
+/// instance root
+pub const WORLDS_DIR_NAME: &str = "worlds";
+
+const DEFAULT_LEVEL_NAME: &str = "world";
+
+/// Suffixes of the dimension folders Bukkit based servers create next to the main world
+const DIMENSION_SUFFIXES: [&str; 2] = ["_nether", "_the_end"];
+
+/// How deep to look for `level.dat` inside an imported archive
+const MAX_WORLD_ROOT_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct SpawnPoint {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+/// Metadata read from the `Data` compound of `level.dat`
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct LevelData {
+    pub name: Option<String>,
+    pub seed: Option<i64>,
+    pub game_mode: Option<String>,
+    pub difficulty: Option<String>,
+    pub spawn: Option<SpawnPoint>,
+    pub data_version: Option<i32>,
+    /// Name of the Minecraft version that last saved the world, e.g. `1.19.4`
+    pub version_name: Option<String>,
+}
+
+impl From<&Tag> for LevelData {
+    fn from(data: &Tag) -> Self {
+        let get_i32 = |key: &str| {
+            data.get(key)
+                .and_then(Tag::as_i64)
+                .and_then(|v| i32::try_from(v).ok())
+        };
+        let version = data.get("Version");
+        Self {
+            name: data
+                .get("LevelName")
+                .and_then(Tag::as_str)
+                .map(str::to_string),
+            // moved into WorldGenSettings in 1.16
+            seed: data
+                .get("WorldGenSettings")
+                .and_then(|settings| settings.get("seed"))
+                .or_else(|| data.get("RandomSeed"))
+                .and_then(Tag::as_i64),
+            game_mode: get_i32("GameType").and_then(|v| match v {
+                0 => Some(Gamemode::Survival.to_string()),
+                1 => Some(Gamemode::Creative.to_string()),
+                2 => Some(Gamemode::Adventure.to_string()),
+                3 => Some(Gamemode::Spectator.to_string()),
+                _ => None,
+            }),
+            difficulty: get_i32("Difficulty").and_then(|v| match v {
+                0 => Some(Difficulty::Peaceful.to_string()),
+                1 => Some(Difficulty::Easy.to_string()),
+                2 => Some(Difficulty::Normal.to_string()),
+                3 => Some(Difficulty::Hard.to_string()),
+                _ => None,
+            }),
+            spawn: match (get_i32("SpawnX"), get_i32("SpawnY"), get_i32("SpawnZ")) {
+                (Some(x), Some(y), Some(z)) => Some(SpawnPoint { x, y, z }),
+                _ => None,
+            },
+            data_version: get_i32("DataVersion").or_else(|| {
+                version
+                    .and_then(|version| version.get("Id"))
+                    .and_then(Tag::as_i64)
+                    .and_then(|v| i32::try_from(v).ok())
+            }),
+            version_name: version
+                .and_then(|version| version.get("Name"))
+                .and_then(Tag::as_str)
+                .map(str::to_string),
+        }
+    }
+}
+
+impl LevelData {
+    /// Whether the world was saved by a newer Minecraft version than `version`.
+    ///
+    /// Compares data versions when the one of `version` is known, and falls back to
+    /// comparing release names otherwise
+    fn is_newer_than(&self, version: &str, data_version: Option<i32>) -> bool {
+        if let (Some(level_data_version), Some(data_version)) = (self.data_version, data_version) {
+            return level_data_version > data_version;
+        }
+        let is_release =
+            |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_digit() || c == '.');
+        match self.version_name.as_deref() {
+            Some(version_name) if is_release(version_name) && is_release(version) => {
+                compare_versions(
+                    &version_components(version_name),
+                    &version_components(version),
+                ) == std::cmp::Ordering::Greater
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Read the `Data` compound of the world's `level.dat`
+fn read_level_dat(path_to_world: &Path) -> Result<Tag, Error> {
+    let (_, root) = read_nbt_file(&path_to_world.join("level.dat"))?;
+    match root {
+        Tag::Compound(mut root) => root.remove("Data").ok_or_else(|| {
+            eyre!(
+                "level.dat of {} has no Data compound",
+                path_to_world.display()
+            )
+            .into()
+        }),
+        _ => Err(eyre!("level.dat of {} is not a compound", path_to_world.display()).into()),
+    }
+}
+
+/// The data version a server jar writes its worlds with, from its `version.json`
+fn jar_data_version(path_to_jar: &Path) -> Option<i32> {
+    let file = std::fs::File::open(path_to_jar).ok()?;
+    let mut archive = zip::ZipArchive::new(std::io::BufReader::new(file)).ok()?;
+    let content = read_zip_entry(&mut archive, "version.json")?;
+    serde_json::from_str::<serde_json::Value>(&content).ok()?["world_version"]
+        .as_i64()
+        .and_then(|v| i32::try_from(v).ok())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS, PartialEq, Eq)]
+#[ts(export)]
+pub struct WorldEntry {
+    /// Value of `level-name` that selects this world, relative to the instance directory
+    pub level_name: String,
+    pub name: String,
+    pub size: u64,
+    // unix timestamp
+    pub last_played: Option<u64>,
+    pub active: bool,
+    /// `None` if `level.dat` could not be parsed
+    pub level: Option<LevelData>,
+}
+
+fn is_world_dir(path: &Path) -> bool {
+    path.join("level.dat").is_file()
+}
+
+fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn world_dirs_in(dir: &Path) -> Vec<PathBuf> {
+    let mut ret: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| is_world_dir(path))
+                .collect()
+        })
+        .unwrap_or_default();
+    // the nether and end folders of a Bukkit world are part of that world
+    let names: Vec<String> = ret
+        .iter()
+        .filter_map(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+    ret.retain(|path| {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        !DIMENSION_SUFFIXES.iter().any(|suffix| {
+            name.strip_suffix(suffix)
+                .map(|base| names.iter().any(|name| name == base))
+                .unwrap_or(false)
+        })
+    });
+    ret
+}
+
+/// Lists the worlds in the instance root and in `resources/worlds`
+fn list_worlds_in(
+    path_to_instance: &Path,
+    path_to_worlds: &Path,
+    level_name: &str,
+) -> Vec<WorldEntry> {
+    let active_path = path_to_instance.join(level_name);
+    let mut paths = world_dirs_in(path_to_instance);
+    paths.extend(world_dirs_in(path_to_worlds));
+    // the active world can live anywhere below the instance directory
+    if is_world_dir(&active_path) && !paths.contains(&active_path) {
+        paths.push(active_path.clone());
+    }
+    let mut ret: Vec<WorldEntry> = paths
+        .into_iter()
+        .filter_map(|path| {
+            let relative_path = path.strip_prefix(path_to_instance).ok()?;
+            let data = read_level_dat(&path).ok();
+            Some(WorldEntry {
+                level_name: relative_path
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                name: path.file_name()?.to_string_lossy().into_owned(),
+                size: dir_size(&path),
+                // LastPlayed is in milliseconds
+                last_played: data
+                    .as_ref()
+                    .and_then(|data| data.get("LastPlayed"))
+                    .and_then(Tag::as_i64)
+                    .and_then(|v| u64::try_from(v / 1000).ok())
+                    .or_else(|| {
+                        path.join("level.dat")
+                            .metadata()
+                            .and_then(|m| m.modified())
+                            .ok()
+                            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                            .map(|d| d.as_secs())
+                    }),
+                active: path == active_path,
+                level: data.as_ref().map(LevelData::from),
+            })
+        })
+        .collect();
+    ret.sort_by(|a, b| a.level_name.cmp(&b.level_name));
+    ret
+}
+
+/// Finds the shallowest directory containing `level.dat`, worlds are often archived
+/// inside one or more wrapping folders
+fn find_world_root(dir: &Path) -> Option<PathBuf> {
+    walkdir::WalkDir::new(dir)
+        .max_depth(MAX_WORLD_ROOT_DEPTH)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && entry.file_name() == "level.dat")
+        .min_by_key(|entry| entry.depth())
+        .and_then(|entry| entry.path().parent().map(Path::to_path_buf))
+}
+
+impl MinecraftInstance {
+    pub fn path_to_worlds(&self) -> PathBuf {
+        self.path_to_resources.join(WORLDS_DIR_NAME)
+    }
+
+    async fn level_name(&self) -> String {
+        read_properties_from_path(&self.path_to_properties)
+            .await
+            .ok()
+            .and_then(|properties| properties.get("level-name").cloned())
+            .filter(|level_name| !level_name.is_empty())
+            .unwrap_or_else(|| DEFAULT_LEVEL_NAME.to_string())
+    }
+
+    async fn ensure_stopped(&self) -> Result<(), Error> {
+        if self.state().await != State::Stopped {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Cannot manage worlds while server is running"),
+            });
+        }
+        Ok(())
+    }
+
+    pub async fn list_worlds(&self) -> Result<Vec<WorldEntry>, Error> {
+        let level_name = self.level_name().await;
+        let path_to_instance = self.path_to_instance.clone();
+        let path_to_worlds = self.path_to_worlds();
+        tokio::task::spawn_blocking(move || {
+            list_worlds_in(&path_to_instance, &path_to_worlds, &level_name)
+        })
+        .await
+        .context("Failed to list worlds")
+        .map_err(Error::from)
+    }
+
+    /// Make the world at `level_name` the one the server loads on next start
+    pub async fn set_active_world(&mut self, level_name: String) -> Result<(), Error> {
+        self.ensure_stopped().await?;
+        if !self
+            .list_worlds()
+            .await?
+            .iter()
+            .any(|world| world.level_name == level_name)
+        {
+            return Err(Error {
+                kind: ErrorKind::NotFound,
+                source: eyre!("World {} not found", level_name),
+            });
+        }
+        self.set_server_property(ServerPropertySetting::LevelName(level_name))
+            .await
+    }
+
+    /// Import a world from a .zip or .tar.gz archive into `resources/worlds`
+    pub async fn import_world(&self, path_to_archive: &Path) -> Result<WorldEntry, Error> {
+        self.ensure_stopped().await?;
+        let temp_dir = tempfile::tempdir_in(path_to_tmp())
+            .context("Failed to create temporary directory for the world")?;
+        unzip_file_async(
+            path_to_archive,
+            UnzipOption::ToDir(temp_dir.path().to_path_buf()),
+        )
+        .await?;
+        let world_root = find_world_root(temp_dir.path()).ok_or_else(|| Error {
+            kind: ErrorKind::BadRequest,
+            source: eyre!("The archive does not contain a level.dat"),
+        })?;
+
+        // a level.dat at the top of the archive leaves us without a folder name
+        let name = if world_root == temp_dir.path() {
+            // only the extension goes, "my.world.zip" becomes "my.world"
+            path_to_archive
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        } else {
+            world_root
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let name = sanitize_filename::sanitize(name);
+        let name = if name.is_empty() {
+            DEFAULT_LEVEL_NAME.to_string()
+        } else {
+            name
+        };
+
+        crate::util::fs::create_dir_all(self.path_to_worlds()).await?;
+        let dest = resolve_path_conflict(self.path_to_worlds().join(name), None);
+        crate::util::fs::rename(&world_root, &dest).await?;
+
+        let level_name = self.level_name().await;
+        let path_to_instance = self.path_to_instance.clone();
+        let path_to_worlds = self.path_to_worlds();
+        tokio::task::spawn_blocking(move || {
+            list_worlds_in(&path_to_instance, &path_to_worlds, &level_name)
+                .into_iter()
+                .find(|world| path_to_instance.join(&world.level_name) == dest)
+        })
+        .await
+        .context("Failed to list worlds")?
+        .ok_or_else(|| eyre!("Imported world is missing").into())
+    }
+
+    /// Refuse to switch to `version` if any world was saved by a newer Minecraft version,
+    /// since loading it in an older one corrupts it. `path_to_jar` is the new server jar
+    pub(super) async fn check_world_downgrade(
+        &self,
+        version: &str,
+        path_to_jar: &Path,
+    ) -> Result<(), Error> {
+        let path_to_jar = path_to_jar.to_path_buf();
+        let data_version = tokio::task::spawn_blocking(move || jar_data_version(&path_to_jar))
+            .await
+            .context("Failed to read the data version of the server jar")?;
+        for world in self.list_worlds().await? {
+            if let Some(level) = world.level {
+                if level.is_newer_than(version, data_version) {
+                    return Err(Error {
+                        kind: ErrorKind::BadRequest,
+                        source: eyre!(
+                            "World {} was saved by a newer Minecraft version ({}), downgrading to {} would corrupt it",
+                            world.name,
+                            level.version_name.as_deref().unwrap_or("unknown"),
+                            version
+                        ),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Back up the instance, then delete the active world so that the server generates
+    /// a new one with `seed` on next start. An empty or missing seed picks a random one
+    pub async fn regenerate_world(
+        &mut self,
+        seed: Option<String>,
+        caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        self.ensure_stopped().await?;
+        self.backup(caused_by).await?;
+        let path_to_world = self.path_to_instance.join(self.level_name().await);
+        let mut paths = vec![path_to_world.clone()];
+        if let Some(name) = path_to_world.file_name() {
+            for suffix in DIMENSION_SUFFIXES {
+                paths.push(path_to_world.with_file_name(format!(
+                    "{}{}",
+                    name.to_string_lossy(),
+                    suffix
+                )));
+            }
+        }
+        for path in paths {
+            if path.is_dir() {
+                crate::util::fs::remove_dir_all(&path).await?;
+            }
+        }
+        self.set_server_property(ServerPropertySetting::LevelSeed(seed.unwrap_or_default()))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_world(path: &Path) {
+        std::fs::create_dir_all(path).unwrap();
+        std::fs::write(path.join("level.dat"), [0u8; 16]).unwrap();
+    }
+
+    #[test]
+    fn test_find_world_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert_eq!(find_world_root(temp_dir.path()), None);
+
+        create_world(&temp_dir.path().join("backup").join("my world"));
+        create_world(&temp_dir.path().join("backup").join("my world").join("DIM1"));
+        assert_eq!(
+            find_world_root(temp_dir.path()),
+            Some(temp_dir.path().join("backup").join("my world"))
+        );
+
+        create_world(temp_dir.path());
+        assert_eq!(
+            find_world_root(temp_dir.path()),
+            Some(temp_dir.path().to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_level_data() {
+        let version = Tag::Compound(
+            [
+                ("Id".to_string(), Tag::Int(3337)),
+                ("Name".to_string(), Tag::String("1.19.4".to_string())),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let world_gen_settings = Tag::Compound(
+            [("seed".to_string(), Tag::Long(-1234567890123))]
+                .into_iter()
+                .collect(),
+        );
+        let data = Tag::Compound(
+            [
+                ("LevelName".to_string(), Tag::String("world".to_string())),
+                ("GameType".to_string(), Tag::Int(1)),
+                ("Difficulty".to_string(), Tag::Byte(3)),
+                ("SpawnX".to_string(), Tag::Int(-16)),
+                ("SpawnY".to_string(), Tag::Int(64)),
+                ("SpawnZ".to_string(), Tag::Int(32)),
+                ("DataVersion".to_string(), Tag::Int(3337)),
+                ("Version".to_string(), version),
+                ("WorldGenSettings".to_string(), world_gen_settings),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let level = LevelData::from(&data);
+        assert_eq!(
+            level,
+            LevelData {
+                name: Some("world".to_string()),
+                seed: Some(-1234567890123),
+                game_mode: Some("creative".to_string()),
+                difficulty: Some("hard".to_string()),
+                spawn: Some(SpawnPoint {
+                    x: -16,
+                    y: 64,
+                    z: 32
+                }),
+                data_version: Some(3337),
+                version_name: Some("1.19.4".to_string()),
+            }
+        );
+
+        assert!(level.is_newer_than("1.19.2", Some(3120)));
+        assert!(!level.is_newer_than("1.20.1", Some(3465)));
+        // without the data version of the target, fall back to the release names
+        assert!(level.is_newer_than("1.19.3", None));
+        assert!(!level.is_newer_than("1.19.4", None));
+        assert!(!level.is_newer_than("23w13a", None));
+    }
+
+    #[test]
+    fn test_list_worlds_in() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path_to_instance = temp_dir.path();
+        let path_to_worlds = path_to_instance.join("resources").join("worlds");
+        create_world(&path_to_instance.join("world"));
+        create_world(&path_to_instance.join("world_nether"));
+        create_world(&path_to_instance.join("world_the_end"));
+        create_world(&path_to_worlds.join("creative"));
+        std::fs::create_dir_all(path_to_instance.join("logs")).unwrap();
+
+        let worlds = list_worlds_in(path_to_instance, &path_to_worlds, "world");
+        assert_eq!(
+            worlds
+                .iter()
+                .map(|w| (w.level_name.as_str(), w.name.as_str(), w.active))
+                .collect::<Vec<_>>(),
+            vec![
+                ("resources/worlds/creative", "creative", false),
+                ("world", "world", true),
+            ]
+        );
+        assert_eq!(worlds[1].size, 16);
+
+        let worlds = list_worlds_in(
+            path_to_instance,
+            &path_to_worlds,
+            "resources/worlds/creative",
+        );
+        assert!(worlds[0].active);
+        assert!(!worlds[1].active);
+    }
+}
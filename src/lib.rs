@@ -6,16 +6,18 @@ use crate::prelude::{
     init_paths, lodestone_path, path_to_global_settings, path_to_stores, path_to_users, VERSION,
 };
 use crate::traits::t_configurable::GameType;
-use crate::traits::t_server::State;
 use crate::{
-    db::write::write_event_to_db_task,
+    db::{player_sessions::write_player_sessions_task, write::write_event_to_db_task},
     global_settings::GlobalSettingsData,
     handlers::{
         checks::get_checks_routes, core_info::get_core_info_routes, events::get_events_routes,
         gateway::get_gateway_routes, global_fs::get_global_fs_routes,
         global_settings::get_global_settings_routes, instance::*,
+        instance_access_list::get_instance_access_list_routes,
         instance_config::get_instance_config_routes, instance_fs::get_instance_fs_routes,
         instance_macro::get_instance_macro_routes, instance_players::get_instance_players_routes,
+        instance_resource::get_instance_resource_routes,
+        instance_schedule::get_instance_schedule_routes,
         instance_server::get_instance_server_routes,
         instance_setup_configs::get_instance_setup_config_routes, monitor::get_monitor_routes,
         setup::get_setup_route, system::get_system_routes, users::get_user_routes,
@@ -28,10 +30,10 @@ use axum::Router;
 
 use axum_server::tls_rustls::RustlsConfig;
 use clap::Parser;
-use color_eyre::eyre::Context;
+use color_eyre::eyre::{eyre, Context};
 use color_eyre::Report;
-use error::Error;
-use events::{CausedBy, Event};
+use error::{Error, ErrorKind};
+use events::Event;
 use futures::Future;
 use global_settings::GlobalSettings;
 use implementations::{generic, minecraft};
@@ -40,9 +42,11 @@ use port_manager::PortManager;
 use prelude::GameInstance;
 use reqwest::{header, Method};
 use ringbuffer::{AllocRingBuffer, RingBufferWrite};
+use scheduler::{run_scheduler, Scheduler};
 
 use semver::Version;
 use sqlx::{sqlite::SqliteConnectOptions, Pool};
+use startup::{auto_start_instances, stop_all_instances, StartupManager};
 use std::{
     collections::{HashMap, HashSet},
     net::SocketAddr,
@@ -64,7 +68,7 @@ use tracing::{debug, error, info, warn};
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, EnvFilter};
 use traits::{t_configurable::TConfigurable, t_server::MonitorReport, t_server::TServer};
-use types::{DotLodestoneConfig, InstanceUuid};
+use types::{DotLodestoneConfig, InstanceRestoreFailure, InstanceUuid};
 use uuid::Uuid;
 pub mod auth;
 pub mod db;
@@ -80,6 +84,8 @@ mod migration;
 mod output_types;
 mod port_manager;
 pub mod prelude;
+mod scheduler;
+mod startup;
 pub mod tauri_export;
 mod traits;
 pub mod types;
@@ -96,19 +102,29 @@ pub struct AppState {
     uuid: String,
     up_since: i64,
     global_settings: Arc<Mutex<GlobalSettings>>,
+    scheduler: Arc<Mutex<Scheduler>>,
+    startup_manager: Arc<Mutex<StartupManager>>,
     system: Arc<Mutex<sysinfo::System>>,
     port_manager: Arc<Mutex<PortManager>>,
     first_time_setup_key: Arc<Mutex<Option<String>>>,
     download_urls: Arc<Mutex<HashMap<String, PathBuf>>>,
     macro_executor: MacroExecutor,
     sqlite_pool: sqlx::SqlitePool,
+    restore_failures: Arc<Vec<InstanceRestoreFailure>>,
 }
 async fn restore_instances(
     instances_path: &Path,
     event_broadcaster: EventBroadcaster,
     macro_executor: MacroExecutor,
-) -> Result<HashMap<InstanceUuid, GameInstance>, Error> {
+) -> Result<
+    (
+        HashMap<InstanceUuid, GameInstance>,
+        Vec<InstanceRestoreFailure>,
+    ),
+    Error,
+> {
     let mut ret: HashMap<InstanceUuid, GameInstance> = HashMap::new();
+    let mut failures: Vec<InstanceRestoreFailure> = Vec::new();
 
     for entry in instances_path
         .read_dir()
@@ -121,10 +137,23 @@ async fn restore_instances(
                 continue;
             }
         };
+        if !path.join(".lodestone_config").is_file() {
+            debug!(
+                "Skipping {} as it has no .lodestone_config file",
+                path.display()
+            );
+            continue;
+        }
         let dot_lodestone_config_file = match std::fs::File::open(path.join(".lodestone_config")) {
             Ok(v) => v,
             Err(e) => {
                 error!("Error while restoring instance {}, failed to read .lodestone_config file : {e}", path.display());
+                failures.push(InstanceRestoreFailure {
+                    path,
+                    uuid: None,
+                    game_type: None,
+                    error: format!("Failed to read .lodestone_config file : {e}"),
+                });
                 continue;
             }
         };
@@ -134,30 +163,55 @@ async fn restore_instances(
             Ok(v) => v,
             Err(e) => {
                 error!("Error while restoring instance {}, failed to parse .lodestone_config file : {e}", path.display());
+                failures.push(InstanceRestoreFailure {
+                    path,
+                    uuid: None,
+                    game_type: None,
+                    error: format!("Failed to parse .lodestone_config file : {e}"),
+                });
                 continue;
             }
         };
         debug!("restoring instance: {}", path.display());
-        if let GameType::MinecraftJava = dot_lodestone_config.game_type() {
-            let instance = match minecraft::MinecraftInstance::restore(
+        let instance: Result<GameInstance, Error> = match dot_lodestone_config.game_type() {
+            GameType::MinecraftJava => minecraft::MinecraftInstance::restore(
                 path.to_owned(),
                 dot_lodestone_config.clone(),
                 event_broadcaster.clone(),
                 macro_executor.clone(),
             )
             .await
-            {
-                Ok(v) => v,
-                Err(e) => {
-                    error!("Error while restoring instance {} : {e}", path.display());
-                    continue;
-                }
-            };
-            debug!("Restored successfully");
-            ret.insert(dot_lodestone_config.uuid().to_owned(), instance.into());
+            .map(Into::into),
+            GameType::Generic => generic::GenericInstance::restore(
+                path.to_owned(),
+                dot_lodestone_config.clone(),
+                event_broadcaster.clone(),
+                macro_executor.clone(),
+            )
+            .await
+            .map(Into::into),
+            GameType::MinecraftBedrock => Err(Error {
+                kind: ErrorKind::UnsupportedOperation,
+                source: eyre!("Minecraft Bedrock instances are not supported"),
+            }),
+        };
+        match instance {
+            Ok(instance) => {
+                debug!("Restored successfully");
+                ret.insert(dot_lodestone_config.uuid().to_owned(), instance);
+            }
+            Err(e) => {
+                error!("Error while restoring instance {} : {e}", path.display());
+                failures.push(InstanceRestoreFailure {
+                    path,
+                    uuid: Some(dot_lodestone_config.uuid().to_owned()),
+                    game_type: Some(*dot_lodestone_config.game_type()),
+                    error: format!("{:#}", e.source),
+                });
+            }
         }
     }
-    Ok(ret)
+    Ok((ret, failures))
 }
 
 fn setup_tracing() -> tracing_appender::non_blocking::WorkerGuard {
@@ -383,6 +437,14 @@ pub async fn run(
 
     global_settings.load_from_file().await.unwrap();
 
+    let mut scheduler = Scheduler::new(path_to_stores().join("scheduled_jobs.json"));
+
+    scheduler.load_from_file().await.unwrap();
+
+    let mut startup_manager = StartupManager::new(path_to_stores().join("startup.json"));
+
+    startup_manager.load_from_file().await.unwrap();
+
     let first_time_setup_key = if !users_manager.as_ref().iter().any(|(_, user)| user.is_owner) {
         let key = rand_alphanumeric(16);
         // log the first time setup key in green so it's easy to find
@@ -400,30 +462,25 @@ pub async fn run(
         None
     };
     let macro_executor = MacroExecutor::new(tx.clone());
-    let mut instances = restore_instances(&path_to_instances, tx.clone(), macro_executor.clone())
-        .await
-        .map_err(|e| {
-            error!(
-                "Failed to restore instances: {}, lodestone will now crash...",
-                e
-            );
-        })
-        .unwrap();
-    for (_, instance) in instances.iter_mut() {
-        if instance.auto_start().await {
-            info!("Auto starting instance {}", instance.name().await);
-            if let Err(e) = instance.start(CausedBy::System, false).await {
+    let (instances, restore_failures) =
+        restore_instances(&path_to_instances, tx.clone(), macro_executor.clone())
+            .await
+            .map_err(|e| {
                 error!(
-                    "Failed to start instance {}: {:?}",
-                    instance.name().await,
+                    "Failed to restore instances: {}, lodestone will now crash...",
                     e
                 );
-            }
-        }
-    }
+            })
+            .unwrap();
     let mut allocated_ports = HashSet::new();
-    for (_, instance) in instances.iter() {
+    let mut query_ports = HashMap::new();
+    for (uuid, instance) in instances.iter() {
         allocated_ports.insert(instance.port().await);
+        if let GameInstance::MinecraftInstance(instance) = instance {
+            if let Some(query_port) = instance.query_port().await {
+                query_ports.insert(uuid.clone(), query_port);
+            }
+        }
     }
     let shared_state = AppState {
         instances: Arc::new(Mutex::new(instances)),
@@ -434,11 +491,13 @@ pub async fn run(
         event_broadcaster: tx.clone(),
         uuid: Uuid::new_v4().to_string(),
         up_since: chrono::Utc::now().timestamp(),
-        port_manager: Arc::new(Mutex::new(PortManager::new(allocated_ports))),
+        port_manager: Arc::new(Mutex::new(PortManager::new(allocated_ports, query_ports))),
         first_time_setup_key: Arc::new(Mutex::new(first_time_setup_key)),
         system: Arc::new(Mutex::new(sysinfo::System::new_all())),
         download_urls: Arc::new(Mutex::new(HashMap::new())),
         global_settings: Arc::new(Mutex::new(global_settings)),
+        scheduler: Arc::new(Mutex::new(scheduler)),
+        startup_manager: Arc::new(Mutex::new(startup_manager)),
         macro_executor,
         sqlite_pool: Pool::connect_with(
             SqliteConnectOptions::from_str(&format!(
@@ -450,8 +509,11 @@ pub async fn run(
         )
         .await
         .unwrap(),
+        restore_failures: Arc::new(restore_failures),
     };
 
+    tokio::task::spawn(auto_start_instances(shared_state.clone()));
+
     let event_buffer_task = {
         let event_buffer = shared_state.events_buffer.clone();
         let console_out_buffer = shared_state.console_out_buffer.clone();
@@ -488,6 +550,9 @@ pub async fn run(
 
     let write_to_db_task = write_event_to_db_task(tx.subscribe(), shared_state.sqlite_pool.clone());
 
+    let player_sessions_task =
+        write_player_sessions_task(tx.subscribe(), shared_state.sqlite_pool.clone());
+
     let monitor_report_task = {
         let monitor_buffer = shared_state.monitor_buffer.clone();
         let instances = shared_state.instances.clone();
@@ -508,6 +573,8 @@ pub async fn run(
         }
     };
 
+    let scheduler_task = run_scheduler(shared_state.clone());
+
     let tls_config_result = RustlsConfig::from_pem_file(
         lodestone_path.join("tls").join("cert.pem"),
         lodestone_path.join("tls").join("key.pem"),
@@ -538,6 +605,9 @@ pub async fn run(
                     .merge(get_instance_server_routes(shared_state.clone()))
                     .merge(get_instance_config_routes(shared_state.clone()))
                     .merge(get_instance_players_routes(shared_state.clone()))
+                    .merge(get_instance_resource_routes(shared_state.clone()))
+                    .merge(get_instance_access_list_routes(shared_state.clone()))
+                    .merge(get_instance_schedule_routes(shared_state.clone()))
                     .merge(get_instance_routes(shared_state.clone()))
                     .merge(get_system_routes(shared_state.clone()))
                     .merge(get_checks_routes(shared_state.clone()))
@@ -595,27 +665,17 @@ pub async fn run(
                 });
                 select! {
                     _ = write_to_db_task => info!("Write to db task exited"),
+                    _ = player_sessions_task => info!("Player sessions task exited"),
                     _ = event_buffer_task => info!("Event buffer task exited"),
                     _ = monitor_report_task => info!("Monitor report task exited"),
+                    _ = scheduler_task => info!("Scheduler task exited"),
                     _ = tokio::signal::ctrl_c() => info!("Ctrl+C received"),
                 }
                 info!("Shutting down web server");
                 axum_server_handle.shutdown();
                 info!("Signalling all instances to stop");
-                // cleanup
-                let mut instances = shared_state.instances.lock().await;
-                for (_, instance) in instances.iter_mut() {
-                    if instance.state().await == State::Stopped {
-                        continue;
-                    }
-                    if let Err(e) = instance.stop(CausedBy::System, false).await {
-                        error!(
-                            "Failed to stop instance {} : {}. Instance may need manual cleanup",
-                            instance.uuid().await,
-                            e
-                        );
-                    }
-                }
+                // cleanup, dependents stop before their dependencies
+                stop_all_instances(shared_state.clone()).await;
             }
         },
         shared_state,
@@ -4,7 +4,13 @@ use color_eyre::eyre::Context;
 use serde_json::{json, Value};
 use tracing::error;
 
-use crate::{error::Error, implementations::minecraft::RestoreConfig};
+use crate::{
+    error::Error,
+    implementations::minecraft::{
+        backup::default_max_backups, countdown::CountdownConfig,
+        mod_analysis::default_check_mods_on_start, server::default_stop_timeout, RestoreConfig,
+    },
+};
 
 use super::RestoreConfigV042;
 
@@ -22,6 +28,11 @@ impl From<RestoreConfigV042> for RestoreConfig {
             auto_start: config.auto_start,
             restart_on_crash: config.restart_on_crash,
             backup_period: config.backup_period,
+            max_backups: default_max_backups(),
+            stop_timeout: default_stop_timeout(),
+            countdown: CountdownConfig::default(),
+            check_mods_on_start: default_check_mods_on_start(),
+            line_rules: Vec::new(),
             jre_major_version: config.jre_major_version,
             has_started: config.has_started,
             java_cmd: None,
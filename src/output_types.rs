@@ -25,6 +25,7 @@ impl From<&Event> for ClientEvent {
             EventInner::InstanceEvent(i) => match i.instance_event_inner {
                 InstanceEventInner::InstanceError { .. } => EventLevel::Error,
                 InstanceEventInner::InstanceWarning { .. } => EventLevel::Warning,
+                InstanceEventInner::ScheduledJobRun { error: Some(_), .. } => EventLevel::Error,
                 _ => EventLevel::Info,
             },
             EventInner::UserEvent(_) => EventLevel::Info,
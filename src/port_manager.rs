@@ -1,12 +1,17 @@
-use std::{collections::HashSet, net::SocketAddrV4};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{SocketAddrV4, UdpSocket},
+};
 
 use color_eyre::eyre::{eyre, Context};
 use serde::{Deserialize, Serialize};
 
-use crate::error::Error;
+use crate::{error::Error, types::InstanceUuid};
 
 pub struct PortManager {
     allocated_ports: HashSet<u32>,
+    // UDP query ports of instances with query enabled, they may share a number with a game port
+    query_ports: HashMap<InstanceUuid, u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -16,14 +21,24 @@ pub struct PortStatus {
 }
 
 impl PortManager {
-    pub fn new(allocated_ports: HashSet<u32>) -> PortManager {
-        PortManager { allocated_ports }
+    pub fn new(
+        allocated_ports: HashSet<u32>,
+        query_ports: HashMap<InstanceUuid, u32>,
+    ) -> PortManager {
+        PortManager {
+            allocated_ports,
+            query_ports,
+        }
+    }
+
+    fn is_allocated(&self, port: u32) -> bool {
+        self.allocated_ports.contains(&port) || self.query_ports.values().any(|p| *p == port)
     }
 
     pub fn allocate(&mut self, start_port: u32) -> u32 {
-        if self.allocated_ports.contains(&start_port) {
+        if self.is_allocated(start_port) {
             let mut new_port = start_port + 1;
-            while self.allocated_ports.contains(&new_port)
+            while self.is_allocated(new_port)
                 || !port_scanner::local_port_available(new_port as u16)
             {
                 new_port += 1;
@@ -39,7 +54,24 @@ impl PortManager {
     pub fn port_status(&self, port: u32) -> PortStatus {
         PortStatus {
             is_in_use: !port_scanner::local_port_available(port as u16),
-            is_allocated: self.allocated_ports.contains(&port),
+            is_allocated: self.is_allocated(port),
+        }
+    }
+
+    /// Whether nothing is bound to the UDP port, used for query ports
+    pub fn udp_port_available(port: u32) -> bool {
+        UdpSocket::bind(("0.0.0.0", port as u16)).is_ok()
+    }
+
+    /// `None` stops tracking the query port of the instance
+    pub fn set_query_port(&mut self, instance_uuid: &InstanceUuid, port: Option<u32>) {
+        match port {
+            Some(port) => {
+                self.query_ports.insert(instance_uuid.clone(), port);
+            }
+            None => {
+                self.query_ports.remove(instance_uuid);
+            }
         }
     }
 
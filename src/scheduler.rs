@@ -0,0 +1,534 @@
+use std::{collections::BTreeSet, path::PathBuf, str::FromStr, time::Duration};
+
+use chrono::{DateTime, Datelike, Local, TimeZone, Timelike, Utc};
+use color_eyre::eyre::{eyre, Context};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info, warn};
+use ts_rs::TS;
+
+use crate::{
+    error::{Error, ErrorKind},
+    events::{CausedBy, Event, EventInner, InstanceEvent, InstanceEventInner},
+    implementations::minecraft::countdown::CountdownAction,
+    prelude::GameInstance,
+    traits::{t_configurable::TConfigurable, t_macro::TMacro, t_server::TServer},
+    types::{InstanceUuid, Snowflake},
+    AppState,
+};
+
+/// A five field cron expression: minute, hour, day of month, month and day of week
+///
+/// Each field accepts `*`, single values, ranges (`1-5`), lists (`1,15`) and steps (`*/15`).
+/// The `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` shorthands are also accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days_of_month: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    // 0 is Sunday
+    days_of_week: BTreeSet<u32>,
+    // like cron, a day matches if either day field matches when both are restricted
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+fn invalid_cron(message: String) -> Error {
+    Error {
+        kind: ErrorKind::BadRequest,
+        source: eyre!("Invalid cron expression: {}", message),
+    }
+}
+
+fn parse_cron_value(value: &str, min: u32, max: u32) -> Result<u32, Error> {
+    let parsed = value
+        .parse::<u32>()
+        .map_err(|_| invalid_cron(format!("'{value}' is not a number")))?;
+    if parsed < min || parsed > max {
+        return Err(invalid_cron(format!(
+            "{parsed} is out of range {min}-{max}"
+        )));
+    }
+    Ok(parsed)
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<BTreeSet<u32>, Error> {
+    let mut values = BTreeSet::new();
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<usize>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| invalid_cron(format!("'{step}' is not a valid step")))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_cron_value(start, min, max)?,
+                parse_cron_value(end, min, max)?,
+            )
+        } else {
+            let value = parse_cron_value(range, min, max)?;
+            // "5/10" means every 10 starting at 5
+            if step > 1 {
+                (value, max)
+            } else {
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(invalid_cron(format!("'{range}' is an empty range")));
+        }
+        values.extend((start..=end).step_by(step));
+    }
+    Ok(values)
+}
+
+impl FromStr for CronSchedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid_cron(format!(
+                "expected 5 fields, got {}",
+                fields.len()
+            )));
+        }
+        Ok(CronSchedule {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            days_of_month: parse_cron_field(fields[2], 1, 31)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            // both 0 and 7 are Sunday
+            days_of_week: parse_cron_field(fields[4], 0, 7)?
+                .into_iter()
+                .map(|day| day % 7)
+                .collect(),
+            day_of_month_restricted: !fields[2].starts_with('*'),
+            day_of_week_restricted: !fields[4].starts_with('*'),
+        })
+    }
+}
+
+impl CronSchedule {
+    pub fn matches<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let day_of_month = self.days_of_month.contains(&time.day());
+        let day_of_week = self
+            .days_of_week
+            .contains(&time.weekday().num_days_from_sunday());
+        let day = if self.day_of_month_restricted && self.day_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        };
+        day && self.minutes.contains(&time.minute())
+            && self.hours.contains(&time.hour())
+            && self.months.contains(&time.month())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, TS, PartialEq)]
+#[ts(export)]
+#[serde(tag = "type")]
+pub enum ScheduledAction {
+    Start,
+    /// `countdown` warns players first, see [`CountdownConfig`](crate::implementations::minecraft::countdown::CountdownConfig)
+    Stop {
+        #[serde(default)]
+        countdown: bool,
+    },
+    Restart {
+        #[serde(default)]
+        countdown: bool,
+    },
+    Command {
+        command: String,
+    },
+    Macro {
+        name: String,
+        args: Vec<String>,
+    },
+}
+
+#[derive(Deserialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct ScheduledJobConfig {
+    pub cron: String,
+    pub action: ScheduledAction,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, TS)]
+#[ts(export)]
+pub struct ScheduledJob {
+    pub id: String,
+    pub instance_uuid: InstanceUuid,
+    pub cron: String,
+    pub action: ScheduledAction,
+    pub enabled: bool,
+    pub last_run: Option<i64>,
+}
+
+pub struct Scheduler {
+    path_to_jobs: PathBuf,
+    jobs: IndexMap<String, ScheduledJob>,
+}
+
+impl Scheduler {
+    pub fn new(path_to_jobs: PathBuf) -> Self {
+        Self {
+            path_to_jobs,
+            jobs: IndexMap::new(),
+        }
+    }
+
+    pub async fn load_from_file(&mut self) -> Result<(), Error> {
+        if tokio::fs::OpenOptions::new()
+            .read(true)
+            .create(true)
+            .write(true)
+            .open(&self.path_to_jobs)
+            .await
+            .context(format!(
+                "Failed to open scheduled jobs file at {}",
+                self.path_to_jobs.display()
+            ))?
+            .metadata()
+            .await
+            .context(format!(
+                "Failed to get metadata for scheduled jobs file at {}",
+                self.path_to_jobs.display()
+            ))?
+            .len()
+            == 0
+        {
+            self.jobs = IndexMap::new();
+        } else {
+            self.jobs = serde_json::from_slice(
+                &tokio::fs::read(&self.path_to_jobs).await.context(format!(
+                    "Failed to read scheduled jobs file at {}",
+                    self.path_to_jobs.display()
+                ))?,
+            )
+            .context(format!(
+                "Failed to parse scheduled jobs file at {}",
+                self.path_to_jobs.display()
+            ))?;
+        }
+        Ok(())
+    }
+
+    async fn write_to_file(&self) -> Result<(), Error> {
+        let mut file = tokio::fs::File::create(&self.path_to_jobs)
+            .await
+            .context(format!(
+                "Failed to create scheduled jobs file at {}",
+                self.path_to_jobs.display()
+            ))?;
+        file.write_all(
+            serde_json::to_string_pretty(&self.jobs)
+                .context("Failed to serialize scheduled jobs")?
+                .as_bytes(),
+        )
+        .await
+        .context(format!(
+            "Failed to write to scheduled jobs file at {}",
+            self.path_to_jobs.display()
+        ))?;
+        Ok(())
+    }
+
+    pub fn get_job(&self, job_id: &str) -> Option<ScheduledJob> {
+        self.jobs.get(job_id).cloned()
+    }
+
+    pub fn jobs_for_instance(&self, instance_uuid: &InstanceUuid) -> Vec<ScheduledJob> {
+        self.jobs
+            .values()
+            .filter(|job| &job.instance_uuid == instance_uuid)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn add_job(
+        &mut self,
+        instance_uuid: InstanceUuid,
+        config: ScheduledJobConfig,
+    ) -> Result<ScheduledJob, Error> {
+        CronSchedule::from_str(&config.cron)?;
+        let job = ScheduledJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            instance_uuid,
+            cron: config.cron,
+            action: config.action,
+            enabled: config.enabled,
+            last_run: None,
+        };
+        self.jobs.insert(job.id.clone(), job.clone());
+        if let Err(e) = self.write_to_file().await {
+            self.jobs.remove(&job.id);
+            return Err(e);
+        }
+        Ok(job)
+    }
+
+    pub async fn update_job(
+        &mut self,
+        job_id: &str,
+        config: ScheduledJobConfig,
+    ) -> Result<ScheduledJob, Error> {
+        CronSchedule::from_str(&config.cron)?;
+        let job = self.jobs.get_mut(job_id).ok_or_else(|| Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Scheduled job not found"),
+        })?;
+        let old_job = job.clone();
+        job.cron = config.cron;
+        job.action = config.action;
+        job.enabled = config.enabled;
+        let job = job.clone();
+        if let Err(e) = self.write_to_file().await {
+            self.jobs.insert(old_job.id.clone(), old_job);
+            return Err(e);
+        }
+        Ok(job)
+    }
+
+    pub async fn delete_job(&mut self, job_id: &str) -> Result<(), Error> {
+        let job = self.jobs.shift_remove(job_id).ok_or_else(|| Error {
+            kind: ErrorKind::NotFound,
+            source: eyre!("Scheduled job not found"),
+        })?;
+        if let Err(e) = self.write_to_file().await {
+            self.jobs.insert(job.id.clone(), job);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub async fn delete_instance_jobs(
+        &mut self,
+        instance_uuid: &InstanceUuid,
+    ) -> Result<(), Error> {
+        let old_jobs = self.jobs.clone();
+        self.jobs
+            .retain(|_, job| &job.instance_uuid != instance_uuid);
+        if let Err(e) = self.write_to_file().await {
+            self.jobs = old_jobs;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Returns the enabled jobs due at `now` and marks them as run,
+    /// so a job never fires twice within the same minute
+    async fn take_due_jobs<Tz: TimeZone>(&mut self, now: &DateTime<Tz>) -> Vec<ScheduledJob> {
+        let minute = now.timestamp().div_euclid(60);
+        let mut due_jobs = Vec::new();
+        for job in self.jobs.values_mut() {
+            if !job.enabled || job.last_run.map(|t| t.div_euclid(60)) == Some(minute) {
+                continue;
+            }
+            match CronSchedule::from_str(&job.cron) {
+                Ok(schedule) if schedule.matches(now) => {
+                    job.last_run = Some(now.timestamp());
+                    due_jobs.push(job.clone());
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Skipping scheduled job {}: {}", job.id, e),
+            }
+        }
+        if !due_jobs.is_empty() {
+            if let Err(e) = self.write_to_file().await {
+                error!("Failed to save scheduled jobs: {}", e);
+            }
+        }
+        due_jobs
+    }
+}
+
+async fn run_job(state: AppState, job: ScheduledJob) {
+    let instance = state
+        .instances
+        .lock()
+        .await
+        .get(&job.instance_uuid)
+        .cloned();
+    let mut instance = match instance {
+        Some(instance) => instance,
+        None => {
+            warn!(
+                "Scheduled job {} targets instance {} which does not exist",
+                job.id, job.instance_uuid
+            );
+            return;
+        }
+    };
+    let instance_name = instance.name().await;
+    info!("[{}] Running scheduled job {}", instance_name, job.id);
+    let result = match &job.action {
+        ScheduledAction::Start => instance.start(CausedBy::System, false).await,
+        ScheduledAction::Stop { countdown: false } => instance.stop(CausedBy::System, false).await,
+        ScheduledAction::Restart { countdown: false } => {
+            instance.restart(CausedBy::System, false).await
+        }
+        ScheduledAction::Stop { countdown: true }
+        | ScheduledAction::Restart { countdown: true } => {
+            let countdown_action = match job.action {
+                ScheduledAction::Stop { .. } => CountdownAction::Stop,
+                _ => CountdownAction::Restart,
+            };
+            match &instance {
+                GameInstance::MinecraftInstance(instance) => {
+                    instance
+                        .start_countdown(countdown_action, CausedBy::System)
+                        .await
+                }
+                GameInstance::GenericInstance(_) => Err(Error {
+                    kind: ErrorKind::UnsupportedOperation,
+                    source: eyre!("This instance does not support countdowns"),
+                }),
+            }
+        }
+        ScheduledAction::Command { command } => {
+            instance.send_command(command, CausedBy::System).await
+        }
+        ScheduledAction::Macro { name, args } => instance
+            .run_macro(name, args.clone(), CausedBy::System)
+            .await
+            .map(|_| ()),
+    };
+    let error = match result {
+        Ok(()) => None,
+        Err(e) => {
+            error!("[{}] Scheduled job {} failed: {}", instance_name, job.id, e);
+            Some(e.source.to_string())
+        }
+    };
+    state.event_broadcaster.send(Event {
+        event_inner: EventInner::InstanceEvent(InstanceEvent {
+            instance_uuid: job.instance_uuid.clone(),
+            instance_name,
+            instance_event_inner: InstanceEventInner::ScheduledJobRun {
+                job_id: job.id.clone(),
+                action: job.action.clone(),
+                error,
+            },
+        }),
+        details: format!("Scheduled job ran on schedule '{}'", job.cron),
+        snowflake: Snowflake::default(),
+        caused_by: CausedBy::System,
+    });
+}
+
+/// Wakes up at the start of every minute and runs the jobs that are due
+pub async fn run_scheduler(state: AppState) {
+    loop {
+        let millis_into_minute = Utc::now().timestamp_millis().rem_euclid(60_000) as u64;
+        tokio::time::sleep(Duration::from_millis(60_000 - millis_into_minute)).await;
+        // an IANA zone rather than a fixed offset, so schedules follow daylight saving changes
+        let timezone = state.global_settings.lock().await.timezone();
+        let mut scheduler = state.scheduler.lock().await;
+        let due_jobs = match timezone {
+            Some(timezone) => {
+                scheduler
+                    .take_due_jobs(&Utc::now().with_timezone(&timezone))
+                    .await
+            }
+            None => scheduler.take_due_jobs(&Local::now()).await,
+        };
+        drop(scheduler);
+        for job in due_jobs {
+            tokio::task::spawn(run_job(state.clone(), job));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2023-05-01 is a Monday
+        Utc.with_ymd_and_hms(2023, 5, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_cron() {
+        let schedule = CronSchedule::from_str("*/15 4 * * 1-5").unwrap();
+        assert_eq!(
+            schedule.minutes,
+            BTreeSet::from_iter([0, 15, 30, 45].into_iter())
+        );
+        assert_eq!(schedule.hours, BTreeSet::from_iter([4].into_iter()));
+        assert_eq!(
+            schedule.days_of_week,
+            BTreeSet::from_iter([1, 2, 3, 4, 5].into_iter())
+        );
+
+        assert_eq!(
+            CronSchedule::from_str("@daily").unwrap(),
+            CronSchedule::from_str("0 0 * * *").unwrap()
+        );
+        assert_eq!(
+            CronSchedule::from_str("0 0 * * 7").unwrap(),
+            CronSchedule::from_str("0 0 * * 0").unwrap()
+        );
+
+        assert!(CronSchedule::from_str("* * * *").is_err());
+        assert!(CronSchedule::from_str("60 * * * *").is_err());
+        assert!(CronSchedule::from_str("*/0 * * * *").is_err());
+        assert!(CronSchedule::from_str("5-1 * * * *").is_err());
+        assert!(CronSchedule::from_str("a * * * *").is_err());
+    }
+
+    #[test]
+    fn test_cron_matches() {
+        let daily = CronSchedule::from_str("0 4 * * *").unwrap();
+        assert!(daily.matches(&at(1, 4, 0)));
+        assert!(!daily.matches(&at(1, 4, 1)));
+        assert!(!daily.matches(&at(1, 5, 0)));
+
+        let mondays = CronSchedule::from_str("30 12 * * 1").unwrap();
+        assert!(mondays.matches(&at(1, 12, 30)));
+        assert!(!mondays.matches(&at(2, 12, 30)));
+
+        // either day field matching is enough when both are restricted
+        let either = CronSchedule::from_str("0 0 2 * 1").unwrap();
+        assert!(either.matches(&at(1, 0, 0)));
+        assert!(either.matches(&at(2, 0, 0)));
+        assert!(!either.matches(&at(3, 0, 0)));
+
+        let offset = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        assert!(daily.matches(&at(1, 2, 0).with_timezone(&offset)));
+
+        // 04:00 in Berlin is 03:00 UTC in winter and 02:00 UTC in summer
+        let berlin = chrono_tz::Europe::Berlin;
+        assert!(daily.matches(
+            &Utc.with_ymd_and_hms(2023, 1, 2, 3, 0, 0)
+                .unwrap()
+                .with_timezone(&berlin)
+        ));
+        assert!(daily.matches(
+            &Utc.with_ymd_and_hms(2023, 7, 3, 2, 0, 0)
+                .unwrap()
+                .with_timezone(&berlin)
+        ));
+    }
+}
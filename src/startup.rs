@@ -0,0 +1,494 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::Duration,
+};
+
+use color_eyre::eyre::{eyre, Context};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::{error, info, warn};
+use ts_rs::TS;
+
+use crate::{
+    error::{Error, ErrorKind},
+    events::CausedBy,
+    prelude::GameInstance,
+    traits::{
+        t_configurable::TConfigurable,
+        t_server::{State, TServer},
+    },
+    types::InstanceUuid,
+    AppState,
+};
+
+/// How long to wait for a dependency to reach `State::Running` before giving up on its dependents
+const DEPENDENCY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// How long to wait for each tier of instances to stop on shutdown before killing them
+const SHUTDOWN_TIER_TIMEOUT: Duration = Duration::from_secs(90);
+/// How long shutdown waits for all instances in total, whatever is left after that is killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+
+#[derive(Serialize, Deserialize, Clone, Debug, TS, PartialEq, Eq, Default)]
+#[ts(export)]
+pub struct StartupConfig {
+    /// instances with a lower priority are started first
+    pub priority: i32,
+    /// instances that must reach `Running` before this one is started
+    pub start_after: Vec<InstanceUuid>,
+}
+
+/// Orders `instances` so that each one comes after the instances it starts after,
+/// breaking ties by priority.
+///
+/// Dependencies outside of `instances` are ignored. Instances caught in a dependency cycle
+/// are appended in priority order.
+pub fn startup_order(
+    instances: &[InstanceUuid],
+    configs: &HashMap<InstanceUuid, StartupConfig>,
+) -> Vec<InstanceUuid> {
+    let mut remaining = instances.to_vec();
+    remaining.sort_by(|a, b| {
+        let priority = |uuid: &InstanceUuid| configs.get(uuid).map(|c| c.priority).unwrap_or(0);
+        priority(a)
+            .cmp(&priority(b))
+            .then_with(|| a.as_ref().cmp(b.as_ref()))
+    });
+    let mut order = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|uuid| {
+            configs
+                .get(uuid)
+                .map(|config| {
+                    config
+                        .start_after
+                        .iter()
+                        .all(|dep| !remaining.contains(dep))
+                })
+                .unwrap_or(true)
+        });
+        match ready {
+            Some(index) => order.push(remaining.remove(index)),
+            None => {
+                warn!("Instances have a startup dependency cycle, ignoring their dependencies");
+                order.append(&mut remaining);
+            }
+        }
+    }
+    order
+}
+
+/// Groups `instances` into tiers that can be stopped in parallel, dependents before
+/// their dependencies.
+///
+/// An instance's tier is one above the highest tier among its dependencies. Dependencies
+/// outside of `instances` or caught in a cycle are ignored.
+pub fn shutdown_tiers(
+    instances: &[InstanceUuid],
+    configs: &HashMap<InstanceUuid, StartupConfig>,
+) -> Vec<Vec<InstanceUuid>> {
+    let mut depths: HashMap<InstanceUuid, usize> = HashMap::new();
+    let mut tiers: Vec<Vec<InstanceUuid>> = Vec::new();
+    for uuid in startup_order(instances, configs) {
+        // startup order puts every dependency first, except for those in a cycle
+        let depth = configs
+            .get(&uuid)
+            .into_iter()
+            .flat_map(|config| config.start_after.iter())
+            .filter_map(|dep| depths.get(dep))
+            .map(|depth| depth + 1)
+            .max()
+            .unwrap_or(0);
+        depths.insert(uuid.clone(), depth);
+        if tiers.len() <= depth {
+            tiers.resize_with(depth + 1, Vec::new);
+        }
+        tiers[depth].push(uuid);
+    }
+    tiers.reverse();
+    tiers
+}
+
+/// Whether making `uuid` start after `start_after` would create a dependency cycle
+fn creates_cycle(
+    configs: &HashMap<InstanceUuid, StartupConfig>,
+    uuid: &InstanceUuid,
+    start_after: &[InstanceUuid],
+) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = start_after.to_vec();
+    while let Some(current) = stack.pop() {
+        if &current == uuid {
+            return true;
+        }
+        if let Some(config) = configs.get(&current) {
+            if visited.insert(current) {
+                stack.extend(config.start_after.iter().cloned());
+            }
+        }
+    }
+    false
+}
+
+pub struct StartupManager {
+    path_to_startup_configs: PathBuf,
+    configs: HashMap<InstanceUuid, StartupConfig>,
+}
+
+impl StartupManager {
+    pub fn new(path_to_startup_configs: PathBuf) -> Self {
+        Self {
+            path_to_startup_configs,
+            configs: HashMap::new(),
+        }
+    }
+
+    pub async fn load_from_file(&mut self) -> Result<(), Error> {
+        if tokio::fs::OpenOptions::new()
+            .read(true)
+            .create(true)
+            .write(true)
+            .open(&self.path_to_startup_configs)
+            .await
+            .context(format!(
+                "Failed to open startup config file at {}",
+                self.path_to_startup_configs.display()
+            ))?
+            .metadata()
+            .await
+            .context(format!(
+                "Failed to get metadata for startup config file at {}",
+                self.path_to_startup_configs.display()
+            ))?
+            .len()
+            == 0
+        {
+            self.configs = HashMap::new();
+        } else {
+            self.configs = serde_json::from_slice(
+                &tokio::fs::read(&self.path_to_startup_configs)
+                    .await
+                    .context(format!(
+                        "Failed to read startup config file at {}",
+                        self.path_to_startup_configs.display()
+                    ))?,
+            )
+            .context(format!(
+                "Failed to parse startup config file at {}",
+                self.path_to_startup_configs.display()
+            ))?;
+        }
+        Ok(())
+    }
+
+    async fn write_to_file(&self) -> Result<(), Error> {
+        let mut file = tokio::fs::File::create(&self.path_to_startup_configs)
+            .await
+            .context(format!(
+                "Failed to create startup config file at {}",
+                self.path_to_startup_configs.display()
+            ))?;
+        file.write_all(
+            serde_json::to_string_pretty(&self.configs)
+                .context("Failed to serialize startup configs")?
+                .as_bytes(),
+        )
+        .await
+        .context(format!(
+            "Failed to write to startup config file at {}",
+            self.path_to_startup_configs.display()
+        ))?;
+        Ok(())
+    }
+
+    pub fn get_config(&self, uuid: &InstanceUuid) -> StartupConfig {
+        self.configs.get(uuid).cloned().unwrap_or_default()
+    }
+
+    pub async fn set_config(
+        &mut self,
+        uuid: InstanceUuid,
+        config: StartupConfig,
+    ) -> Result<(), Error> {
+        if creates_cycle(&self.configs, &uuid, &config.start_after) {
+            return Err(Error {
+                kind: ErrorKind::BadRequest,
+                source: eyre!("Instances cannot depend on each other in a cycle"),
+            });
+        }
+        let old_config = self.configs.insert(uuid.clone(), config);
+        if let Err(e) = self.write_to_file().await {
+            match old_config {
+                Some(old_config) => self.configs.insert(uuid, old_config),
+                None => self.configs.remove(&uuid),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub async fn remove_config(&mut self, uuid: &InstanceUuid) -> Result<(), Error> {
+        if let Some(old_config) = self.configs.remove(uuid) {
+            if let Err(e) = self.write_to_file().await {
+                self.configs.insert(uuid.clone(), old_config);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn startup_order(&self, instances: &[InstanceUuid]) -> Vec<InstanceUuid> {
+        startup_order(instances, &self.configs)
+    }
+
+    pub fn shutdown_tiers(&self, instances: &[InstanceUuid]) -> Vec<Vec<InstanceUuid>> {
+        shutdown_tiers(instances, &self.configs)
+    }
+}
+
+async fn wait_until_running(instance: &GameInstance) -> bool {
+    let wait = async {
+        loop {
+            match instance.state().await {
+                State::Running => return true,
+                State::Starting => tokio::time::sleep(Duration::from_secs(1)).await,
+                // it stopped or crashed without ever reaching running
+                _ => return false,
+            }
+        }
+    };
+    tokio::time::timeout(DEPENDENCY_TIMEOUT, wait)
+        .await
+        .unwrap_or(false)
+}
+
+/// Starts every instance with `auto_start` set, in startup order and staggered by the
+/// configured delay. An instance is skipped if one of its dependencies fails to start
+pub async fn auto_start_instances(state: AppState) {
+    let instances: HashMap<InstanceUuid, GameInstance> = state.instances.lock().await.clone();
+    let mut auto_start = Vec::new();
+    for (uuid, instance) in instances.iter() {
+        if instance.auto_start().await {
+            auto_start.push(uuid.clone());
+        }
+    }
+    let (order, configs) = {
+        let startup_manager = state.startup_manager.lock().await;
+        let order = startup_manager.startup_order(&auto_start);
+        let configs: HashMap<InstanceUuid, StartupConfig> = order
+            .iter()
+            .map(|uuid| (uuid.clone(), startup_manager.get_config(uuid)))
+            .collect();
+        (order, configs)
+    };
+    let delay = Duration::from_secs(state.global_settings.lock().await.auto_start_delay() as u64);
+    let mut failed: HashSet<InstanceUuid> = HashSet::new();
+    let mut started_any = false;
+    for (i, uuid) in order.iter().enumerate() {
+        let mut instance = instances[uuid].clone();
+        let name = instance.name().await;
+        let mut dependencies_ready = true;
+        for dependency in configs[uuid].start_after.iter() {
+            let dependency_instance = match instances.get(dependency) {
+                Some(dependency_instance) if order[..i].contains(dependency) => dependency_instance,
+                // only dependencies that were auto started before this instance are waited on
+                _ => continue,
+            };
+            if failed.contains(dependency) || !wait_until_running(dependency_instance).await {
+                dependencies_ready = false;
+                break;
+            }
+        }
+        if !dependencies_ready {
+            error!(
+                "Not auto starting instance {} as one of its dependencies failed to start",
+                name
+            );
+            failed.insert(uuid.clone());
+            continue;
+        }
+        // skipped instances never started, so there is nothing to stagger against
+        if started_any {
+            tokio::time::sleep(delay).await;
+        }
+        started_any = true;
+        info!("Auto starting instance {}", name);
+        if let Err(e) = instance.start(CausedBy::System, false).await {
+            error!("Failed to start instance {}: {:?}", name, e);
+            failed.insert(uuid.clone());
+        }
+    }
+}
+
+/// Stops `instance`, waiting for it to finish starting or stopping first
+async fn stop_for_shutdown(uuid: &InstanceUuid, mut instance: GameInstance) {
+    loop {
+        match instance.state().await {
+            State::Stopped | State::Error => return,
+            State::Running => {
+                if let Err(e) = instance.stop(CausedBy::System, true).await {
+                    error!("Failed to stop instance {} : {}", uuid, e);
+                }
+            }
+            // a starting instance can't be stopped and a stopping one is already on its way
+            State::Starting | State::Stopping => {}
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Kills the instances among `uuids` that are not stopped yet
+async fn kill_for_shutdown(
+    instances: &HashMap<InstanceUuid, GameInstance>,
+    uuids: &[InstanceUuid],
+) {
+    for uuid in uuids {
+        let mut instance = instances[uuid].clone();
+        if matches!(instance.state().await, State::Stopped | State::Error) {
+            continue;
+        }
+        if let Err(e) = instance.kill(CausedBy::System).await {
+            error!(
+                "Failed to kill instance {} : {}. Instance may need manual cleanup",
+                uuid, e
+            );
+        }
+    }
+}
+
+/// Stops every instance on shutdown, dependents before their dependencies. Each tier is
+/// stopped in parallel and whatever is still running when a tier times out is killed.
+/// Once `SHUTDOWN_TIMEOUT` runs out, every instance left is killed without waiting for its tier
+pub async fn stop_all_instances(state: AppState) {
+    let instances: HashMap<InstanceUuid, GameInstance> = state.instances.lock().await.clone();
+    let uuids: Vec<InstanceUuid> = instances.keys().cloned().collect();
+    let tiers = state.startup_manager.lock().await.shutdown_tiers(&uuids);
+    let deadline = tokio::time::Instant::now() + SHUTDOWN_TIMEOUT;
+    for (i, tier) in tiers.iter().enumerate() {
+        let stops = tier
+            .iter()
+            .map(|uuid| stop_for_shutdown(uuid, instances[uuid].clone()));
+        let tier_deadline = deadline.min(tokio::time::Instant::now() + SHUTDOWN_TIER_TIMEOUT);
+        if tokio::time::timeout_at(tier_deadline, futures::future::join_all(stops))
+            .await
+            .is_ok()
+        {
+            continue;
+        }
+        if tier_deadline < deadline {
+            error!("Timed out waiting for instances to stop, killing the rest of the tier");
+            kill_for_shutdown(&instances, tier).await;
+            continue;
+        }
+        error!("Timed out waiting for all instances to stop, killing the remaining instances");
+        kill_for_shutdown(&instances, &tiers[i..].concat()).await;
+        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uuid(name: &str) -> InstanceUuid {
+        InstanceUuid::from(name.to_string())
+    }
+
+    fn config(priority: i32, start_after: &[&str]) -> StartupConfig {
+        StartupConfig {
+            priority,
+            start_after: start_after.iter().map(|name| uuid(name)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_startup_order() {
+        let instances = vec![uuid("lobby"), uuid("proxy"), uuid("survival")];
+        let mut configs = HashMap::new();
+        assert_eq!(
+            startup_order(&instances, &configs),
+            vec![uuid("lobby"), uuid("proxy"), uuid("survival")]
+        );
+
+        configs.insert(uuid("proxy"), config(-1, &[]));
+        assert_eq!(
+            startup_order(&instances, &configs),
+            vec![uuid("proxy"), uuid("lobby"), uuid("survival")]
+        );
+
+        // dependencies win over priority
+        configs.insert(uuid("proxy"), config(-1, &["lobby", "survival"]));
+        configs.insert(uuid("survival"), config(5, &[]));
+        assert_eq!(
+            startup_order(&instances, &configs),
+            vec![uuid("lobby"), uuid("survival"), uuid("proxy")]
+        );
+
+        // dependencies that aren't being started are ignored
+        configs.insert(uuid("lobby"), config(0, &["creative"]));
+        assert_eq!(
+            startup_order(&instances, &configs),
+            vec![uuid("lobby"), uuid("survival"), uuid("proxy")]
+        );
+
+        // cycles fall back to priority order
+        configs.insert(uuid("lobby"), config(0, &["proxy"]));
+        assert_eq!(
+            startup_order(&instances, &configs),
+            vec![uuid("survival"), uuid("proxy"), uuid("lobby")]
+        );
+    }
+
+    #[test]
+    fn test_shutdown_tiers() {
+        let instances = vec![
+            uuid("creative"),
+            uuid("lobby"),
+            uuid("proxy"),
+            uuid("survival"),
+        ];
+        let mut configs = HashMap::new();
+        assert_eq!(
+            shutdown_tiers(&instances, &configs),
+            vec![vec![
+                uuid("creative"),
+                uuid("lobby"),
+                uuid("proxy"),
+                uuid("survival")
+            ]]
+        );
+
+        configs.insert(uuid("lobby"), config(0, &["survival"]));
+        configs.insert(uuid("proxy"), config(0, &["lobby", "creative"]));
+        assert_eq!(
+            shutdown_tiers(&instances, &configs),
+            vec![
+                vec![uuid("proxy")],
+                vec![uuid("lobby")],
+                vec![uuid("creative"), uuid("survival")]
+            ]
+        );
+
+        // cycles are stopped together
+        configs.insert(uuid("survival"), config(0, &["proxy"]));
+        assert_eq!(shutdown_tiers(&instances, &configs).concat().len(), 4);
+    }
+
+    #[test]
+    fn test_creates_cycle() {
+        let mut configs = HashMap::new();
+        configs.insert(uuid("proxy"), config(0, &["lobby"]));
+        configs.insert(uuid("lobby"), config(0, &["survival"]));
+        assert!(creates_cycle(&configs, &uuid("survival"), &[uuid("proxy")]));
+        assert!(creates_cycle(
+            &configs,
+            &uuid("survival"),
+            &[uuid("survival")]
+        ));
+        assert!(!creates_cycle(
+            &configs,
+            &uuid("creative"),
+            &[uuid("proxy")]
+        ));
+    }
+}
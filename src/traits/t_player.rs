@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 use crate::error::{Error, ErrorKind};
+use crate::events::CausedBy;
 use crate::implementations::generic::player::GenericPlayer;
 use crate::minecraft::player::MinecraftPlayer;
 use crate::traits::GameInstance;
@@ -63,4 +64,67 @@ pub trait TPlayerManagement {
             source: eyre!("Setting max player count is unsupported for this instance"),
         })
     }
+
+    async fn kick_player(
+        &mut self,
+        _player_name: &str,
+        _reason: Option<String>,
+        _caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("Kicking players is unsupported for this instance"),
+        })
+    }
+
+    /// `duration` is in seconds, `None` bans permanently
+    async fn ban_player(
+        &mut self,
+        _player_name: &str,
+        _reason: Option<String>,
+        _duration: Option<u32>,
+        _caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("Banning players is unsupported for this instance"),
+        })
+    }
+
+    async fn pardon_player(
+        &mut self,
+        _player_name: &str,
+        _caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("Pardoning players is unsupported for this instance"),
+        })
+    }
+
+    async fn op_player(&mut self, _player_name: &str, _caused_by: CausedBy) -> Result<(), Error> {
+        Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("Making players operators is unsupported for this instance"),
+        })
+    }
+
+    async fn deop_player(&mut self, _player_name: &str, _caused_by: CausedBy) -> Result<(), Error> {
+        Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("Removing operators is unsupported for this instance"),
+        })
+    }
+
+    async fn message_player(
+        &mut self,
+        _player_name: &str,
+        _message: &str,
+        _caused_by: CausedBy,
+    ) -> Result<(), Error> {
+        Err(Error {
+            kind: ErrorKind::UnsupportedOperation,
+            source: eyre!("Messaging players is unsupported for this instance"),
+        })
+    }
 }
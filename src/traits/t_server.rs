@@ -21,8 +21,10 @@ pub enum State {
 pub enum StateAction {
     UserStart,
     UserStop,
+    UserKill,
     InstanceStart,
     InstanceStop,
+    InstanceCrash,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -52,6 +54,22 @@ pub struct MonitorReport {
     pub disk_usage: Option<DiskUsage>,
     pub cpu_usage: Option<f32>,
     pub start_time: Option<u64>,
+    #[serde(default)]
+    pub tick: Option<TickReport>,
+}
+
+/// Whether the game keeps up with its tick rate, only reported by Minecraft instances
+#[derive(Debug, Clone, Serialize, Deserialize, TS, Default, PartialEq)]
+#[ts(export)]
+pub struct TickReport {
+    /// Ticks per second, 20 when the server keeps up
+    pub tps: Option<f32>,
+    /// Milliseconds per tick, above 50 the server falls behind
+    pub mspt: Option<f32>,
+    /// "Can't keep up!" warnings in the last 5 minutes
+    pub lag_warnings: u32,
+    /// Ticks skipped over those warnings
+    pub ticks_behind: u64,
 }
 
 impl ToString for State {
@@ -81,6 +99,14 @@ impl State {
             }
             (_, StateAction::InstanceStart) => Ok(State::Running),
             (_, StateAction::InstanceStop) => Ok(State::Stopped),
+            (_, StateAction::InstanceCrash) => Ok(State::Error),
+            (State::Stopped, StateAction::UserKill) => {
+                Err(eyre!("Cannot kill an instance that is already stopped"))
+            }
+            (State::Error, StateAction::UserKill) => {
+                Err(eyre!("Cannot kill an instance that has crashed"))
+            }
+            (_, StateAction::UserKill) => Ok(State::Stopping),
             (State::Running, StateAction::UserStart) => {
                 Err(eyre!("Cannot start an instance that is already running"))
             }
@@ -95,8 +121,9 @@ impl State {
             (State::Stopped, StateAction::UserStop) => {
                 Err(eyre!("Cannot stop an instance that is already stopped"))
             }
-            (State::Error, StateAction::UserStart) => todo!(),
-            (State::Error, StateAction::UserStop) => todo!(),
+            (State::Error, StateAction::UserStart) => Ok(State::Starting),
+            // the process is already gone, stopping only clears the error
+            (State::Error, StateAction::UserStop) => Ok(State::Stopped),
         }?;
         if let Some(on_transit) = on_transit {
             on_transit(state);
@@ -128,3 +155,24 @@ pub trait TServer {
     async fn send_command(&self, command: &str, caused_by: CausedBy) -> Result<(), Error>;
     async fn monitor(&self) -> MonitorReport;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_state_transitions() {
+        let mut state = State::Running;
+        state
+            .try_transition(StateAction::InstanceCrash, None)
+            .unwrap();
+        assert_eq!(state, State::Error);
+        assert!(state.try_new_state(StateAction::UserKill, None).is_err());
+        assert_eq!(
+            state.try_new_state(StateAction::UserStart, None).unwrap(),
+            State::Starting
+        );
+        state.try_transition(StateAction::UserStop, None).unwrap();
+        assert_eq!(state, State::Stopped);
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::path::PathBuf;
 
 use crate::migration::DotLodestoneConfigV043;
 use crate::traits::t_configurable::GameType;
@@ -123,6 +124,16 @@ pub struct LodestoneMetadata {
     pub semver: semver::Version,
 }
 
+/// An instance found on disk that could not be restored when the core started
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export)]
+pub struct InstanceRestoreFailure {
+    pub path: PathBuf,
+    pub uuid: Option<InstanceUuid>,
+    pub game_type: Option<GameType>,
+    pub error: String,
+}
+
 /// A marker file to indicate to lodestone that the directory contains a lodestone instance
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
 #[ts(export)]